@@ -0,0 +1,170 @@
+use std::time::Instant;
+
+use glam::Vec2;
+
+use mylib::scene::Scene;
+use mylib::simulation::grid::Grid;
+use mylib::simulation::node::Node;
+
+const OBJECT_SIZES: [usize; 7] = [15, 25, 50, 75, 100, 150, 200];
+const REPEATS: usize = 50;
+
+/// Grid used before `simulation::grid::Grid`, kept for comparison
+mod nested {
+    use glam::Vec2;
+    use mylib::simulation::node::Node;
+
+    pub struct Grid {
+        pub top_left: Vec2,
+        pub cells: Vec<Vec<Vec<usize>>>,
+        pub cell_size: f32,
+        pub cell_count_x: usize,
+        pub cell_count_y: usize,
+    }
+
+    impl Grid {
+        pub fn get_cell_index(&self, point: &Vec2) -> (usize, usize) {
+            let x = ((point.x - self.top_left.x) / self.cell_size) as i32;
+            let y = ((self.top_left.y - point.y) / self.cell_size) as i32;
+            (
+                x.clamp(0, self.cell_count_x as i32 - 1) as usize,
+                y.clamp(0, self.cell_count_y as i32 - 1) as usize,
+            )
+        }
+
+        pub fn get_node_indexes_from_neighbours(&self, point: &Vec2) -> Vec<usize> {
+            let (x, y) = self.get_cell_index(point);
+            let mut neighbours: Vec<usize> = Vec::new();
+            for (dx, dy) in [(0, 0), (0, -1), (0, 1), (-1, 0), (1, 0), (-1, -1), (-1, 1), (1, -1), (1, 1)] {
+                let (cx, cy) = (x as i32 + dx, y as i32 + dy);
+                if cx >= 0 && cy >= 0 && cx < self.cell_count_x as i32 && cy < self.cell_count_y as i32 {
+                    neighbours.append(&mut self.cells[cx as usize][cy as usize].clone());
+                }
+            }
+            neighbours
+        }
+
+        pub fn new(nodes: &[Node], cell_size: f32) -> Grid {
+            const MAX_SIZE: f32 = 2.0;
+            let mut top_left = Vec2::new(f32::MAX, f32::MIN);
+            let mut bottom_right = Vec2::new(f32::MIN, f32::MAX);
+            nodes.iter().for_each(|n| {
+                top_left.x = top_left.x.min(n.position.x);
+                top_left.y = top_left.y.max(n.position.y);
+                bottom_right.x = bottom_right.x.max(n.position.x);
+                bottom_right.y = bottom_right.y.min(n.position.y);
+            });
+            top_left.x = top_left.x.max(-MAX_SIZE);
+            top_left.y = top_left.y.min(MAX_SIZE);
+            bottom_right.x = bottom_right.x.min(MAX_SIZE);
+            bottom_right.y = bottom_right.y.max(-MAX_SIZE);
+
+            let cell_count_x = ((bottom_right.x - top_left.x) / cell_size).ceil() as usize;
+            let cell_count_y = ((top_left.y - bottom_right.y) / cell_size).ceil() as usize;
+            let mut grid = Grid {
+                top_left,
+                cells: vec![vec![Vec::new(); cell_count_y]; cell_count_x],
+                cell_size,
+                cell_count_x,
+                cell_count_y,
+            };
+            nodes.iter().enumerate().for_each(|(index, n)| {
+                if n.is_boundary {
+                    let (x, y) = grid.get_cell_index(&n.position);
+                    grid.cells[x][y].push(index);
+                }
+            });
+            grid
+        }
+    }
+}
+
+/// Moves nodes a little so that some of them change cells, like in one update of the simulation
+fn shake(nodes: &mut [Node], step: usize, amplitude: f32) {
+    nodes.iter_mut().enumerate().for_each(|(i, n)| {
+        let phase = (i * 7 + step * 13) as f32;
+        n.position += amplitude * Vec2::new(phase.sin(), phase.cos());
+    });
+}
+
+fn count_neighbours_nested(nodes: &[Node], grid: &nested::Grid) -> usize {
+    nodes.iter().filter(|n| n.is_boundary).map(|n| grid.get_node_indexes_from_neighbours(&n.position).len()).sum()
+}
+
+fn count_neighbours_flat(nodes: &[Node], grid: &Grid) -> usize {
+    nodes.iter().filter(|n| n.is_boundary).map(|n| grid.neighbours(&n.position).count()).sum()
+}
+
+fn scene(object_size: usize) -> Scene {
+    let mut scene = mylib::scene::three_squares::generate(object_size);
+    scene.update_boundary_nodes();
+    scene
+}
+
+fn main() {
+    println!("Grid build and neighbour search of all boundary nodes, {} repeats, times in ms", REPEATS);
+    println!("{:>6} {:>8} {:>10} {:>10} {:>8} {:>12} {:>8}", "size", "nodes", "nested", "flat", "speedup", "incremental", "speedup");
+
+    for object_size in OBJECT_SIZES {
+        let scene = scene(object_size);
+        let cell_size = scene.object_repulsion_dx * 2.0;
+        let amplitude = 0.05 * scene.object_repulsion_dx;
+
+        // cells of the grids are aligned differently, but both must find all nodes closer than cell size
+        let nested_grid = nested::Grid::new(&scene.nodes, cell_size);
+        let flat_grid = Grid::new(&scene.nodes, cell_size);
+        let within_cell_size = |n: &Node, mut found: Vec<usize>| {
+            found.retain(|j| scene.nodes[*j].position.distance(n.position) < cell_size);
+            found.sort();
+            found
+        };
+        scene.nodes.iter().filter(|n| n.is_boundary).for_each(|n| {
+            assert_eq!(
+                within_cell_size(n, nested_grid.get_node_indexes_from_neighbours(&n.position)),
+                within_cell_size(n, flat_grid.get_node_indexes_from_neighbours(&n.position)),
+            );
+        });
+
+        let mut nodes = scene.nodes.clone();
+        let mut found = 0;
+        let start = Instant::now();
+        for step in 0..REPEATS {
+            shake(&mut nodes, step, amplitude);
+            let grid = nested::Grid::new(&nodes, cell_size);
+            found += count_neighbours_nested(&nodes, &grid);
+        }
+        let nested_time = start.elapsed().as_secs_f64() * 1000.0;
+
+        let mut nodes = scene.nodes.clone();
+        let start = Instant::now();
+        for step in 0..REPEATS {
+            shake(&mut nodes, step, amplitude);
+            let grid = Grid::new(&nodes, cell_size);
+            found += count_neighbours_flat(&nodes, &grid);
+        }
+        let flat_time = start.elapsed().as_secs_f64() * 1000.0;
+
+        let mut nodes = scene.nodes.clone();
+        let mut grid = Grid::new(&nodes, cell_size);
+        let start = Instant::now();
+        for step in 0..REPEATS {
+            shake(&mut nodes, step, amplitude);
+            grid.update(&nodes, cell_size);
+            found += count_neighbours_flat(&nodes, &grid);
+        }
+        let incremental_time = start.elapsed().as_secs_f64() * 1000.0;
+
+        println!(
+            "{:>6} {:>8} {:>10.2} {:>10.2} {:>7.1}x {:>12.2} {:>7.1}x",
+            object_size,
+            scene.nodes.len(),
+            nested_time,
+            flat_time,
+            nested_time / flat_time,
+            incremental_time,
+            nested_time / incremental_time,
+        );
+        // keeps the searches from being optimised away
+        assert!(found > 0);
+    }
+}
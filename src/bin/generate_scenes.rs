@@ -10,6 +10,7 @@ fn main() {
         (mylib::scene::scene03::generate(), "scene03".to_string()),
         (mylib::scene::scene04::generate(), "scene04".to_string()),
         (mylib::scene::scene05::generate(), "scene05".to_string()),
+        (mylib::scene::container::generate(), "container".to_string()),
     ];
 
     let object_sizes = [3, 5, 9, 13, 15, 19, 21, 25, 30, 35, 40, 45, 50, 55, 60];
@@ -22,9 +23,18 @@ fn main() {
 
     scenes_to_generate.append(&mut multiple);
 
+    // compile scene descriptions
+    std::fs::read_dir("scenes").unwrap().for_each(|entry| {
+        let path = entry.unwrap().path();
+        if path.extension().map_or(false, |e| e == "ron") {
+            let name = path.file_stem().unwrap().to_string_lossy().to_string();
+            let scene = mylib::scene::load_from_file(&path.to_string_lossy()).unwrap();
+            scenes_to_generate.push((scene, name));
+        }
+    });
+
     scenes_to_generate.par_iter().for_each(|(scene, name)| {
-        let f = std::fs::File::create(format!("scenes/{}.bincode", name)).unwrap();
-        bincode::serialize_into(f, &scene).unwrap();
+        mylib::scene::format::save(&format!("scenes/{}.bincode", name), scene).unwrap();
     });
 
     // let f2 = std::fs::File::open("scenes/default.bincode").unwrap();
@@ -0,0 +1,388 @@
+use mylib::scene;
+use mylib::simulation::{self, checkpoint::Checkpoint, manager::{SimulationSettings, SimulationEngineEnum}, material::MaterialTable, integrator::Integrator, timestep::AdaptiveDtMethod, forces::CutoffShift};
+
+const USAGE: &str = "Usage: headless <scene> [options]
+
+Runs scene without opening a window and writes final state, energy log, log of broken bonds,
+fragment history and checkpoint.
+<scene> is either a path to a .bincode or .ron file or a name of a file inside scenes/ directory.
+Simulation written to a .checkpoint file is resumed with its settings, options given
+on the command line replace them.
+
+Options:
+    --duration <seconds>        simulated time to run until, including time before resumed checkpoint (default 1.0)
+    --dt <seconds>              simulation time step (default 0.00002)
+    --steps-per-frame <n>       simulation steps per update (default 5)
+    --engine <name>             cpu, multithread, singlekernel or opencl (default singlekernel)
+    --integrator <name>         euler, position-verlet, velocity-verlet, rk4 or implicit (default velocity-verlet)
+    --grid / --no-grid          use grid for collision detection (default on)
+    --cell-size <size>          grid cell size (default 2.5 * largest repulsion dx)
+    --neighbour-list / --no-neighbour-list
+                                with grid rebuild collisions only after nodes moved half of the skin (default on)
+    --neighbour-skin <d>        skin of the neighbour list (default 0.5 * largest repulsion dx)
+    --repulsion-cutoff <c>      nodes farther than c * repulsion dx do not repel, replaces cell size (default from scene)
+    --no-repulsion-cutoff       repulsion of unlimited range
+    --cutoff-shift <name>       truncated, shifted-potential or shifted-force (default from scene)
+    --backup / --no-backup      error correction with scene backups (default on)
+    --backup-interval <seconds> time between backups (default 0.1)
+    --auto-dt / --no-auto-dt    increase dt after each successful backup (default off)
+    --auto-dt-factor <factor>   dt multiplier used by auto dt (default 1.1)
+    --adaptive-dt <method>      choose dt before every step by cfl or step-doubling (default off)
+    --courant <fraction>        fraction of the shortest bond a node may travel in a step with cfl (default 0.01)
+    --error-tolerance <tol>     allowed step error relative to the shortest bond with step-doubling (default 0.001)
+    --min-dt <seconds>          smallest dt chosen by adaptive dt (default 0.000001)
+    --max-dt <seconds>          largest dt chosen by adaptive dt (default 0.00005)
+    --healing / --no-healing    bond nodes that come back into contact (default off)
+    --healing-distance <d>      largest distance of nodes to be bonded (default object repulsion dx)
+    --healing-max-speed <v>     largest relative speed of nodes to be bonded (default 0.5)
+    --welding / --no-welding    allow healing to bond different objects (default off)
+    --self-collision / --no-self-collision
+                                boundary nodes of the same object repel each other (default off)
+    --self-collision-hops <k>   nodes within k bonds of each other do not self-collide (default 3)
+    --deterministic             sum forces in fixed order, cpu engines give bit-identical results (default off)
+    --log-interval <seconds>    time between energy log records (default 0.01)
+    --no-log                    do not write energy, fracture and fragment logs
+    --checkpoint-interval <s>   write checkpoint every interval of simulated time, not only at the end
+    --output <directory>        directory for output files (default data)";
+
+fn parse_engine(name: &str) -> SimulationEngineEnum {
+    match name {
+        "cpu" => SimulationEngineEnum::Cpu,
+        "multithread" => SimulationEngineEnum::CpuMultithread,
+        "singlekernel" => SimulationEngineEnum::CpuMultithreadSingleKernel,
+        "opencl" => SimulationEngineEnum::OpenCl,
+        _ => exit_with_usage(&format!("Unknown engine: {}", name)),
+    }
+}
+
+fn parse_integrator(name: &str) -> Integrator {
+    Integrator::from_name(name).unwrap_or_else(|| exit_with_usage(&format!("Unknown integrator: {}", name)))
+}
+
+fn parse_cutoff_shift(name: &str) -> CutoffShift {
+    CutoffShift::from_name(name).unwrap_or_else(|| exit_with_usage(&format!("Unknown cutoff shift: {}", name)))
+}
+
+fn parse_adaptive_dt_method(name: &str) -> AdaptiveDtMethod {
+    AdaptiveDtMethod::from_name(name).unwrap_or_else(|| exit_with_usage(&format!("Unknown adaptive dt method: {}", name)))
+}
+
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    std::process::exit(1);
+}
+
+fn next_value<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>, flag: &str) -> T {
+    let value = args.next().unwrap_or_else(|| exit_with_usage(&format!("Missing value for {}", flag)));
+    value.parse().unwrap_or_else(|_| exit_with_usage(&format!("Invalid value for {}: {}", flag, value)))
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+
+    let scene_arg = match args.next() {
+        Some(arg) if arg != "--help" && arg != "-h" => arg,
+        _ => exit_with_usage("Missing scene argument"),
+    };
+
+    let resume = scene_arg.ends_with(".checkpoint");
+    let scene_path = if resume { scene_arg.clone() } else { scene::scene_path_from_argument(&scene_arg) };
+    let scene_name = std::path::Path::new(&scene_path).file_stem().unwrap().to_string_lossy().to_string();
+
+    let (mut scene, checkpoint) = if resume {
+        println!("Trying to read checkpoint from file: {}", scene_path);
+        let checkpoint = simulation::checkpoint::load(&scene_path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+        (checkpoint.scene.clone(), Some(checkpoint))
+    } else {
+        println!("Trying to read scene from file: {}", scene_path);
+        let scene = scene::load_from_file(&scene_path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+        (scene, None)
+    };
+
+    let mut settings = match &checkpoint {
+        Some(checkpoint) => checkpoint.settings,
+        None => SimulationSettings {
+            dt: 0.00002,
+            steps_per_frame: 5,
+            engine: SimulationEngineEnum::CpuMultithreadSingleKernel,
+            integrator: Integrator::VelocityVerlet,
+            use_grid: true,
+            cell_size: MaterialTable::new(&scene).max_repulsion_dx() * 2.5,
+            use_neighbour_list: true,
+            neighbour_skin: MaterialTable::new(&scene).max_repulsion_dx() * 0.5,
+            log_to_csv: true,
+            log_interval: 0.01,
+            use_backup: true,
+            backup_interval: 0.1,
+            use_auto_dt: false,
+            auto_dt_factor: 1.1,
+            use_adaptive_dt: false,
+            adaptive_dt_method: AdaptiveDtMethod::Cfl,
+            courant_number: 0.01,
+            error_tolerance: 0.001,
+            min_dt: 0.000001,
+            max_dt: simulation::manager::MAX_DT,
+            use_healing: false,
+            healing_distance: scene.object_repulsion_dx,
+            healing_max_speed: 0.5,
+            use_welding: false,
+            use_self_collision: false,
+            self_collision_hops: 3,
+            deterministic: false,
+        },
+    };
+    let mut repulsion = scene.force_field.repulsion;
+    let mut duration: f32 = 1.0;
+    let mut output_dir = "data".to_string();
+    let mut checkpoint_interval: Option<f32> = None;
+
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--duration" => duration = next_value(&mut args, &flag),
+            "--dt" => settings.dt = next_value(&mut args, &flag),
+            "--steps-per-frame" => settings.steps_per_frame = next_value(&mut args, &flag),
+            "--engine" => settings.engine = parse_engine(&next_value::<String>(&mut args, &flag)),
+            "--integrator" => settings.integrator = parse_integrator(&next_value::<String>(&mut args, &flag)),
+            "--grid" => settings.use_grid = true,
+            "--no-grid" => settings.use_grid = false,
+            "--cell-size" => settings.cell_size = next_value(&mut args, &flag),
+            "--neighbour-list" => settings.use_neighbour_list = true,
+            "--no-neighbour-list" => settings.use_neighbour_list = false,
+            "--neighbour-skin" => settings.neighbour_skin = next_value(&mut args, &flag),
+            "--repulsion-cutoff" => repulsion.cutoff = Some(next_value(&mut args, &flag)),
+            "--no-repulsion-cutoff" => repulsion.cutoff = None,
+            "--cutoff-shift" => repulsion.shift = parse_cutoff_shift(&next_value::<String>(&mut args, &flag)),
+            "--backup" => settings.use_backup = true,
+            "--no-backup" => settings.use_backup = false,
+            "--backup-interval" => settings.backup_interval = next_value(&mut args, &flag),
+            "--auto-dt" => settings.use_auto_dt = true,
+            "--no-auto-dt" => settings.use_auto_dt = false,
+            "--auto-dt-factor" => settings.auto_dt_factor = next_value(&mut args, &flag),
+            "--adaptive-dt" => {
+                settings.use_adaptive_dt = true;
+                settings.adaptive_dt_method = parse_adaptive_dt_method(&next_value::<String>(&mut args, &flag));
+            }
+            "--courant" => settings.courant_number = next_value(&mut args, &flag),
+            "--error-tolerance" => settings.error_tolerance = next_value(&mut args, &flag),
+            "--min-dt" => settings.min_dt = next_value(&mut args, &flag),
+            "--max-dt" => settings.max_dt = next_value(&mut args, &flag),
+            "--healing" => settings.use_healing = true,
+            "--no-healing" => settings.use_healing = false,
+            "--healing-distance" => settings.healing_distance = next_value(&mut args, &flag),
+            "--healing-max-speed" => settings.healing_max_speed = next_value(&mut args, &flag),
+            "--welding" => settings.use_welding = true,
+            "--no-welding" => settings.use_welding = false,
+            "--self-collision" => settings.use_self_collision = true,
+            "--no-self-collision" => settings.use_self_collision = false,
+            "--self-collision-hops" => settings.self_collision_hops = next_value(&mut args, &flag),
+            "--deterministic" => settings.deterministic = true,
+            "--log-interval" => settings.log_interval = next_value(&mut args, &flag),
+            "--no-log" => settings.log_to_csv = false,
+            "--checkpoint-interval" => checkpoint_interval = Some(next_value(&mut args, &flag)),
+            "--output" => output_dir = next_value(&mut args, &flag),
+            _ => exit_with_usage(&format!("Unknown option: {}", flag)),
+        }
+    }
+
+    #[cfg(not(feature = "opencl3"))]
+    if settings.engine == SimulationEngineEnum::OpenCl {
+        exit_with_usage("OpenCL engine is not available, build with opencl3 feature");
+    }
+
+    println!("{:?}", settings);
+    println!("{:?}", repulsion);
+
+    std::fs::create_dir_all(&output_dir).unwrap();
+
+    let mut csv_writer = if settings.log_to_csv {
+        let log_path = format!("{}/{}_energy.csv", output_dir, scene_name);
+        let mut writer = csv::Writer::from_path(log_path).unwrap();
+        writer.write_record(["time", "dt", "kinetic", "gravity", "lennjon", "wallrep", "objrepu", "max_pressure"]).unwrap();
+        Some(writer)
+    } else {
+        None
+    };
+
+    let mut collider_writer = if settings.log_to_csv && !scene.environment.colliders.is_empty() {
+        let colliders_path = format!("{}/{}_colliders.csv", output_dir, scene_name);
+        let mut writer = csv::Writer::from_path(colliders_path).unwrap();
+        writer.write_record(["time", "collider", "x", "y", "angle", "fx", "fy", "torque", "impulse_x", "impulse_y"]).unwrap();
+        Some(writer)
+    } else {
+        None
+    };
+
+    scene.force_field.repulsion = repulsion;
+    let simulation_manager = match checkpoint {
+        Some(mut checkpoint) => {
+            checkpoint.scene_backup.force_field.repulsion = repulsion;
+            simulation::manager::SimulationManager::from_checkpoint(Checkpoint { settings, scene, ..checkpoint })
+        }
+        None => simulation::manager::SimulationManager::new(settings, scene),
+    };
+    let mut simulation_manager = simulation_manager.unwrap_or_else(|errors| {
+        eprintln!("invalid scene:\n{}", scene::validation::format_errors(&errors));
+        std::process::exit(1);
+    });
+    let checkpoint_path = format!("{}/{}.checkpoint", output_dir, scene_name);
+
+    let timer_start = std::time::Instant::now();
+    let mut current_log_dt = settings.log_interval;
+    let mut current_checkpoint_dt = 0.0;
+
+    while simulation_manager.total_simulation_time < duration {
+        simulation_manager.update();
+        current_log_dt += simulation_manager.last_step_dt();
+        current_checkpoint_dt += simulation_manager.last_step_dt();
+
+        if let Some(interval) = checkpoint_interval {
+            if current_checkpoint_dt > interval {
+                simulation_manager.save_checkpoint(&checkpoint_path).unwrap();
+                current_checkpoint_dt = 0.0;
+            }
+        }
+
+        if let Some(writer) = csv_writer.as_mut() {
+            if current_log_dt > simulation_manager.settings.log_interval {
+                let (kinetic, gravity, lennjon, wallrep, objrepu) =
+                    simulation_manager.total_energy();
+                let max_pressure = simulation::pressure::max_pressure(&simulation_manager.scene.nodes, &simulation_manager.connections_structure, &simulation_manager.scene.force_field);
+
+                writer.write_record([
+                    simulation_manager.total_simulation_time.to_string(),
+                    simulation_manager.settings.dt.to_string(),
+                    kinetic.to_string(),
+                    gravity.to_string(),
+                    lennjon.to_string(),
+                    wallrep.to_string(),
+                    objrepu.to_string(),
+                    max_pressure.to_string(),
+                ]).unwrap();
+
+                if let Some(writer) = collider_writer.as_mut() {
+                    simulation_manager.scene.environment.colliders.iter().enumerate().for_each(|(i, c)| {
+                        writer.write_record([
+                            simulation_manager.total_simulation_time.to_string(),
+                            i.to_string(),
+                            c.state.position.x.to_string(),
+                            c.state.position.y.to_string(),
+                            c.state.angle.to_string(),
+                            c.state.force.x.to_string(),
+                            c.state.force.y.to_string(),
+                            c.state.torque.to_string(),
+                            c.state.impulse.x.to_string(),
+                            c.state.impulse.y.to_string(),
+                        ]).unwrap();
+                    });
+                }
+
+                current_log_dt = 0.0;
+            }
+        }
+    }
+
+    if let Some(writer) = csv_writer.as_mut() {
+        writer.flush().unwrap();
+    }
+    if let Some(writer) = collider_writer.as_mut() {
+        writer.flush().unwrap();
+    }
+
+    if simulation_manager.is_broken() {
+        println!("Warning: simulation ended in broken state");
+    }
+
+    if settings.log_to_csv {
+        let fractures_path = format!("{}/{}_fractures.csv", output_dir, scene_name);
+        let mut writer = csv::Writer::from_path(fractures_path).unwrap();
+        writer.write_record(["time", "i", "j", "cause", "strain"]).unwrap();
+        simulation_manager.fracture_state.events.iter().for_each(|event| {
+            writer.write_record([
+                event.time.to_string(),
+                event.bond.0.to_string(),
+                event.bond.1.to_string(),
+                event.cause.name().to_string(),
+                event.strain.to_string(),
+            ]).unwrap();
+        });
+        writer.flush().unwrap();
+        println!("Broken bonds: {}", simulation_manager.fracture_state.events.len());
+
+        if settings.use_adaptive_dt {
+            let dt_path = format!("{}/{}_dt.csv", output_dir, scene_name);
+            let mut writer = csv::Writer::from_path(dt_path).unwrap();
+            writer.write_record(["time", "dt", "rejected_steps"]).unwrap();
+            simulation_manager.dt_history.iter().for_each(|record| {
+                writer.write_record([
+                    record.time.to_string(),
+                    record.dt.to_string(),
+                    record.rejected_steps.to_string(),
+                ]).unwrap();
+            });
+            writer.flush().unwrap();
+            let (min_dt, max_dt) = simulation_manager.dt_history.iter()
+                .fold((f32::INFINITY, 0.0f32), |(min, max), record| (min.min(record.dt), max.max(record.dt)));
+            println!("Adaptive dt between {} and {}", min_dt, max_dt);
+        }
+
+        let fragments_path = format!("{}/{}_fragments.csv", output_dir, scene_name);
+        let mut writer = csv::Writer::from_path(fragments_path).unwrap();
+        writer.write_record(["time", "count", "largest", "sizes"]).unwrap();
+        simulation_manager.fragment_history.iter().for_each(|stats| {
+            let sizes: Vec<String> = stats.sizes.iter().map(|s| s.to_string()).collect();
+            writer.write_record([
+                stats.time.to_string(),
+                stats.count().to_string(),
+                stats.sizes.first().copied().unwrap_or(0).to_string(),
+                sizes.join(" "),
+            ]).unwrap();
+        });
+        writer.flush().unwrap();
+        println!("Fragments: {}", simulation_manager.fragment_history.last().unwrap().count());
+
+        if settings.use_healing {
+            let healing_path = format!("{}/{}_healing.csv", output_dir, scene_name);
+            let mut writer = csv::Writer::from_path(healing_path).unwrap();
+            writer.write_record(["time", "i", "j", "welded"]).unwrap();
+            simulation_manager.healing_events.iter().for_each(|event| {
+                writer.write_record([
+                    event.time.to_string(),
+                    event.bond.0.to_string(),
+                    event.bond.1.to_string(),
+                    event.welded.to_string(),
+                ]).unwrap();
+            });
+            writer.flush().unwrap();
+            println!("Healed bonds: {}", simulation_manager.healing_events.len());
+        }
+    }
+
+    let final_path = format!("{}/{}_final.bincode", output_dir, scene_name);
+    scene::format::save(&final_path, &simulation_manager.scene).unwrap();
+
+    simulation_manager.save_checkpoint(&checkpoint_path).unwrap();
+
+    if settings.use_grid && settings.use_neighbour_list {
+        println!("Neighbour list rebuilds: {}", simulation_manager.neighbour_list.rebuilds);
+    }
+    simulation_manager.scene.environment.colliders.iter().enumerate().for_each(|(i, c)| {
+        println!(
+            "Collider {}: force ({}, {}), torque {}, impulse ({}, {})",
+            i, c.state.force.x, c.state.force.y, c.state.torque, c.state.impulse.x, c.state.impulse.y
+        );
+    });
+
+    println!(
+        "Simulated {:.3}s in {}ms, final state written to {}, checkpoint to {}",
+        simulation_manager.total_simulation_time,
+        timer_start.elapsed().as_millis(),
+        final_path,
+        checkpoint_path
+    );
+}
@@ -1,4 +1,4 @@
-use mylib::scene::Scene;
+use mylib::scene;
 
 fn main() {
 
@@ -14,8 +14,10 @@ fn main() {
 
     println!("Trying to read scene from file: {}", scene_path);
     
-    let f2 = std::fs::File::open(scene_path).unwrap();
-    let decoded_scene: Scene = bincode::deserialize_from(f2).unwrap();
+    let decoded_scene = scene::load_from_file(&scene_path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
 
     mylib::window::run_with_gui(decoded_scene);
 }
\ No newline at end of file
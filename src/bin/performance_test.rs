@@ -1,4 +1,4 @@
-use mylib::{simulation::{manager::{SimulationSettings, SimulationEngineEnum}, self}};
+use mylib::{simulation::{manager::{SimulationSettings, SimulationEngineEnum}, integrator::Integrator, timestep::AdaptiveDtMethod, self}};
 
 
 const SIMULATION_DT: f32 = 0.00002;
@@ -24,18 +24,34 @@ fn main() {
             dt: SIMULATION_DT,
             steps_per_frame: 5,
             engine: SimulationEngineEnum::CpuMultithreadSingleKernel,
+            integrator: Integrator::VelocityVerlet,
             use_grid: true,
             cell_size: scene.object_repulsion_dx * 2.0,
+            use_neighbour_list: true,
+            neighbour_skin: scene.object_repulsion_dx * 0.5,
             log_to_csv: false,
             log_interval: 0.05,
             use_backup: false,
             backup_interval: 0.1,
             use_auto_dt: false,
             auto_dt_factor: 1.1,
+            use_adaptive_dt: false,
+            adaptive_dt_method: AdaptiveDtMethod::Cfl,
+            courant_number: 0.01,
+            error_tolerance: 0.001,
+            min_dt: 0.000001,
+            max_dt: simulation::manager::MAX_DT,
+            use_healing: false,
+            healing_distance: scene.object_repulsion_dx,
+            healing_max_speed: 0.5,
+            use_welding: false,
+            use_self_collision: false,
+            self_collision_hops: 3,
+            deterministic: false,
         };
 
 
-        let mut simulation_manager = simulation::manager::SimulationManager::new(simulation_settings, scene);
+        let mut simulation_manager = simulation::manager::SimulationManager::new(simulation_settings, scene).unwrap();
         simulation_manager.settings = simulation_settings;
 
         let timer_start = std::time::Instant::now();
@@ -50,7 +66,7 @@ fn main() {
 
         let elapsed_ms = timer_start.elapsed().as_millis();
         let iterations_per_second: usize = ((iterations as f32) / (elapsed_ms as f32) * 1000.0) as usize;
-        println!("{object_size}\t{node_count}\t{elapsed_ms}\t{:.0}", iterations_per_second);
+        println!("{object_size}\t{node_count}\t{elapsed_ms}\t{:.0}\t{}", iterations_per_second, simulation_manager.neighbour_list.rebuilds);
 
 
         csv_writer
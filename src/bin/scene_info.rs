@@ -0,0 +1,78 @@
+use std::collections::BTreeMap;
+
+use mylib::scene::{self, Scene};
+
+const USAGE: &str = "Usage: scene_info <scene>...
+
+Prints format version and node, bond and object counts of scene files.
+<scene> is either a path to a .bincode or .ron file or a name of a file inside scenes/ directory.";
+
+/// Loads scene without validation, so that invalid files can be inspected too
+fn load(path: &str) -> Result<(String, Scene), scene::SceneLoadError> {
+    if path.ends_with(".ron") {
+        return Ok(("RON scene description".to_string(), scene::load_from_file(path)?));
+    }
+
+    let (mut scene, version) = scene::format::load(path)?;
+    // bonds of invalid scene may point outside of nodes
+    if scene.validate().is_ok() {
+        scene.update_boundary_nodes();
+    }
+    let format = if version == scene::format::UNVERSIONED {
+        "unversioned, saved in the current version by generate_scenes".to_string()
+    } else {
+        format!("version {}", version)
+    };
+    Ok((format, scene))
+}
+
+fn print_info(path: &str, format: &str, scene: &Scene) {
+    let mut object_sizes: BTreeMap<u32, usize> = BTreeMap::new();
+    scene.nodes.iter().for_each(|n| *object_sizes.entry(n.object_id).or_insert(0) += 1);
+    let boundary_nodes = scene.nodes.iter().filter(|n| n.is_boundary).count();
+
+    println!("{}", path);
+    println!("    format:         {}", format);
+    println!("    nodes:          {} ({} boundary)", scene.nodes.len(), boundary_nodes);
+    println!("    bonds:          {}", scene.connections.len());
+    println!("    objects:        {}", object_sizes.len());
+    println!("    materials:      {}", scene.materials.list.len());
+    println!("    walls:          {}", scene.environment.walls.len());
+    println!("    colliders:      {}", scene.environment.colliders.len());
+    match scene.force_field.repulsion.cutoff {
+        Some(cutoff) => println!("    repulsion:      cutoff {} dx, {}", cutoff, scene.force_field.repulsion.shift.name()),
+        None => println!("    repulsion:      unlimited range"),
+    }
+    if let Err(errors) = scene.validate() {
+        println!("    invalid:\n{}", scene::validation::format_errors(&errors));
+    }
+    object_sizes.iter().for_each(|(object_id, size)| {
+        let material = scene.materials.object_materials.get(object_id)
+            .map_or("none", |m| scene.materials.list[*m].name.as_str());
+        println!("    object {:<8} {} nodes, material {}", object_id, size, material);
+    });
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        eprintln!("{}", USAGE);
+        std::process::exit(1);
+    }
+
+    let mut failed = false;
+    for arg in args {
+        let path = scene::scene_path_from_argument(&arg);
+        match load(&path) {
+            Ok((format, scene)) => print_info(&path, &format, &scene),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
@@ -1,10 +1,13 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std:: f32::consts::PI;
 
 use glam::Vec2;
 
-use crate::simulation::general::Grid;
+use crate::simulation::environment::Environment;
+use crate::simulation::forces::ForceField;
+use crate::simulation::grid::Grid;
 use crate::simulation::node::Node;
+use crate::simulation::temperature::TemperatureCache;
 use crate::simulation;
 use crate::scene::Scene;
 
@@ -122,6 +125,7 @@ pub fn draw_disks(
     scene: &Scene,
     connections_structure: &[Vec<(usize, f32, f32)>],
     coloring_mode: &ColoringMode,
+    temperature_cache: &mut TemperatureCache,
     dt: f32
 ) -> Vec<NodeAttribute> {
 
@@ -129,9 +133,9 @@ pub fn draw_disks(
     // let colors = color_from_kinetic_energy(nodes);
     let colors = match coloring_mode {
         ColoringMode::KineticEnergy => color_from_kinetic_energy(nodes),
-        ColoringMode::Temperature => color_from_temperature(nodes, connections_structure, dt),
+        ColoringMode::Temperature => color_from_temperature(temperature_cache, nodes, connections_structure, &scene.environment, &scene.force_field, dt),
         ColoringMode::Boundary => color_from_boundary(nodes),
-        ColoringMode::Pressure => color_from_pressure(nodes, connections_structure)
+        ColoringMode::Pressure => color_from_pressure(nodes, connections_structure, &scene.force_field)
     };
 
     nodes
@@ -151,27 +155,63 @@ pub fn draw_disks(
         .collect()
 }
 
+/// Outlines of cells of the grid containing nodes
 pub fn draw_grid(grid: &Grid) -> Vec<Vertex> {
     let mut vertices: Vec<Vertex> = Vec::new();
 
-    let y_offset = Vec2::new(0.0, grid.cell_size * grid.cell_count_y as f32);
-    for x in 0..grid.cell_count_x+1 {
-        let start_point = grid.top_left + ((x as f32) * Vec2::new(grid.cell_size, 0.0));
-        let end_point = start_point - y_offset;
-        vertices.push(Vertex { local_position: start_point.to_array() });
-        vertices.push(Vertex { local_position: end_point.to_array() });
-    }
+    grid.occupied_cells().iter().for_each(|(x, y)| {
+        let corner = Vec2::new(*x as f32, *y as f32) * grid.cell_size;
+        let corners = [
+            corner,
+            corner + Vec2::new(grid.cell_size, 0.0),
+            corner + Vec2::new(grid.cell_size, grid.cell_size),
+            corner + Vec2::new(0.0, grid.cell_size),
+        ];
+        for k in 0..4 {
+            vertices.push(Vertex { local_position: corners[k].to_array() });
+            vertices.push(Vertex { local_position: corners[(k + 1) % 4].to_array() });
+        }
+    });
+    vertices
+}
+
+/// Line segments representing walls, shifted by wall `dx` towards the simulation area
+/// so they show where node centers stop.
+pub fn draw_walls(environment: &Environment) -> Vec<Vertex> {
+    const HALF_LENGTH: f32 = 1.5;
+    let mut vertices: Vec<Vertex> = Vec::new();
+
+    environment.walls.iter().enumerate().for_each(|(i, wall)| {
+        let origin = wall.point + wall.normal * wall.dx;
+        let tangent = Vec2::new(-wall.normal.y, wall.normal.x);
+        let mut t_min = -HALF_LENGTH;
+        let mut t_max = HALF_LENGTH;
+
+        // clip the line with remaining walls so closed containers are drawn without overhangs
+        environment.walls.iter().enumerate().filter(|(j, _)| *j != i).for_each(|(_, other)| {
+            let along = tangent.dot(other.normal);
+            if along.abs() > 1e-6 {
+                let t = -(origin - other.point - other.normal * other.dx).dot(other.normal) / along;
+                if along > 0.0 { t_min = t_min.max(t); } else { t_max = t_max.min(t); }
+            }
+        });
+
+        if t_min < t_max {
+            vertices.push(Vertex { local_position: (origin + tangent * t_min).to_array() });
+            vertices.push(Vertex { local_position: (origin + tangent * t_max).to_array() });
+        }
+    });
 
-    let x_offset = Vec2::new(grid.cell_size * grid.cell_count_x as f32, 0.0);
-    for y in 0..grid.cell_count_y+1 {
-        let start_point = grid.top_left - ((y as f32) * Vec2::new(0.0, grid.cell_size));
-        let end_point = start_point + x_offset;
-        vertices.push(Vertex { local_position: start_point.to_array() });
-        vertices.push(Vertex { local_position: end_point.to_array() });
-    }
     vertices
 }
 
+/// Line segments of collider outlines in their current poses
+pub fn draw_colliders(environment: &Environment) -> Vec<Vertex> {
+    environment.colliders.iter().flat_map(|collider| collider.outline()).flat_map(|(a, b)| {
+        [Vertex { local_position: a.to_array() }, Vertex { local_position: b.to_array() }]
+    }).collect()
+}
+
 fn color_from_boundary(nodes: &[Node]) -> Vec<[f32; 3]> {
     let max_id = nodes.iter().max_by(|x, y| x.object_id.cmp(&y.object_id)).unwrap().object_id;
     let min_id = nodes.iter().min_by(|x, y| x.object_id.cmp(&y.object_id)).unwrap().object_id;
@@ -204,10 +244,11 @@ fn min_max_value_per_node(nodes: &[Node], values: &[f32]) -> (f32, f32) {
 
 fn color_from_pressure(
     nodes: &[Node],
-    connections_structure: &[Vec<(usize, f32, f32)>]
+    connections_structure: &[Vec<(usize, f32, f32)>],
+    force_field: &ForceField
 ) -> Vec<[f32; 3]> {
 
-    let pressure_per_node = simulation::pressure::pressure_per_node(nodes, connections_structure);
+    let pressure_per_node = simulation::pressure::pressure_per_node(nodes, connections_structure, force_field);
 
     // calculate max and min pressure ignoring boundary nodes
     let (min_pressure, max_pressure) = min_max_value_per_node(nodes, &pressure_per_node);
@@ -220,12 +261,15 @@ fn color_from_pressure(
 }
 
 fn color_from_temperature(
+    temperature_cache: &mut TemperatureCache,
     nodes: &[Node],
     connections_structure: &[Vec<(usize, f32, f32)>],
+    environment: &Environment,
+    force_field: &ForceField,
     dt: f32
 ) -> Vec<[f32; 3]> {
 
-    let temperature_per_node = simulation::temperature::cached_avg_temperature_per_node(nodes, connections_structure, dt);
+    let temperature_per_node = simulation::temperature::cached_avg_temperature_per_node(temperature_cache, nodes, connections_structure, environment, force_field, dt);
 
     // calculate max and min temperature ignoring boundary nodes
     let (min_temperature, max_temperature) = min_max_value_per_node(nodes, &temperature_per_node);
@@ -294,7 +338,7 @@ glium::implement_vertex!(
     width,
 );
 
-pub fn draw_connections_2(connections: &HashMap<(usize, usize), (f32, f32)>, nodes: &[Node]) -> Vec<Vertex> {
+pub fn draw_connections_2(connections: &BTreeMap<(usize, usize), (f32, f32)>, nodes: &[Node]) -> Vec<Vertex> {
     let mut vertices: Vec<Vertex> = Vec::new();
 
     connections.iter().for_each(|(k, _v)| {
@@ -306,7 +350,7 @@ pub fn draw_connections_2(connections: &HashMap<(usize, usize), (f32, f32)>, nod
 }
 
 
-pub fn draw_connections(connections: &HashMap<(usize, usize), (f32, f32)>, nodes: &[Node]) -> Vec<ConnectionAttribute> {
+pub fn draw_connections(connections: &BTreeMap<(usize, usize), (f32, f32)>, nodes: &[Node]) -> Vec<ConnectionAttribute> {
     connections.iter().map(|(k, _v)| {
         // let (dx, v0) = *v;
         ConnectionAttribute {
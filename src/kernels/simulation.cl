@@ -26,16 +26,70 @@ struct Node {
     bool is_boundary;
 };
 
-const float WALL_V0 = 200.f;
-const float WALL_DX = 0.05f;
+struct Wall {
+    float2 point;
+    float2 normal;
+    float v0;
+    float dx;
+};
+
+// Must match order of BondPotential variants in forces.rs
+#define BOND_LENNARD_JONES 0
+#define BOND_HARMONIC 1
+#define BOND_MORSE 2
+#define BOND_FENE 3
+
+// Force magnitude along the bond, positive values push nodes apart
+float bond_force(const uint potential, const float parameter, const float l, const float dx, const float v0) {
+    const float stiffness = 18.0f * v0 / (dx * dx);
+
+    switch (potential) {
+        case BOND_HARMONIC:
+            return -stiffness * (l - dx);
+        case BOND_MORSE: {
+            const float a = 6.0f / dx;
+            const float e = exp(-a * (l - dx));
+            return -0.5f * v0 * a * e * (1.0f - e);
+        }
+        case BOND_FENE: {
+            const float r_max = parameter * dx;
+            const float r = clamp(l - dx, -0.99f * r_max, 0.99f * r_max);
+            return -stiffness * r / (1.0f - (r / r_max) * (r / r_max));
+        }
+        default:
+            return 3.0f * (v0 / dx) * (pown(dx / l, 13) - pown(dx / l, 7));
+    }
+}
+
+// Must match order of CutoffShift variants in forces.rs
+#define SHIFT_TRUNCATED 0
+#define SHIFT_POTENTIAL 1
+#define SHIFT_FORCE 2
+
+// Repulsion force magnitude, cutoff is relative to dx and 0 for unlimited range
+float repulsion_force(const uint shift, const float cutoff, const float l, const float dx, const float v0) {
+    const float range = cutoff * dx;
+    if (cutoff > 0.0f && l >= range)
+        return 0.0f;
+
+    const float force = 3.0f * (v0 / dx) * pown(dx / l, 13);
+    if (cutoff > 0.0f && shift == SHIFT_FORCE)
+        return force - 3.0f * (v0 / dx) * pown(dx / range, 13);
+    return force;
+}
 
 KERNEL void main(
-    read_only const float collision_dx,
-    read_only const float collision_v0,
+    read_only const uint bond_potential,
+    read_only const float bond_parameter,
+    read_only const uint repulsion_shift,
+    read_only const float repulsion_cutoff,
+    read_only const float2 gravity,
+    read_only const ulong wall_count,
+    read_only const GLOBAL struct Wall * const walls,
     read_only const ulong node_count,
     read_only const GLOBAL struct Node * const nodes, 
     read_only const GLOBAL ulong * const collisions_index, 
-    read_only const GLOBAL ulong * const collisions, 
+    read_only const GLOBAL struct Connection * const collisions, 
     read_only const GLOBAL ulong * const connections_index,
     read_only const GLOBAL struct Connection * const connections,
     write_only GLOBAL float2 *result
@@ -58,12 +112,13 @@ KERNEL void main(
 
 
             for (ulong c_i = collisions_index_start; c_i < collisions_index_end; c_i++) {
-                const ulong j = collisions[c_i];
+                const ulong j = collisions[c_i].j;
+                const float dx = collisions[c_i].dx;
+                const float v0 = collisions[c_i].v0;
 
                 float2 dir = nodes[j].position - nodes[i].position;
                 float l = length(dir);
-                float c = pown(collision_dx / l, 13);
-                acceleration -= (normalize(dir) * 3.0f * (collision_v0 / collision_dx) * c);
+                acceleration -= normalize(dir) * repulsion_force(repulsion_shift, repulsion_cutoff, l, dx, v0);
             }
         }
 
@@ -83,17 +138,14 @@ KERNEL void main(
 
                 float2 dir = nodes[j].position - nodes[i].position;
                 float l = length(dir);
-                float c = pown(dx / l, 7) - pown(dx / l, 13);
-                acceleration += (normalize(dir) * 3.0f * (v0 / dx) * c);
+                acceleration -= normalize(dir) * bond_force(bond_potential, bond_parameter, l, dx, v0);
             }
         }
 
-        // Wall
-        {
-            float2 dir = (float2)(0.0f, -1.f - nodes[i].position.y);
-            float l = length(dir);
-            float c = pown(WALL_DX / l, 13);
-            acceleration -= (normalize(dir) * 3.0f * (WALL_V0 / WALL_DX) * c);
+        // Walls
+        for (ulong w_i = 0; w_i < wall_count; w_i++) {
+            const float d = dot(nodes[i].position - walls[w_i].point, walls[w_i].normal);
+            acceleration += walls[w_i].normal * sign(d) * repulsion_force(repulsion_shift, repulsion_cutoff, fabs(d), walls[w_i].dx, walls[w_i].v0);
         }
 
         // acceleration from nodes interactions
@@ -103,7 +155,7 @@ KERNEL void main(
         acceleration -= nodes[i].velocity * length(nodes[i].velocity) * nodes[i].drag;
         
         // gravity
-        acceleration.y -= 9.81f;
+        acceleration += gravity;
 
 
         result[i] = acceleration;
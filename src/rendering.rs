@@ -25,11 +25,11 @@ impl SceneRenderer {
         }
     }
 
-    pub fn render(&self, display: &glium::Display, target: &mut glium::Frame, settings: &RenderingSettings, screen_ratio: f32, simulation_manager: &SimulationManager) {
+    pub fn render(&self, display: &glium::Display, target: &mut glium::Frame, settings: &RenderingSettings, screen_ratio: f32, simulation_manager: &mut SimulationManager) {
         
-        // draw floor
+        // draw walls and colliders
         {
-            let floor_params = glium::DrawParameters {
+            let wall_params = glium::DrawParameters {
                 depth: glium::Depth {
                     test: glium::DepthTest::IfLess,
                     write: true,
@@ -40,20 +40,22 @@ impl SceneRenderer {
                 ..Default::default()
             };
     
-            const FLOOR_HEIGHT: f32 = -0.96;
-            let floor_verticies: Vec<Vertex> = vec![Vertex {local_position: [-1.5, FLOOR_HEIGHT]}, Vertex {local_position: [1.5, FLOOR_HEIGHT]}];
-            let floor_vertex_buffer = glium::VertexBuffer::immutable(display, &floor_verticies).unwrap();
-            target.draw(
-                &floor_vertex_buffer, 
-                &glium::index::NoIndices(glium::index::PrimitiveType::LinesList), 
-                &self.connection_program, 
-                &glium::uniform! {
-                    screen_ratio: screen_ratio,
-                    zoom: settings.zoom,
-                    camera_position: settings.camera_position.to_array()
-                },
-                &floor_params
-            ).unwrap();
+            let mut wall_verticies: Vec<Vertex> = graphics::draw_walls(&simulation_manager.scene.environment);
+            wall_verticies.extend(graphics::draw_colliders(&simulation_manager.scene.environment));
+            if !wall_verticies.is_empty() {
+                let wall_vertex_buffer = glium::VertexBuffer::immutable(display, &wall_verticies).unwrap();
+                target.draw(
+                    &wall_vertex_buffer, 
+                    &glium::index::NoIndices(glium::index::PrimitiveType::LinesList), 
+                    &self.connection_program, 
+                    &glium::uniform! {
+                        screen_ratio: screen_ratio,
+                        zoom: settings.zoom,
+                        camera_position: settings.camera_position.to_array()
+                    },
+                    &wall_params
+                ).unwrap();
+            }
         }
 
         // draw grid
@@ -131,13 +133,15 @@ impl SceneRenderer {
         }
 
         if settings.draw_nodes {
+            let dt = simulation_manager.last_step_dt();
             let instance_buffer = glium::VertexBuffer::dynamic(
                 display,
                 &graphics::draw_disks(
                     &simulation_manager.scene,
                     &simulation_manager.connections_structure,
                     &settings.coloring_mode,
-                    simulation_manager.last_step_dt(),
+                    &mut simulation_manager.temperature_cache,
+                    dt,
                 ),
             )
             .unwrap();
@@ -0,0 +1,325 @@
+use std::collections::BTreeMap;
+
+use glam::Vec2;
+
+use super::objects::{self, Lattice};
+use super::Scene;
+use super::validation::SceneError;
+use crate::simulation::node::Node;
+use crate::simulation::forces::ForceField;
+use crate::simulation::environment::Environment;
+use crate::simulation::material::{Material, MaterialLibrary};
+use super::description::Shape;
+
+/// Object added to `SceneBuilder`, its nodes are `first_node..first_node + node_count`
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ObjectHandle {
+    pub object_id: u32,
+    pub first_node: usize,
+    pub node_count: usize,
+}
+
+impl ObjectHandle {
+    pub fn nodes(&self) -> std::ops::Range<usize> {
+        self.first_node..self.first_node + self.node_count
+    }
+}
+
+/// Spacing, mass and damping of nodes and strength of bonds of an added object
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct NodeProperties {
+    pub spacing: f32,
+    pub mass: f32,
+    pub damping: f32,
+    pub bond_v0: f32,
+}
+
+impl NodeProperties {
+    pub fn new(spacing: f32, mass: f32, damping: f32, bond_v0: f32) -> NodeProperties {
+        NodeProperties { spacing, mass, damping, bond_v0 }
+    }
+}
+
+/// Builds scene object by object, taking care of node index offsets and object ids.
+pub struct SceneBuilder {
+    nodes: Vec<Node>,
+    connections: BTreeMap<(usize, usize), (f32, f32)>,
+    objects: Vec<ObjectHandle>,
+    next_object_id: u32,
+    lattice: Lattice,
+    object_repulsion_dx: f32,
+    object_repulsion_v0: f32,
+    force_field: ForceField,
+    environment: Environment,
+    materials: MaterialLibrary,
+    /// Errors of objects that could not be added, reported by `build`
+    errors: Vec<SceneError>,
+}
+
+impl SceneBuilder {
+    pub fn new(object_repulsion_dx: f32, object_repulsion_v0: f32) -> SceneBuilder {
+        SceneBuilder {
+            nodes: Vec::new(),
+            connections: BTreeMap::new(),
+            objects: Vec::new(),
+            next_object_id: 1,
+            lattice: Lattice::Square,
+            object_repulsion_dx,
+            object_repulsion_v0,
+            force_field: ForceField::default(),
+            environment: Environment::default(),
+            materials: MaterialLibrary::default(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn with_force_field(mut self, force_field: ForceField) -> SceneBuilder {
+        self.force_field = force_field;
+        self
+    }
+
+    pub fn with_environment(mut self, environment: Environment) -> SceneBuilder {
+        self.environment = environment;
+        self
+    }
+
+    /// Adds material that can be used by objects, returns its index
+    pub fn add_material(&mut self, material: Material) -> usize {
+        self.materials.add(material)
+    }
+
+    /// Overrides repulsion between two materials, by default it is mixed from parameters of both materials
+    pub fn set_pair_repulsion(&mut self, material_a: usize, material_b: usize, dx: f32, v0: f32) {
+        self.materials.set_pair_repulsion(material_a, material_b, dx, v0);
+    }
+
+    /// Material that is not in the library is reported by `build`
+    pub fn assign_material(&mut self, object: ObjectHandle, material: usize) {
+        self.materials.object_materials.insert(object.object_id, material);
+    }
+
+    /// Changes object id of the object, assigned material follows the object.
+    /// Fails if another object already has the id or if it is `u32::MAX`,
+    /// objects added later get ids higher than `object_id`.
+    pub fn set_object_id(&mut self, object: ObjectHandle, object_id: u32) -> Result<ObjectHandle, SceneError> {
+        if self.objects.iter().any(|o| *o != object && o.object_id == object_id) {
+            return Err(SceneError::DuplicateObjectId { object_id });
+        }
+        let next_object_id = object_id.checked_add(1).ok_or(SceneError::ObjectIdOutOfRange { object_id })?;
+
+        let changed = ObjectHandle { object_id, ..object };
+        self.object_nodes_mut(object).iter_mut().for_each(|n| n.object_id = object_id);
+        self.objects.iter_mut().filter(|o| **o == object).for_each(|o| *o = changed);
+        if let Some(material) = self.materials.object_materials.remove(&object.object_id) {
+            self.materials.object_materials.insert(object_id, material);
+        }
+        self.next_object_id = self.next_object_id.max(next_object_id);
+        Ok(changed)
+    }
+
+    /// Lattice used by objects added after this call
+    pub fn set_lattice(&mut self, lattice: Lattice) {
+        self.lattice = lattice;
+    }
+
+    pub fn objects(&self) -> &[ObjectHandle] {
+        &self.objects
+    }
+
+    pub fn object_nodes_mut(&mut self, object: ObjectHandle) -> &mut [Node] {
+        &mut self.nodes[object.nodes()]
+    }
+
+    /// Sets initial velocity of the object, replacing previous one
+    pub fn set_velocity(&mut self, object: ObjectHandle, velocity: Vec2) {
+        let nodes = self.object_nodes_mut(object);
+        nodes.iter_mut().for_each(|n| n.velocity = Vec2::new(0.0, 0.0));
+        objects::add_velocity(nodes, velocity);
+    }
+
+    /// Adds rigid rotation around centre of mass of the object to its velocity
+    pub fn add_angular_velocity(&mut self, object: ObjectHandle, angular_velocity: f32) {
+        objects::add_angular_velocity(self.object_nodes_mut(object), angular_velocity);
+    }
+
+    /// Stretches or compresses the object without changing rest lengths of its bonds, see `objects::apply_strain`
+    pub fn apply_strain(&mut self, object: ObjectHandle, strain: Vec2) {
+        objects::apply_strain(self.object_nodes_mut(object), strain);
+    }
+
+    /// Adds nodes as new object, object id of the nodes is overwritten.
+    /// Nodes closer than `search_distance` are connected with bonds of strength `bond_v0`.
+    pub fn add_nodes(&mut self, mut nodes: Vec<Node>, search_distance: f32, bond_v0: f32) -> ObjectHandle {
+        let object = ObjectHandle {
+            object_id: self.next_object_id,
+            first_node: self.nodes.len(),
+            node_count: nodes.len(),
+        };
+        match self.next_object_id.checked_add(1) {
+            Some(next_object_id) => self.next_object_id = next_object_id,
+            None => self.errors.push(SceneError::ObjectIdOutOfRange { object_id: object.object_id }),
+        }
+
+        nodes.iter_mut().for_each(|n| n.object_id = object.object_id);
+        self.connections.extend(objects::build_connections_map(&nodes, search_distance, bond_v0, object.first_node));
+        self.nodes.append(&mut nodes);
+        self.objects.push(object);
+
+        object
+    }
+
+    pub fn add_rectangle(&mut self, size_x: usize, size_y: usize, offset: Vec2, properties: NodeProperties) -> ObjectHandle {
+        let nodes = objects::build_rectangle_on_lattice(
+            self.lattice, size_x, size_y, properties.spacing, offset.x, offset.y, properties.mass, properties.damping, 0
+        );
+        self.add_nodes(nodes, self.lattice.search_distance(properties.spacing), properties.bond_v0)
+    }
+
+    pub fn add_circle(&mut self, layers: usize, offset: Vec2, properties: NodeProperties) -> ObjectHandle {
+        let nodes = objects::build_circle_on_lattice(
+            self.lattice, layers, properties.spacing, offset.x, offset.y, properties.mass, properties.damping, 0
+        );
+        self.add_nodes(nodes, self.lattice.search_distance(properties.spacing), properties.bond_v0)
+    }
+
+    pub fn add_polygon(&mut self, vertices: &[Vec2], properties: NodeProperties) -> ObjectHandle {
+        self.add_polygon_with_holes(vertices, &[], properties)
+    }
+
+    /// Polygon and every hole need at least 3 vertices, otherwise an empty object is added
+    /// and the error is reported by `build`
+    pub fn add_polygon_with_holes(&mut self, vertices: &[Vec2], holes: &[Vec<Vec2>], properties: NodeProperties) -> ObjectHandle {
+        let too_short = std::iter::once(vertices.len()).chain(holes.iter().map(|hole| hole.len())).find(|len| *len < 3);
+        if let Some(len) = too_short {
+            self.errors.push(SceneError::InvalidObjectParameter { object: self.objects.len(), name: "polygon vertex count", value: len as f32 });
+            return self.add_nodes(Vec::new(), 0.0, 0.0);
+        }
+
+        let nodes = objects::build_polygon_with_holes(
+            self.lattice, vertices, holes, properties.spacing, properties.mass, properties.damping, 0
+        );
+        self.add_nodes(nodes, self.lattice.search_distance(properties.spacing), properties.bond_v0)
+    }
+
+    /// Adds object filling set pixels of `mask`, see `objects::build_mask`
+    pub fn add_mask(&mut self, mask: &[Vec<bool>], pixel_size: f32, offset: Vec2, properties: NodeProperties) -> ObjectHandle {
+        let nodes = objects::build_mask(
+            self.lattice, mask, pixel_size, offset.x, offset.y, properties.spacing, properties.mass, properties.damping, 0
+        );
+        self.add_nodes(nodes, self.lattice.search_distance(properties.spacing), properties.bond_v0)
+    }
+
+    /// Adds object of given shape, `position` has the same meaning as in `ObjectDescription`
+    pub fn add_shape(&mut self, shape: &Shape, position: Vec2, properties: NodeProperties) -> ObjectHandle {
+        match shape {
+            Shape::Rectangle { size_x, size_y } => self.add_rectangle(*size_x, *size_y, position, properties),
+            Shape::Circle { layers } => self.add_circle(*layers, position, properties),
+            Shape::Polygon { vertices, holes } => {
+                let translate = |points: &Vec<Vec2>| -> Vec<Vec2> {
+                    points.iter().map(|p| *p + position).collect()
+                };
+                let holes: Vec<Vec<Vec2>> = holes.iter().map(translate).collect();
+                self.add_polygon_with_holes(&translate(vertices), &holes, properties)
+            }
+            Shape::Mask { rows, pixel_size } => {
+                let mask = objects::mask_from_rows(rows);
+                self.add_mask(&mask, *pixel_size, position, properties)
+            }
+        }
+    }
+
+    /// Adds object of given shape made from material, node spacing, mass, damping and bond strength
+    /// are taken from the material
+    /// Unknown material adds an empty object, the error is reported by `build`
+    pub fn add_shape_of_material(&mut self, shape: &Shape, position: Vec2, material: usize) -> ObjectHandle {
+        let m = match self.materials.list.get(material) {
+            Some(m) => m.clone(),
+            None => {
+                let object = self.add_nodes(Vec::new(), 0.0, 0.0);
+                self.assign_material(object, material);
+                return object;
+            }
+        };
+        let mass = m.density * self.lattice.cell_area(m.rest_spacing);
+        let properties = NodeProperties::new(m.rest_spacing, mass, m.damping, m.bond_stiffness);
+        let object = self.add_shape(shape, position, properties);
+        self.assign_material(object, material);
+        object
+    }
+
+    /// Finishes the scene and checks it with `Scene::validate`, errors of objects
+    /// that could not be added come first
+    pub fn build(self) -> Result<Scene, Vec<SceneError>> {
+        // connections are built per object, so they can never join two different objects
+        debug_assert!(self.connections.keys().all(|(i, j)| {
+            self.nodes[*i].object_id == self.nodes[*j].object_id
+        }));
+
+        let scene = Scene {
+            nodes: self.nodes,
+            connections: self.connections,
+            object_repulsion_dx: self.object_repulsion_dx,
+            object_repulsion_v0: self.object_repulsion_v0,
+            force_field: self.force_field,
+            environment: self.environment,
+            materials: self.materials,
+        };
+        let mut errors = self.errors;
+        if let Err(validation_errors) = scene.validate() {
+            errors.extend(validation_errors);
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(scene)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explicit_object_ids_are_unique() {
+        let mut builder = SceneBuilder::new(0.04, 100.0);
+        let a = builder.add_rectangle(2, 2, Vec2::new(0.0, 0.0), NodeProperties::new(0.03, 1.0, 1.0, 10.0));
+        let b = builder.add_rectangle(2, 2, Vec2::new(1.0, 0.0), NodeProperties::new(0.03, 1.0, 1.0, 10.0));
+
+        assert_eq!(builder.set_object_id(b, a.object_id), Err(SceneError::DuplicateObjectId { object_id: a.object_id }));
+        let b = builder.set_object_id(b, 7).unwrap();
+        let c = builder.add_rectangle(2, 2, Vec2::new(2.0, 0.0), NodeProperties::new(0.03, 1.0, 1.0, 10.0));
+        assert_eq!(c.object_id, 8);
+
+        let scene = builder.build().unwrap();
+        assert!(scene.nodes[b.nodes()].iter().all(|n| n.object_id == 7));
+    }
+
+    #[test]
+    fn last_object_id_is_rejected() {
+        let mut builder = SceneBuilder::new(0.04, 100.0);
+        let properties = NodeProperties::new(0.03, 1.0, 1.0, 10.0);
+        let a = builder.add_rectangle(2, 2, Vec2::new(0.0, 0.0), properties);
+
+        assert_eq!(builder.set_object_id(a, u32::MAX), Err(SceneError::ObjectIdOutOfRange { object_id: u32::MAX }));
+        builder.set_object_id(a, u32::MAX - 1).unwrap();
+        builder.add_rectangle(2, 2, Vec2::new(1.0, 0.0), properties);
+
+        assert_eq!(builder.build().err(), Some(vec![SceneError::ObjectIdOutOfRange { object_id: u32::MAX }]));
+    }
+
+    #[test]
+    fn invalid_objects_are_reported_by_build() {
+        let mut builder = SceneBuilder::new(0.04, 100.0);
+        let properties = NodeProperties::new(0.03, 1.0, 1.0, 10.0);
+        builder.add_rectangle(2, 2, Vec2::new(0.0, 0.0), properties);
+        let line = builder.add_polygon(&[Vec2::new(0.0, 1.0), Vec2::new(1.0, 1.0)], properties);
+        let block = builder.add_shape_of_material(&Shape::Rectangle { size_x: 2, size_y: 2 }, Vec2::new(2.0, 0.0), 5);
+
+        assert_eq!(line.node_count, 0);
+        assert_eq!(block.node_count, 0);
+        assert_eq!(builder.build().err(), Some(vec![
+            SceneError::InvalidObjectParameter { object: 1, name: "polygon vertex count", value: 2.0 },
+            SceneError::UnknownMaterial { object_id: block.object_id, material: 5 },
+        ]));
+    }
+}
@@ -0,0 +1,52 @@
+use std::collections::BTreeMap;
+use std::vec::Vec;
+
+use glam::Vec2;
+
+use super::objects;
+use super::Scene;
+use crate::simulation::forces::ForceField;
+use crate::simulation::environment::Environment;
+use crate::simulation::material::MaterialLibrary;
+
+pub fn generate() -> Scene {
+
+    let object1_sx = 16;
+    let object1_sy = 10;
+    let spacing1 = 0.04;
+
+    let spacing2 = 0.04;
+
+    let mut nodes1 = objects::build_rectangle(object1_sx, object1_sy, spacing1, -0.6, -0.2, 1.0, 0.0, 1);
+    let connections_map_1 = objects::build_connections_map(&nodes1, spacing1 * 1.5, 70.0, 0);
+
+    let mut nodes2 = objects::build_circle(6, spacing2, 0.3, 0.5, 1.0, 0.0, 2);
+    let connections_map_2 = objects::build_connections_map(&nodes2, spacing2 * 1.5, 70.0, nodes1.len());
+
+    let mut connections_map: BTreeMap<(usize, usize), (f32, f32)> = BTreeMap::new();
+    connections_map.extend(connections_map_1);
+    connections_map.extend(connections_map_2);
+
+    let mut nodes = Vec::new();
+    nodes.append(&mut nodes1);
+    nodes.append(&mut nodes2);
+
+    // closed box with gravity pointing to the bottom left corner
+    let environment = Environment::closed_box(
+        Vec2::new(-4.0, -8.0),
+        Vec2::new(-0.8, -0.8),
+        Vec2::new(0.8, 0.8),
+        100.0,
+        0.04
+    );
+
+    Scene {
+        nodes,
+        connections: connections_map,
+        object_repulsion_dx: 0.06,
+        object_repulsion_v0: 100.0,
+        force_field: ForceField::default(),
+        environment,
+        materials: MaterialLibrary::default(),
+    }
+}
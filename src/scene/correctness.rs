@@ -1,8 +1,11 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::vec::Vec;
 
 use super::objects;
 use super::Scene;
+use crate::simulation::forces::ForceField;
+use crate::simulation::environment::Environment;
+use crate::simulation::material::MaterialLibrary;
 
 pub fn generate() -> Scene {
 
@@ -20,7 +23,7 @@ pub fn generate() -> Scene {
     let mut nodes2 = objects::build_rectangle(object2_sx, object2_sy, spacing1, 0.0, -0.08, 1.0, 0.0, 2);
     let connections_map_2 = objects::build_connections_map(&nodes2, spacing1 * 1.5, 70.0, object1_st);
 
-    let mut connections_map: HashMap<(usize, usize), (f32, f32)> = HashMap::new();
+    let mut connections_map: BTreeMap<(usize, usize), (f32, f32)> = BTreeMap::new();
     connections_map.extend(connections_map_1);
     connections_map.extend(connections_map_2);
 
@@ -33,5 +36,8 @@ pub fn generate() -> Scene {
         connections: connections_map,
         object_repulsion_dx: 0.06,
         object_repulsion_v0: 100.0,
+        force_field: ForceField::default(),
+        environment: Environment::default(),
+        materials: MaterialLibrary::default(),
     }
 }
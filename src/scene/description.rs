@@ -0,0 +1,316 @@
+use glam::Vec2;
+use serde::{Serialize, Deserialize};
+
+use super::builder::{NodeProperties, SceneBuilder};
+use super::objects::Lattice;
+use super::{Scene, SceneLoadError};
+use super::validation::SceneError;
+use crate::simulation::forces::ForceField;
+use crate::simulation::environment::Environment;
+use crate::simulation::material::Material;
+
+/// Shape of the object and number of nodes it is built from
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum Shape {
+    /// `size_x` by `size_y` nodes, `position` is the bottom left node
+    Rectangle { size_x: usize, size_y: usize },
+    /// Concentric rings of nodes, `position` is the center
+    Circle { layers: usize },
+    /// Closed polygon with optional holes, vertices are relative to `position`
+    Polygon {
+        vertices: Vec<Vec2>,
+        #[serde(default)]
+        holes: Vec<Vec<Vec2>>,
+    },
+    /// Bitmap mask given as rows of text, `#` is filled pixel and first row is the top one.
+    /// `position` is the bottom left corner of the mask.
+    Mask { rows: Vec<String>, pixel_size: f32 },
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ObjectDescription {
+    pub shape: Shape,
+    #[serde(default)]
+    pub lattice: Lattice,
+    pub position: Vec2,
+    /// Name of material from `SceneDescription::materials`, when given
+    /// `spacing`, `mass`, `damping` and `bond_v0` are taken from the material and can be omitted
+    #[serde(default)]
+    pub material: Option<String>,
+    /// Distance between neighbouring nodes
+    #[serde(default)]
+    pub spacing: f32,
+    /// Mass of a single node
+    #[serde(default)]
+    pub mass: f32,
+    #[serde(default)]
+    pub damping: f32,
+    /// Strength of bonds between nodes of the object
+    #[serde(default)]
+    pub bond_v0: f32,
+    #[serde(default = "zero_vector")]
+    pub velocity: Vec2,
+    /// Rotation around centre of mass in radians per second, counter clockwise
+    #[serde(default)]
+    pub angular_velocity: f32,
+    /// Initial relative stretch along x and y axis, negative values compress the object
+    #[serde(default = "zero_vector")]
+    pub strain: Vec2,
+    /// Assigned automatically if not given
+    #[serde(default)]
+    pub object_id: Option<u32>,
+}
+
+/// Largest number of lattice cells over the bounding box of a polygon or a mask, filling is done cell by cell
+const MAX_FILL_CELLS: f32 = 1e8;
+
+fn zero_vector() -> Vec2 {
+    Vec2::new(0.0, 0.0)
+}
+
+/// Human editable description of a scene, compiled into `Scene` with `compile`.
+///
+/// Stored in RON format, see `scenes/example.ron`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct SceneDescription {
+    pub object_repulsion_dx: f32,
+    pub object_repulsion_v0: f32,
+    #[serde(default)]
+    pub force_field: ForceField,
+    #[serde(default)]
+    pub environment: Environment,
+    #[serde(default)]
+    pub materials: Vec<Material>,
+    /// Repulsion `(dx, v0)` between two materials given by names, replaces mixed parameters of the pair
+    #[serde(default)]
+    pub material_repulsion: Vec<(String, String, f32, f32)>,
+    pub objects: Vec<ObjectDescription>,
+}
+
+impl SceneDescription {
+    pub fn from_ron(text: &str) -> Result<SceneDescription, ron::Error> {
+        ron::from_str(text)
+    }
+
+    pub fn to_ron(&self) -> String {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).unwrap()
+    }
+
+    /// Checks parameters of materials and objects that cannot be caught by `Scene::validate` after the objects are built
+    fn check_objects(&self) -> Vec<SceneError> {
+        let mut errors = Vec::new();
+
+        // objects of material are built with its spacing, mass and bond strength
+        self.materials.iter().for_each(|material| {
+            let mut check_parameter = |name: &'static str, value: f32| {
+                if !value.is_finite() || value <= 0.0 {
+                    errors.push(SceneError::InvalidParameter { name, value });
+                }
+            };
+            check_parameter("material rest spacing", material.rest_spacing);
+            check_parameter("material density", material.density);
+            check_parameter("material bond stiffness", material.bond_stiffness);
+            check_parameter("material repulsion dx", material.repulsion_dx);
+            check_parameter("material repulsion v0", material.repulsion_v0);
+        });
+
+        self.objects.iter().enumerate().for_each(|(i, object)| {
+            if let Shape::Polygon { vertices, holes } = &object.shape {
+                // object builders panic on polygons with less than three vertices
+                let invalid = |name: &'static str, points: &Vec<Vec2>| {
+                    SceneError::InvalidObjectParameter { object: i, name, value: points.len() as f32 }
+                };
+                if vertices.len() < 3 {
+                    errors.push(invalid("polygon vertex count", vertices));
+                }
+                holes.iter().filter(|hole| hole.len() < 3).for_each(|hole| errors.push(invalid("hole vertex count", hole)));
+            }
+        });
+
+        self.objects.iter().enumerate().filter(|(_i, o)| o.material.is_none()).for_each(|(i, object)| {
+            let mut check_parameter = |name: &'static str, value: f32| {
+                if !value.is_finite() || value <= 0.0 {
+                    errors.push(SceneError::InvalidObjectParameter { object: i, name, value });
+                }
+            };
+            check_parameter("spacing", object.spacing);
+            check_parameter("mass", object.mass);
+            check_parameter("bond_v0", object.bond_v0);
+        });
+
+        self.objects.iter().enumerate().for_each(|(i, object)| {
+            let spacing = match &object.material {
+                Some(name) => self.materials.iter().find(|m| m.name == *name).map(|m| m.rest_spacing),
+                None => Some(object.spacing),
+            };
+            let size = match &object.shape {
+                Shape::Polygon { vertices, .. } if vertices.len() > 2 => {
+                    let min = vertices.iter().fold(Vec2::new(f32::MAX, f32::MAX), |acc, v| acc.min(*v));
+                    let max = vertices.iter().fold(Vec2::new(f32::MIN, f32::MIN), |acc, v| acc.max(*v));
+                    Some(max - min)
+                }
+                Shape::Mask { rows, pixel_size } => {
+                    let width = rows.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+                    Some(Vec2::new(width as f32, rows.len() as f32) * *pixel_size)
+                }
+                _ => None,
+            };
+            // invalid spacing is reported above
+            if let (Some(spacing), Some(size)) = (spacing.filter(|s| s.is_finite() && *s > 0.0), size) {
+                let cells = (size.x / spacing).ceil() * (size.y / spacing).ceil();
+                if cells.is_nan() || cells > MAX_FILL_CELLS {
+                    errors.push(SceneError::InvalidObjectParameter { object: i, name: "lattice cell count", value: cells });
+                }
+            }
+        });
+
+        errors
+    }
+
+    /// Builds the scene, fails if object refers to material that is not defined, polygon has less than
+    /// three vertices, material or object without material has invalid spacing, mass or bond strength
+    /// or polygon or mask would be filled with more than `MAX_FILL_CELLS` lattice cells
+    pub fn compile(&self) -> Result<Scene, SceneLoadError> {
+        let errors = self.check_objects();
+        if !errors.is_empty() {
+            return Err(SceneLoadError::Invalid(errors));
+        }
+
+        let mut environment = self.environment.clone();
+        environment.walls.iter_mut().for_each(|wall| wall.normal = wall.normal.normalize());
+        environment.move_colliders(0.0);
+
+        let mut builder = SceneBuilder::new(self.object_repulsion_dx, self.object_repulsion_v0)
+            .with_force_field(self.force_field)
+            .with_environment(environment);
+
+        self.materials.iter().for_each(|material| {
+            builder.add_material(material.clone());
+        });
+
+        let find_material = |name: &String| -> Result<usize, SceneLoadError> {
+            self.materials.iter().position(|m| m.name == *name).ok_or_else(|| SceneLoadError::UnknownMaterial(name.clone()))
+        };
+
+        for (a, b, dx, v0) in self.material_repulsion.iter() {
+            builder.set_pair_repulsion(find_material(a)?, find_material(b)?, *dx, *v0);
+        }
+
+        for object in self.objects.iter() {
+            builder.set_lattice(object.lattice);
+            let handle = match &object.material {
+                Some(name) => builder.add_shape_of_material(&object.shape, object.position, find_material(name)?),
+                None => builder.add_shape(
+                    &object.shape,
+                    object.position,
+                    NodeProperties::new(object.spacing, object.mass, object.damping, object.bond_v0)
+                ),
+            };
+
+            builder.apply_strain(handle, object.strain);
+            builder.set_velocity(handle, object.velocity);
+            builder.add_angular_velocity(handle, object.angular_velocity);
+
+            if let Some(object_id) = object.object_id {
+                builder.set_object_id(handle, object_id).map_err(|e| SceneLoadError::Invalid(vec![e]))?;
+            }
+        }
+
+        builder.build().map_err(SceneLoadError::Invalid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OBJECT_WITHOUT_MATERIAL: &str = "(
+        object_repulsion_dx: 0.04,
+        object_repulsion_v0: 100.0,
+        objects: [
+            (shape: Rectangle(size_x: 3, size_y: 3), position: (0.0, 0.0), spacing: 0.03, mass: 1.0, bond_v0: 10.0),
+            (shape: Rectangle(size_x: 3, size_y: 3), position: (1.0, 0.0)),
+        ],
+    )";
+
+    #[test]
+    fn object_without_material_needs_spacing_mass_and_bond_v0() {
+        let description = SceneDescription::from_ron(OBJECT_WITHOUT_MATERIAL).unwrap();
+        match description.compile() {
+            Err(SceneLoadError::Invalid(errors)) => assert_eq!(errors, vec![
+                SceneError::InvalidObjectParameter { object: 1, name: "spacing", value: 0.0 },
+                SceneError::InvalidObjectParameter { object: 1, name: "mass", value: 0.0 },
+                SceneError::InvalidObjectParameter { object: 1, name: "bond_v0", value: 0.0 },
+            ]),
+            other => panic!("expected invalid scene, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn degenerate_polygon_is_rejected() {
+        let description = SceneDescription::from_ron("(
+            object_repulsion_dx: 0.04,
+            object_repulsion_v0: 100.0,
+            objects: [(
+                shape: Polygon(vertices: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)], holes: [[(0.1, 0.1), (0.2, 0.1)]]),
+                position: (0.0, 0.0),
+                spacing: 0.03,
+                mass: 1.0,
+                bond_v0: 10.0,
+            ), (
+                shape: Polygon(vertices: [(0.0, 0.0), (1.0, 0.0)]),
+                position: (2.0, 0.0),
+                spacing: 0.03,
+                mass: 1.0,
+                bond_v0: 10.0,
+            )],
+        )").unwrap();
+        match description.compile() {
+            Err(SceneLoadError::Invalid(errors)) => assert_eq!(errors, vec![
+                SceneError::InvalidObjectParameter { object: 0, name: "hole vertex count", value: 2.0 },
+                SceneError::InvalidObjectParameter { object: 1, name: "polygon vertex count", value: 2.0 },
+            ]),
+            other => panic!("expected invalid scene, got {:?}", other),
+        }
+    }
+
+    fn polygon_of_material(rest_spacing: f32) -> SceneDescription {
+        SceneDescription::from_ron(&format!("(
+            object_repulsion_dx: 0.04,
+            object_repulsion_v0: 100.0,
+            materials: [(
+                name: \"ice\",
+                density: 900.0,
+                bond_stiffness: 50.0,
+                rest_spacing: {:e},
+                repulsion_dx: 0.04,
+                repulsion_v0: 100.0,
+            )],
+            objects: [(
+                shape: Polygon(vertices: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]),
+                position: (0.0, 0.0),
+                material: Some(\"ice\"),
+            )],
+        )", rest_spacing)).unwrap()
+    }
+
+    #[test]
+    fn material_spacing_is_checked_before_objects_are_built() {
+        match polygon_of_material(0.0).compile() {
+            Err(SceneLoadError::Invalid(errors)) => assert_eq!(errors, vec![
+                SceneError::InvalidParameter { name: "material rest spacing", value: 0.0 },
+            ]),
+            other => panic!("expected invalid scene, got {:?}", other),
+        }
+
+        match polygon_of_material(1e-9).compile() {
+            Err(SceneLoadError::Invalid(errors)) => assert!(matches!(
+                errors.as_slice(),
+                [SceneError::InvalidObjectParameter { object: 0, name: "lattice cell count", .. }]
+            )),
+            other => panic!("expected invalid scene, got {:?}", other),
+        }
+
+        assert!(polygon_of_material(0.05).compile().is_ok());
+    }
+}
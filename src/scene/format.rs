@@ -0,0 +1,907 @@
+use std::io::Write;
+
+use bincode::Options;
+
+use super::{Scene, SceneLoadError};
+
+/// First bytes of every scene file with a header
+pub const SCENE_MAGIC: [u8; 8] = *b"EOSCENE\0";
+
+/// Version of the scene file written by `write`
+pub const SCENE_FORMAT_VERSION: u32 = 3;
+
+/// Version reported for files without header, which are raw bincode of `Scene`
+pub const UNVERSIONED: u32 = 0;
+
+/// On-disk schema of version 1, independent of in-memory structs.
+///
+/// Records of a version are never changed once files were written with it. New version gets
+/// its own module with new records and the previous version is migrated to it in `decode`.
+mod v1 {
+    use std::collections::{BTreeMap, HashMap};
+
+    use glam::Vec2;
+    use serde::{Serialize, Deserialize};
+
+    use crate::simulation::environment::Environment;
+    use crate::simulation::forces::BondPotential;
+    use crate::simulation::fracture::{Fatigue, FractureRule};
+    use crate::simulation::material::{Material, MaterialLibrary};
+    use crate::simulation::node::Node;
+
+    /// Node without boundary flag, it is recomputed from bonds when scene is loaded
+    #[derive(Serialize, Deserialize)]
+    pub struct NodeRecord {
+        pub position: [f32; 2],
+        pub velocity: [f32; 2],
+        pub last_acceleration: [f32; 2],
+        pub current_acceleration: [f32; 2],
+        pub mass: f32,
+        pub drag: f32,
+        pub object_id: u32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct BondRecord {
+        pub a: u64,
+        pub b: u64,
+        pub rest_length: f32,
+        pub strength: f32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub enum BondPotentialRecord {
+        LennardJones,
+        Harmonic,
+        Morse,
+        Fene { max_extension: f32 },
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct WallRecord {
+        pub point: [f32; 2],
+        pub normal: [f32; 2],
+        pub v0: f32,
+        pub dx: f32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct EnvironmentRecord {
+        pub gravity: [f32; 2],
+        pub walls: Vec<WallRecord>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct FatigueRecord {
+        pub threshold_strain: f32,
+        pub rate: f32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct FractureRecord {
+        pub max_force: Option<f32>,
+        pub max_compression: Option<f32>,
+        pub fatigue: Option<FatigueRecord>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MaterialRecord {
+        pub name: String,
+        pub density: f32,
+        pub bond_stiffness: f32,
+        pub rest_spacing: f32,
+        pub break_strain: f32,
+        pub fracture: FractureRecord,
+        pub damping: f32,
+        pub repulsion_dx: f32,
+        pub repulsion_v0: f32,
+    }
+
+    /// Materials in the same layout as bincode of `MaterialLibrary`, so unversioned files can reuse it
+    #[derive(Serialize, Deserialize)]
+    pub struct MaterialsRecord {
+        pub list: Vec<MaterialRecord>,
+        pub object_materials: Vec<(u32, u64)>,
+        pub pair_repulsion: Vec<((u64, u64), (f32, f32))>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct SceneFile {
+        pub nodes: Vec<NodeRecord>,
+        pub bonds: Vec<BondRecord>,
+        pub object_repulsion_dx: f32,
+        pub object_repulsion_v0: f32,
+        pub bond_potential: BondPotentialRecord,
+        pub environment: EnvironmentRecord,
+        pub materials: MaterialsRecord,
+    }
+
+    impl From<&Node> for NodeRecord {
+        fn from(n: &Node) -> Self {
+            NodeRecord {
+                position: n.position.to_array(),
+                velocity: n.velocity.to_array(),
+                last_acceleration: n.last_acceleration.to_array(),
+                current_acceleration: n.current_acceleration.to_array(),
+                mass: n.mass,
+                drag: n.drag,
+                object_id: n.object_id,
+            }
+        }
+    }
+
+    impl From<&NodeRecord> for Node {
+        fn from(n: &NodeRecord) -> Self {
+            Node {
+                position: Vec2::from(n.position),
+                velocity: Vec2::from(n.velocity),
+                last_acceleration: Vec2::from(n.last_acceleration),
+                current_acceleration: Vec2::from(n.current_acceleration),
+                mass: n.mass,
+                drag: n.drag,
+                object_id: n.object_id,
+                is_boundary: false,
+            }
+        }
+    }
+
+    impl From<&BondPotential> for BondPotentialRecord {
+        fn from(bond: &BondPotential) -> Self {
+            match *bond {
+                BondPotential::LennardJones => BondPotentialRecord::LennardJones,
+                BondPotential::Harmonic => BondPotentialRecord::Harmonic,
+                BondPotential::Morse => BondPotentialRecord::Morse,
+                BondPotential::Fene { max_extension } => BondPotentialRecord::Fene { max_extension },
+            }
+        }
+    }
+
+    impl From<&BondPotentialRecord> for BondPotential {
+        fn from(bond: &BondPotentialRecord) -> Self {
+            match *bond {
+                BondPotentialRecord::LennardJones => BondPotential::LennardJones,
+                BondPotentialRecord::Harmonic => BondPotential::Harmonic,
+                BondPotentialRecord::Morse => BondPotential::Morse,
+                BondPotentialRecord::Fene { max_extension } => BondPotential::Fene { max_extension },
+            }
+        }
+    }
+
+    impl From<&Environment> for EnvironmentRecord {
+        fn from(environment: &Environment) -> Self {
+            EnvironmentRecord {
+                gravity: environment.gravity.to_array(),
+                walls: environment.walls.iter().map(|w| WallRecord {
+                    point: w.point.to_array(),
+                    normal: w.normal.to_array(),
+                    v0: w.v0,
+                    dx: w.dx,
+                }).collect(),
+            }
+        }
+    }
+
+    impl From<&Material> for MaterialRecord {
+        fn from(m: &Material) -> Self {
+            MaterialRecord {
+                name: m.name.clone(),
+                density: m.density,
+                bond_stiffness: m.bond_stiffness,
+                rest_spacing: m.rest_spacing,
+                break_strain: m.break_strain,
+                fracture: FractureRecord {
+                    max_force: m.fracture.max_force,
+                    max_compression: m.fracture.max_compression,
+                    fatigue: m.fracture.fatigue.map(|f| FatigueRecord {
+                        threshold_strain: f.threshold_strain,
+                        rate: f.rate,
+                    }),
+                },
+                damping: m.damping,
+                repulsion_dx: m.repulsion_dx,
+                repulsion_v0: m.repulsion_v0,
+            }
+        }
+    }
+
+    impl From<&MaterialRecord> for Material {
+        fn from(m: &MaterialRecord) -> Self {
+            Material {
+                name: m.name.clone(),
+                density: m.density,
+                bond_stiffness: m.bond_stiffness,
+                rest_spacing: m.rest_spacing,
+                break_strain: m.break_strain,
+                fracture: FractureRule {
+                    max_force: m.fracture.max_force,
+                    max_compression: m.fracture.max_compression,
+                    fatigue: m.fracture.fatigue.as_ref().map(|f| Fatigue {
+                        threshold_strain: f.threshold_strain,
+                        rate: f.rate,
+                    }),
+                },
+                damping: m.damping,
+                repulsion_dx: m.repulsion_dx,
+                repulsion_v0: m.repulsion_v0,
+            }
+        }
+    }
+
+    impl From<&MaterialLibrary> for MaterialsRecord {
+        fn from(materials: &MaterialLibrary) -> Self {
+            let mut object_materials: Vec<(u32, u64)> = materials.object_materials.iter()
+                .map(|(object_id, material)| (*object_id, *material as u64))
+                .collect();
+            object_materials.sort();
+
+            MaterialsRecord {
+                list: materials.list.iter().map(MaterialRecord::from).collect(),
+                object_materials,
+                pair_repulsion: materials.pair_repulsion.iter()
+                    .map(|((a, b), repulsion)| ((*a as u64, *b as u64), *repulsion))
+                    .collect(),
+            }
+        }
+    }
+
+    impl From<&MaterialsRecord> for MaterialLibrary {
+        fn from(materials: &MaterialsRecord) -> Self {
+            MaterialLibrary {
+                list: materials.list.iter().map(Material::from).collect(),
+                object_materials: materials.object_materials.iter()
+                    .map(|(object_id, material)| (*object_id, *material as usize))
+                    .collect::<HashMap<u32, usize>>(),
+                pair_repulsion: materials.pair_repulsion.iter()
+                    .map(|((a, b), repulsion)| ((*a as usize, *b as usize), *repulsion))
+                    .collect::<BTreeMap<(usize, usize), (f32, f32)>>(),
+            }
+        }
+    }
+
+    impl SceneFile {
+        /// Repulsion had unlimited range
+        pub fn migrate(self) -> super::v2::SceneFile {
+            super::v2::SceneFile {
+                nodes: self.nodes,
+                bonds: self.bonds,
+                object_repulsion_dx: self.object_repulsion_dx,
+                object_repulsion_v0: self.object_repulsion_v0,
+                bond_potential: self.bond_potential,
+                repulsion: super::v2::RepulsionRecord {
+                    cutoff: None,
+                    shift: super::v2::CutoffShiftRecord::Truncated,
+                },
+                environment: self.environment,
+                materials: self.materials,
+            }
+        }
+    }
+}
+
+/// Version 2 added cutoff of repulsion, other records are the same as in version 1
+mod v2 {
+    use serde::{Serialize, Deserialize};
+
+    use crate::simulation::forces::{CutoffShift, Repulsion};
+    use super::v1::{NodeRecord, BondRecord, BondPotentialRecord, EnvironmentRecord, MaterialsRecord};
+
+    #[derive(Serialize, Deserialize)]
+    pub enum CutoffShiftRecord {
+        Truncated,
+        ShiftedPotential,
+        ShiftedForce,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct RepulsionRecord {
+        pub cutoff: Option<f32>,
+        pub shift: CutoffShiftRecord,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct SceneFile {
+        pub nodes: Vec<NodeRecord>,
+        pub bonds: Vec<BondRecord>,
+        pub object_repulsion_dx: f32,
+        pub object_repulsion_v0: f32,
+        pub bond_potential: BondPotentialRecord,
+        pub repulsion: RepulsionRecord,
+        pub environment: EnvironmentRecord,
+        pub materials: MaterialsRecord,
+    }
+
+    impl From<&Repulsion> for RepulsionRecord {
+        fn from(repulsion: &Repulsion) -> Self {
+            RepulsionRecord {
+                cutoff: repulsion.cutoff,
+                shift: match repulsion.shift {
+                    CutoffShift::Truncated => CutoffShiftRecord::Truncated,
+                    CutoffShift::ShiftedPotential => CutoffShiftRecord::ShiftedPotential,
+                    CutoffShift::ShiftedForce => CutoffShiftRecord::ShiftedForce,
+                },
+            }
+        }
+    }
+
+    impl From<&RepulsionRecord> for Repulsion {
+        fn from(repulsion: &RepulsionRecord) -> Self {
+            Repulsion {
+                cutoff: repulsion.cutoff,
+                shift: match repulsion.shift {
+                    CutoffShiftRecord::Truncated => CutoffShift::Truncated,
+                    CutoffShiftRecord::ShiftedPotential => CutoffShift::ShiftedPotential,
+                    CutoffShiftRecord::ShiftedForce => CutoffShift::ShiftedForce,
+                },
+            }
+        }
+    }
+
+    impl SceneFile {
+        /// Scenes had no colliders
+        pub fn migrate(self) -> super::v3::SceneFile {
+            super::v3::SceneFile {
+                nodes: self.nodes,
+                bonds: self.bonds,
+                object_repulsion_dx: self.object_repulsion_dx,
+                object_repulsion_v0: self.object_repulsion_v0,
+                bond_potential: self.bond_potential,
+                repulsion: self.repulsion,
+                environment: super::v3::EnvironmentRecord {
+                    gravity: self.environment.gravity,
+                    walls: self.environment.walls,
+                    colliders: Vec::new(),
+                },
+                materials: self.materials,
+            }
+        }
+    }
+}
+
+/// Version 3 added colliders to the environment, their runtime state is not stored
+pub(crate) mod v3 {
+    use glam::Vec2;
+    use serde::{Serialize, Deserialize};
+
+    use crate::scene::Scene;
+    use crate::simulation::collider::{Collider, ColliderState, ContactLaw, Keyframe, Motion, Shape};
+    use crate::simulation::environment::{Environment, Wall};
+    use crate::simulation::forces::{BondPotential, ForceField, Repulsion};
+    use crate::simulation::material::MaterialLibrary;
+    use crate::simulation::node::Node;
+    use super::v1::{NodeRecord, BondRecord, BondPotentialRecord, WallRecord, MaterialsRecord};
+    use super::v2::RepulsionRecord;
+
+    #[derive(Serialize, Deserialize)]
+    pub enum ShapeRecord {
+        Segment { a: [f32; 2], b: [f32; 2] },
+        Polyline { points: Vec<[f32; 2]> },
+        Box { half_extents: [f32; 2] },
+        Circle { radius: f32 },
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct KeyframeRecord {
+        pub time: f32,
+        pub offset: [f32; 2],
+        pub angle: f32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub enum MotionRecord {
+        Static,
+        Constant { velocity: [f32; 2], angular_velocity: f32 },
+        Oscillating { amplitude: [f32; 2], angular_amplitude: f32, frequency: f32 },
+        Path { keyframes: Vec<KeyframeRecord> },
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub enum ContactLawRecord {
+        Repulsion,
+        Linear,
+        Hertz,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct ColliderRecord {
+        pub shape: ShapeRecord,
+        pub position: [f32; 2],
+        pub angle: f32,
+        pub motion: MotionRecord,
+        pub law: ContactLawRecord,
+        pub v0: f32,
+        pub dx: f32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct EnvironmentRecord {
+        pub gravity: [f32; 2],
+        pub walls: Vec<WallRecord>,
+        pub colliders: Vec<ColliderRecord>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct SceneFile {
+        pub nodes: Vec<NodeRecord>,
+        pub bonds: Vec<BondRecord>,
+        pub object_repulsion_dx: f32,
+        pub object_repulsion_v0: f32,
+        pub bond_potential: BondPotentialRecord,
+        pub repulsion: RepulsionRecord,
+        pub environment: EnvironmentRecord,
+        pub materials: MaterialsRecord,
+    }
+
+    impl From<&Collider> for ColliderRecord {
+        fn from(c: &Collider) -> Self {
+            ColliderRecord {
+                shape: match &c.shape {
+                    Shape::Segment { a, b } => ShapeRecord::Segment { a: a.to_array(), b: b.to_array() },
+                    Shape::Polyline { points } => ShapeRecord::Polyline { points: points.iter().map(|p| p.to_array()).collect() },
+                    Shape::Box { half_extents } => ShapeRecord::Box { half_extents: half_extents.to_array() },
+                    Shape::Circle { radius } => ShapeRecord::Circle { radius: *radius },
+                },
+                position: c.position.to_array(),
+                angle: c.angle,
+                motion: match &c.motion {
+                    Motion::Static => MotionRecord::Static,
+                    Motion::Constant { velocity, angular_velocity } => MotionRecord::Constant {
+                        velocity: velocity.to_array(),
+                        angular_velocity: *angular_velocity,
+                    },
+                    Motion::Oscillating { amplitude, angular_amplitude, frequency } => MotionRecord::Oscillating {
+                        amplitude: amplitude.to_array(),
+                        angular_amplitude: *angular_amplitude,
+                        frequency: *frequency,
+                    },
+                    Motion::Path { keyframes } => MotionRecord::Path {
+                        keyframes: keyframes.iter().map(|k| KeyframeRecord {
+                            time: k.time,
+                            offset: k.offset.to_array(),
+                            angle: k.angle,
+                        }).collect(),
+                    },
+                },
+                law: match c.law {
+                    ContactLaw::Repulsion => ContactLawRecord::Repulsion,
+                    ContactLaw::Linear => ContactLawRecord::Linear,
+                    ContactLaw::Hertz => ContactLawRecord::Hertz,
+                },
+                v0: c.v0,
+                dx: c.dx,
+            }
+        }
+    }
+
+    impl From<&ColliderRecord> for Collider {
+        /// Collider is placed at its pose at time 0
+        fn from(c: &ColliderRecord) -> Self {
+            let mut collider = Collider {
+                shape: match &c.shape {
+                    ShapeRecord::Segment { a, b } => Shape::Segment { a: Vec2::from(*a), b: Vec2::from(*b) },
+                    ShapeRecord::Polyline { points } => Shape::Polyline { points: points.iter().map(|p| Vec2::from(*p)).collect() },
+                    ShapeRecord::Box { half_extents } => Shape::Box { half_extents: Vec2::from(*half_extents) },
+                    ShapeRecord::Circle { radius } => Shape::Circle { radius: *radius },
+                },
+                position: Vec2::from(c.position),
+                angle: c.angle,
+                motion: match &c.motion {
+                    MotionRecord::Static => Motion::Static,
+                    MotionRecord::Constant { velocity, angular_velocity } => Motion::Constant {
+                        velocity: Vec2::from(*velocity),
+                        angular_velocity: *angular_velocity,
+                    },
+                    MotionRecord::Oscillating { amplitude, angular_amplitude, frequency } => Motion::Oscillating {
+                        amplitude: Vec2::from(*amplitude),
+                        angular_amplitude: *angular_amplitude,
+                        frequency: *frequency,
+                    },
+                    MotionRecord::Path { keyframes } => Motion::Path {
+                        keyframes: keyframes.iter().map(|k| Keyframe {
+                            time: k.time,
+                            offset: Vec2::from(k.offset),
+                            angle: k.angle,
+                        }).collect(),
+                    },
+                },
+                law: match c.law {
+                    ContactLawRecord::Repulsion => ContactLaw::Repulsion,
+                    ContactLawRecord::Linear => ContactLaw::Linear,
+                    ContactLawRecord::Hertz => ContactLaw::Hertz,
+                },
+                v0: c.v0,
+                dx: c.dx,
+                state: ColliderState::default(),
+            };
+            collider.move_to_time(0.0);
+            collider
+        }
+    }
+
+    impl From<&Environment> for EnvironmentRecord {
+        fn from(environment: &Environment) -> Self {
+            EnvironmentRecord {
+                gravity: environment.gravity.to_array(),
+                walls: environment.walls.iter().map(|w| WallRecord {
+                    point: w.point.to_array(),
+                    normal: w.normal.to_array(),
+                    v0: w.v0,
+                    dx: w.dx,
+                }).collect(),
+                colliders: environment.colliders.iter().map(ColliderRecord::from).collect(),
+            }
+        }
+    }
+
+    impl From<&EnvironmentRecord> for Environment {
+        fn from(environment: &EnvironmentRecord) -> Self {
+            Environment {
+                gravity: Vec2::from(environment.gravity),
+                walls: environment.walls.iter().map(|w| Wall {
+                    point: Vec2::from(w.point),
+                    normal: Vec2::from(w.normal),
+                    v0: w.v0,
+                    dx: w.dx,
+                }).collect(),
+                colliders: environment.colliders.iter().map(Collider::from).collect(),
+            }
+        }
+    }
+
+    impl From<&Scene> for SceneFile {
+        fn from(scene: &Scene) -> Self {
+            SceneFile {
+                nodes: scene.nodes.iter().map(NodeRecord::from).collect(),
+                bonds: scene.connections.iter().map(|((a, b), (rest_length, strength))| BondRecord {
+                    a: *a as u64,
+                    b: *b as u64,
+                    rest_length: *rest_length,
+                    strength: *strength,
+                }).collect(),
+                object_repulsion_dx: scene.object_repulsion_dx,
+                object_repulsion_v0: scene.object_repulsion_v0,
+                bond_potential: BondPotentialRecord::from(&scene.force_field.bond),
+                repulsion: RepulsionRecord::from(&scene.force_field.repulsion),
+                environment: EnvironmentRecord::from(&scene.environment),
+                materials: MaterialsRecord::from(&scene.materials),
+            }
+        }
+    }
+
+    impl From<&SceneFile> for Scene {
+        fn from(file: &SceneFile) -> Self {
+            Scene {
+                nodes: file.nodes.iter().map(Node::from).collect(),
+                connections: file.bonds.iter()
+                    .map(|bond| ((bond.a as usize, bond.b as usize), (bond.rest_length, bond.strength)))
+                    .collect(),
+                object_repulsion_dx: file.object_repulsion_dx,
+                object_repulsion_v0: file.object_repulsion_v0,
+                force_field: ForceField {
+                    bond: BondPotential::from(&file.bond_potential),
+                    repulsion: Repulsion::from(&file.repulsion),
+                },
+                environment: Environment::from(&file.environment),
+                materials: MaterialLibrary::from(&file.materials),
+            }
+        }
+    }
+}
+
+/// Files written before scene files had a header, raw bincode of two layouts of `Scene`
+mod unversioned {
+    use serde::{Serialize, Deserialize};
+
+    use crate::simulation::environment::Environment;
+    use super::v1;
+
+    /// Bond as stored in `BTreeMap` of `Scene::connections`, `((a, b), (rest_length, strength))`
+    pub type ConnectionRecord = ((u64, u64), (f32, f32));
+
+    #[derive(Serialize, Deserialize)]
+    pub struct NodeRecord {
+        pub position: [f32; 2],
+        pub velocity: [f32; 2],
+        pub last_acceleration: [f32; 2],
+        pub current_acceleration: [f32; 2],
+        pub mass: f32,
+        pub drag: f32,
+        pub object_id: u32,
+        pub is_boundary: bool,
+    }
+
+    /// First layout, scenes had no force field, environment nor materials
+    #[derive(Serialize, Deserialize)]
+    pub struct BaselineScene {
+        pub nodes: Vec<NodeRecord>,
+        pub connections: Vec<ConnectionRecord>,
+        pub object_repulsion_dx: f32,
+        pub object_repulsion_v0: f32,
+    }
+
+    /// Last layout before the header was added
+    #[derive(Serialize, Deserialize)]
+    pub struct Scene {
+        pub nodes: Vec<NodeRecord>,
+        pub connections: Vec<ConnectionRecord>,
+        pub object_repulsion_dx: f32,
+        pub object_repulsion_v0: f32,
+        pub bond_potential: v1::BondPotentialRecord,
+        pub environment: v1::EnvironmentRecord,
+        pub materials: v1::MaterialsRecord,
+    }
+
+    fn migrate_node(n: &NodeRecord) -> v1::NodeRecord {
+        v1::NodeRecord {
+            position: n.position,
+            velocity: n.velocity,
+            last_acceleration: n.last_acceleration,
+            current_acceleration: n.current_acceleration,
+            mass: n.mass,
+            drag: n.drag,
+            object_id: n.object_id,
+        }
+    }
+
+    fn migrate_connections(connections: &[ConnectionRecord]) -> Vec<v1::BondRecord> {
+        connections.iter().map(|((a, b), (rest_length, strength))| v1::BondRecord {
+            a: *a,
+            b: *b,
+            rest_length: *rest_length,
+            strength: *strength,
+        }).collect()
+    }
+
+    impl BaselineScene {
+        /// Uses force field, environment and materials that were built into the simulation at that time
+        pub fn migrate(self) -> v1::SceneFile {
+            v1::SceneFile {
+                nodes: self.nodes.iter().map(migrate_node).collect(),
+                bonds: migrate_connections(&self.connections),
+                object_repulsion_dx: self.object_repulsion_dx,
+                object_repulsion_v0: self.object_repulsion_v0,
+                bond_potential: v1::BondPotentialRecord::LennardJones,
+                environment: v1::EnvironmentRecord::from(&Environment::default()),
+                materials: v1::MaterialsRecord {
+                    list: Vec::new(),
+                    object_materials: Vec::new(),
+                    pair_repulsion: Vec::new(),
+                },
+            }
+        }
+    }
+
+    impl Scene {
+        pub fn migrate(self) -> v1::SceneFile {
+            v1::SceneFile {
+                nodes: self.nodes.iter().map(migrate_node).collect(),
+                bonds: migrate_connections(&self.connections),
+                object_repulsion_dx: self.object_repulsion_dx,
+                object_repulsion_v0: self.object_repulsion_v0,
+                bond_potential: self.bond_potential,
+                environment: self.environment,
+                materials: self.materials,
+            }
+        }
+    }
+}
+
+fn bincode_options() -> impl Options {
+    // the same encoding as `bincode::serialize`, but files with extra bytes are not accepted,
+    // so layouts of unversioned files can be told apart
+    bincode::DefaultOptions::new().with_fixint_encoding().reject_trailing_bytes()
+}
+
+/// Decodes file of any supported version into the schema of the current version
+fn decode(bytes: &[u8]) -> Result<(v3::SceneFile, u32), SceneLoadError> {
+    if !bytes.starts_with(&SCENE_MAGIC) {
+        return bincode_options().deserialize::<unversioned::Scene>(bytes)
+            .map(unversioned::Scene::migrate)
+            .or_else(|_| bincode_options().deserialize::<unversioned::BaselineScene>(bytes).map(unversioned::BaselineScene::migrate))
+            .map(|file| (file.migrate().migrate(), UNVERSIONED))
+            .map_err(SceneLoadError::Decode);
+    }
+
+    let body = &bytes[SCENE_MAGIC.len()..];
+    if body.len() < 4 {
+        return Err(SceneLoadError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+    }
+    let version = u32::from_le_bytes([body[0], body[1], body[2], body[3]]);
+    let body = &body[4..];
+    match version {
+        1 => bincode_options().deserialize::<v1::SceneFile>(body).map(|file| (file.migrate().migrate(), version)).map_err(SceneLoadError::Decode),
+        2 => bincode_options().deserialize::<v2::SceneFile>(body).map(|file| (file.migrate(), version)).map_err(SceneLoadError::Decode),
+        3 => bincode_options().deserialize::<v3::SceneFile>(body).map(|file| (file, version)).map_err(SceneLoadError::Decode),
+        _ => Err(SceneLoadError::UnsupportedVersion(version)),
+    }
+}
+
+/// Reads scene file of any supported version, returns the scene and version of the file.
+/// Boundary flags of nodes are not stored, see `Scene::update_boundary_nodes`.
+pub fn read(bytes: &[u8]) -> Result<(Scene, u32), SceneLoadError> {
+    let (file, version) = decode(bytes)?;
+    Ok((Scene::from(&file), version))
+}
+
+/// Writes header and the scene in the current version of the schema
+pub fn write(writer: &mut impl Write, scene: &Scene) -> Result<(), bincode::Error> {
+    writer.write_all(&SCENE_MAGIC)?;
+    writer.write_all(&SCENE_FORMAT_VERSION.to_le_bytes())?;
+    bincode::serialize_into(writer, &v3::SceneFile::from(scene))
+}
+
+pub fn load(path: &str) -> Result<(Scene, u32), SceneLoadError> {
+    let bytes = std::fs::read(path).map_err(SceneLoadError::Io)?;
+    read(&bytes)
+}
+
+pub fn save(path: &str, scene: &Scene) -> Result<(), bincode::Error> {
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+    write(&mut writer, scene)?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::*;
+    use crate::scene::builder::{NodeProperties, SceneBuilder};
+    use crate::simulation::collider::{Collider, Shape};
+    use crate::simulation::environment::{Environment, Wall};
+    use crate::simulation::forces::{BondPotential, CutoffShift, ForceField, Repulsion};
+    use crate::simulation::fracture::FractureRule;
+    use crate::simulation::material::{Material, MaterialLibrary};
+
+    /// Two objects, one of them of a material, with given repulsion and environment
+    fn scene(repulsion: Repulsion, environment: Environment) -> Scene {
+        let force_field = ForceField { bond: BondPotential::Morse, repulsion };
+        let mut builder = SceneBuilder::new(0.04, 100.0).with_force_field(force_field).with_environment(environment);
+        let steel = builder.add_material(Material {
+            name: "steel".to_string(),
+            density: 7800.0,
+            bond_stiffness: 200.0,
+            rest_spacing: 0.03,
+            break_strain: 0.2,
+            fracture: FractureRule::default(),
+            damping: 0.5,
+            repulsion_dx: 0.03,
+            repulsion_v0: 150.0,
+        });
+        builder.set_pair_repulsion(steel, steel, 0.035, 120.0);
+        builder.add_rectangle(3, 2, Vec2::new(0.0, 0.0), NodeProperties::new(0.03, 1.0, 1.0, 100.0));
+        let block = builder.add_rectangle(2, 2, Vec2::new(0.5, 0.0), NodeProperties::new(0.03, 2.0, 0.5, 50.0));
+        builder.assign_material(block, steel);
+        builder.set_velocity(block, Vec2::new(1.0, -2.0));
+        builder.build().unwrap()
+    }
+
+    fn with_header(version: u32, body: &[u8]) -> Vec<u8> {
+        let mut bytes = SCENE_MAGIC.to_vec();
+        bytes.extend_from_slice(&version.to_le_bytes());
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    /// Reads the file and recomputes boundary flags, as callers of `read` do
+    fn read_with_boundary(bytes: &[u8]) -> (Scene, u32) {
+        let (mut scene, version) = read(bytes).unwrap();
+        scene.update_boundary_nodes();
+        (scene, version)
+    }
+
+    fn v1_file(scene: &Scene) -> v1::SceneFile {
+        let file = v3::SceneFile::from(scene);
+        v1::SceneFile {
+            nodes: file.nodes,
+            bonds: file.bonds,
+            object_repulsion_dx: file.object_repulsion_dx,
+            object_repulsion_v0: file.object_repulsion_v0,
+            bond_potential: file.bond_potential,
+            environment: v1::EnvironmentRecord::from(&scene.environment),
+            materials: file.materials,
+        }
+    }
+
+    fn unversioned_nodes(scene: &Scene) -> Vec<unversioned::NodeRecord> {
+        scene.nodes.iter().map(|n| unversioned::NodeRecord {
+            position: n.position.to_array(),
+            velocity: n.velocity.to_array(),
+            last_acceleration: n.last_acceleration.to_array(),
+            current_acceleration: n.current_acceleration.to_array(),
+            mass: n.mass,
+            drag: n.drag,
+            object_id: n.object_id,
+            is_boundary: n.is_boundary,
+        }).collect()
+    }
+
+    fn unversioned_connections(scene: &Scene) -> Vec<unversioned::ConnectionRecord> {
+        scene.connections.iter().map(|((a, b), bond)| ((*a as u64, *b as u64), *bond)).collect()
+    }
+
+    #[test]
+    fn current_version_round_trips() {
+        let collider = Collider::new(Shape::Circle { radius: 0.1 }, Vec2::new(0.2, -0.5), 0.3, 100.0, 0.04);
+        let scene = scene(Repulsion::with_cutoff(2.5, CutoffShift::ShiftedForce), Environment::default().with_collider(collider));
+
+        let mut bytes = Vec::new();
+        write(&mut bytes, &scene).unwrap();
+
+        assert_eq!(read_with_boundary(&bytes), (scene, SCENE_FORMAT_VERSION));
+    }
+
+    #[test]
+    fn version_2_file_is_migrated() {
+        let scene = scene(Repulsion::with_cutoff(2.5, CutoffShift::ShiftedPotential), Environment::default());
+        let file = v3::SceneFile::from(&scene);
+        let file = v2::SceneFile {
+            nodes: file.nodes,
+            bonds: file.bonds,
+            object_repulsion_dx: file.object_repulsion_dx,
+            object_repulsion_v0: file.object_repulsion_v0,
+            bond_potential: file.bond_potential,
+            repulsion: file.repulsion,
+            environment: v1::EnvironmentRecord::from(&scene.environment),
+            materials: file.materials,
+        };
+        let bytes = with_header(2, &bincode::serialize(&file).unwrap());
+
+        assert_eq!(read_with_boundary(&bytes), (scene, 2));
+    }
+
+    #[test]
+    fn version_1_file_is_migrated_with_unlimited_repulsion() {
+        let scene = scene(Repulsion::UNLIMITED, Environment::empty().with_wall(Wall::floor(-0.5, 80.0, 0.05)));
+        let bytes = with_header(1, &bincode::serialize(&v1_file(&scene)).unwrap());
+
+        assert_eq!(read_with_boundary(&bytes), (scene, 1));
+    }
+
+    #[test]
+    fn unversioned_file_is_migrated() {
+        let scene = scene(Repulsion::UNLIMITED, Environment::default());
+        let v1 = v1_file(&scene);
+        let file = unversioned::Scene {
+            nodes: unversioned_nodes(&scene),
+            connections: unversioned_connections(&scene),
+            object_repulsion_dx: v1.object_repulsion_dx,
+            object_repulsion_v0: v1.object_repulsion_v0,
+            bond_potential: v1.bond_potential,
+            environment: v1.environment,
+            materials: v1.materials,
+        };
+
+        assert_eq!(read_with_boundary(&bincode::serialize(&file).unwrap()), (scene, UNVERSIONED));
+    }
+
+    #[test]
+    fn baseline_file_gets_default_force_field_environment_and_materials() {
+        let scene = scene(Repulsion::UNLIMITED, Environment::empty());
+        let file = unversioned::BaselineScene {
+            nodes: unversioned_nodes(&scene),
+            connections: unversioned_connections(&scene),
+            object_repulsion_dx: scene.object_repulsion_dx,
+            object_repulsion_v0: scene.object_repulsion_v0,
+        };
+        let expected = Scene {
+            force_field: ForceField::default(),
+            environment: Environment::default(),
+            materials: MaterialLibrary::default(),
+            ..scene
+        };
+
+        assert_eq!(read_with_boundary(&bincode::serialize(&file).unwrap()), (expected, UNVERSIONED));
+    }
+
+    #[test]
+    fn unknown_version_and_truncated_files_are_rejected() {
+        let mut bytes = Vec::new();
+        write(&mut bytes, &scene(Repulsion::UNLIMITED, Environment::default())).unwrap();
+
+        let next_version = SCENE_FORMAT_VERSION + 1;
+        assert!(matches!(read(&with_header(next_version, &bytes[12..])), Err(SceneLoadError::UnsupportedVersion(v)) if v == next_version));
+        assert!(matches!(read(&bytes[..bytes.len() - 1]), Err(SceneLoadError::Decode(_))));
+        assert!(matches!(read(&SCENE_MAGIC), Err(SceneLoadError::Io(_))));
+    }
+}
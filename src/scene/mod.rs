@@ -1,16 +1,93 @@
-use std::collections::HashMap;
-use crate::simulation::node::Node;
+use std::collections::BTreeMap;
+use crate::simulation::{node::Node, forces::ForceField, environment::Environment, material::MaterialLibrary};
 use serde::{Serialize, Deserialize};
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Scene {
     pub nodes: Vec<Node>,
-    pub connections: HashMap<(usize, usize), (f32, f32)>,
+    pub connections: BTreeMap<(usize, usize), (f32, f32)>,
     pub object_repulsion_dx: f32,
     pub object_repulsion_v0: f32,
+    pub force_field: ForceField,
+    pub environment: Environment,
+    pub materials: MaterialLibrary,
 }
 
-mod objects;
+#[derive(Debug)]
+pub enum SceneLoadError {
+    Io(std::io::Error),
+    Decode(bincode::Error),
+    UnsupportedVersion(u32),
+    Parse(ron::Error),
+    Invalid(Vec<validation::SceneError>),
+    UnknownMaterial(String),
+}
+
+impl std::fmt::Display for SceneLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SceneLoadError::Io(e) => write!(f, "cannot read scene file: {}", e),
+            SceneLoadError::Decode(e) => write!(f, "cannot decode scene file: {}", e),
+            SceneLoadError::UnsupportedVersion(version) => write!(
+                f,
+                "scene file version {} is not supported, newest supported version is {}",
+                version, format::SCENE_FORMAT_VERSION
+            ),
+            SceneLoadError::Parse(e) => write!(f, "cannot parse scene description: {}", e),
+            SceneLoadError::UnknownMaterial(name) => write!(f, "material {} is not defined", name),
+            SceneLoadError::Invalid(errors) => write!(f, "invalid scene:\n{}", validation::format_errors(errors)),
+        }
+    }
+}
+
+impl std::error::Error for SceneLoadError {}
+
+impl Scene {
+    /// Recomputes `is_boundary` of all nodes from bonds, replacing flags set by object builders
+    pub fn update_boundary_nodes(&mut self) {
+        let connections_structure = crate::simulation::general::calculate_connections_structure(&self.connections, &self.nodes);
+        crate::simulation::boundary::update_boundary_nodes(&mut self.nodes, &connections_structure);
+    }
+}
+
+/// Loads scene from `.ron` scene description or `.bincode` scene file of any supported version,
+/// loaded scene is validated and its boundary nodes are recomputed from bonds
+pub fn load_from_file(path: &str) -> Result<Scene, SceneLoadError> {
+    let mut scene: Scene = if path.ends_with(".ron") {
+        let text = std::fs::read_to_string(path).map_err(SceneLoadError::Io)?;
+        let description = description::SceneDescription::from_ron(&text).map_err(SceneLoadError::Parse)?;
+        description.compile()?
+    } else {
+        format::load(path)?.0
+    };
+    scene.validate().map_err(SceneLoadError::Invalid)?;
+    scene.update_boundary_nodes();
+    Ok(scene)
+}
+
+/// Turns command line argument into path of the scene file.
+///
+/// Paths ending with `.ron` or `.bincode` are used as they are, other arguments are treated
+/// as names of files inside `scenes/` directory, compiled `.bincode` file is preferred.
+pub fn scene_path_from_argument(argument: &str) -> String {
+    if argument.ends_with(".ron") || argument.ends_with(".bincode") {
+        return argument.to_string();
+    }
+
+    let compiled = format!("scenes/{}.bincode", argument);
+    let description = format!("scenes/{}.ron", argument);
+    if !std::path::Path::new(&compiled).exists() && std::path::Path::new(&description).exists() {
+        description
+    } else {
+        compiled
+    }
+}
+
+pub mod objects;
+pub mod builder;
+pub mod description;
+pub mod format;
+pub mod validation;
 pub mod default;
 pub mod scene01;
 pub mod scene02;
@@ -23,4 +100,5 @@ pub mod blank;
 pub mod pressure;
 pub mod pressure02;
 pub mod presentation01;
-pub mod presentation02;
\ No newline at end of file
+pub mod presentation02;
+pub mod container;
\ No newline at end of file
@@ -1,5 +1,56 @@
 use crate::simulation::node::{Node};
 use glam::Vec2;
+use serde::{Serialize, Deserialize};
+
+/// Arrangement of nodes inside generated objects
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Lattice {
+    /// Nodes connected with 4 direct and 4 diagonal neighbours, elastic response depends on direction
+    Square,
+    /// Hexagonal close packing, every node has 6 neighbours at equal distance and response is isotropic
+    Hexagonal,
+}
+
+impl Default for Lattice {
+    fn default() -> Self {
+        Lattice::Square
+    }
+}
+
+impl Lattice {
+    /// Distance between neighbouring rows of nodes
+    pub fn row_spacing(&self, spacing: f32) -> f32 {
+        match self {
+            Lattice::Square => spacing,
+            Lattice::Hexagonal => spacing * 3.0_f32.sqrt() / 2.0,
+        }
+    }
+
+    /// Area belonging to a single node
+    pub fn cell_area(&self, spacing: f32) -> f32 {
+        spacing * self.row_spacing(spacing)
+    }
+
+    /// Position of lattice site in column `x` and row `y` relative to site `(0, 0)`,
+    /// odd rows of hexagonal lattice are shifted by half of spacing
+    pub fn site(&self, x: i64, y: i64, spacing: f32) -> Vec2 {
+        let shift = match self {
+            Lattice::Square => 0.0,
+            Lattice::Hexagonal => 0.5 * y.rem_euclid(2) as f32,
+        };
+        Vec2::new((x as f32 + shift) * spacing, y as f32 * self.row_spacing(spacing))
+    }
+
+    /// Nodes closer than this distance are connected.
+    /// Square lattice connects diagonal neighbours at `spacing * sqrt(2)`,
+    /// hexagonal lattice only nearest neighbours, next ones are at `spacing * sqrt(3)`.
+    pub fn search_distance(&self, spacing: f32) -> f32 {
+        match self {
+            Lattice::Square => spacing * 1.5,
+            Lattice::Hexagonal => spacing * 1.2,
+        }
+    }
+}
 
 pub fn build_rectangle(
     size_x: usize,
@@ -10,6 +61,21 @@ pub fn build_rectangle(
     mass: f32,
     damping: f32,
     object_id: u32
+) -> Vec<Node> {
+    build_rectangle_on_lattice(Lattice::Square, size_x, size_y, spacing, offset_x, offset_y, mass, damping, object_id)
+}
+
+/// Rectangle of `size_x` nodes in a row and `size_y` rows, first node is placed at the offset
+pub fn build_rectangle_on_lattice(
+    lattice: Lattice,
+    size_x: usize,
+    size_y: usize,
+    spacing: f32,
+    offset_x: f32,
+    offset_y: f32,
+    mass: f32,
+    damping: f32,
+    object_id: u32
 ) -> Vec<Node> {
     let mut nodes = Vec::with_capacity(size_x * size_y);
     for y in 0..size_y {
@@ -18,10 +84,7 @@ pub fn build_rectangle(
             let is_boundary = if y == size_y - 1 || y == 0 || x == size_x - 1 || x == 0 { true } else { false };
 
             nodes.push(Node {
-                position: Vec2::new(
-                    offset_x + (x as f32) * spacing,
-                    offset_y + (y as f32) * spacing,
-                ),
+                position: Vec2::new(offset_x, offset_y) + lattice.site(x as i64, y as i64, spacing),
                 velocity: Vec2::new(0.0, 0.0),
                 current_acceleration: Vec2::new(0.0, 0.0),
                 last_acceleration: Vec2::new(0.0, 0.0),
@@ -44,6 +107,25 @@ pub fn build_circle(
     damping: f32,
     object_id: u32
 ) -> Vec<Node> {
+    build_circle_on_lattice(Lattice::Square, layers, spacing, offset_x, offset_y, mass, damping, object_id)
+}
+
+/// Circle with radius of `layers - 1` spacings centered at the offset.
+/// Square lattice places nodes on concentric rings, hexagonal lattice fills the disk with central node in the middle.
+pub fn build_circle_on_lattice(
+    lattice: Lattice,
+    layers: usize,
+    spacing: f32,
+    offset_x: f32,
+    offset_y: f32,
+    mass: f32,
+    damping: f32,
+    object_id: u32
+) -> Vec<Node> {
+    if lattice == Lattice::Hexagonal {
+        return build_hexagonal_disk(layers, spacing, Vec2::new(offset_x, offset_y), mass, damping, object_id);
+    }
+
     let mut nodes = Vec::new();
     for layer in 0..layers {
         let r = spacing * layer as f32;
@@ -69,14 +151,259 @@ pub fn build_circle(
     return nodes;
 }
 
-use std::{collections::HashMap, f32::consts::PI};
+fn build_hexagonal_disk(
+    layers: usize,
+    spacing: f32,
+    center: Vec2,
+    mass: f32,
+    damping: f32,
+    object_id: u32
+) -> Vec<Node> {
+    let radius = spacing * layers.saturating_sub(1) as f32;
+    let rows = (radius / Lattice::Hexagonal.row_spacing(spacing)).ceil() as i64;
+    let columns = layers as i64;
+
+    let mut nodes = Vec::new();
+    for y in -rows..=rows {
+        for x in -columns..=columns {
+            let local = Lattice::Hexagonal.site(x, y, spacing);
+            // small tolerance keeps nodes lying exactly on the circle
+            if local.length() > radius + spacing * 0.01 {
+                continue;
+            }
+
+            nodes.push(Node {
+                position: center + local,
+                velocity: Vec2::new(0.0, 0.0),
+                current_acceleration: Vec2::new(0.0, 0.0),
+                last_acceleration: Vec2::new(0.0, 0.0),
+                mass: mass,
+                drag: damping,
+                object_id: object_id,
+                is_boundary: radius - local.length() < spacing
+            });
+        }
+    }
+    return nodes;
+}
+
+// https://en.wikipedia.org/wiki/Point_in_polygon#Ray_casting_algorithm
+pub fn point_in_polygon(point: Vec2, vertices: &[Vec2]) -> bool {
+    let mut inside = false;
+    let mut j = vertices.len() - 1;
+    for i in 0..vertices.len() {
+        let a = vertices[i];
+        let b = vertices[j];
+        if (a.y > point.y) != (b.y > point.y) && point.x < (b.x - a.x) * (point.y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+pub fn distance_to_segment(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let t = ((point - a).dot(ab) / ab.length_squared()).clamp(0.0, 1.0);
+    (a + ab * t - point).length()
+}
+
+pub fn distance_to_outline(point: Vec2, vertices: &[Vec2]) -> f32 {
+    let mut j = vertices.len() - 1;
+    let mut distance = f32::MAX;
+    for i in 0..vertices.len() {
+        distance = distance.min(distance_to_segment(point, vertices[j], vertices[i]));
+        j = i;
+    }
+    distance
+}
+
+/// Places nodes on lattice covering rectangle from `min` to `max`, keeping only nodes for which `inside` is true.
+fn fill_region(
+    lattice: Lattice,
+    min: Vec2,
+    max: Vec2,
+    spacing: f32,
+    mass: f32,
+    damping: f32,
+    object_id: u32,
+    inside: impl Fn(Vec2) -> bool,
+    is_boundary: impl Fn(Vec2) -> bool
+) -> Vec<Node> {
+    let size_x = ((max.x - min.x) / spacing).ceil() as i64;
+    let size_y = ((max.y - min.y) / lattice.row_spacing(spacing)).ceil() as i64;
+    // lattice is shifted by half of spacing so no node lies exactly on axis aligned edges
+    let origin = min + Vec2::new(0.5 * spacing, 0.5 * lattice.row_spacing(spacing));
+
+    let mut nodes = Vec::new();
+    for y in 0..size_y {
+        for x in 0..size_x {
+            let position = origin + lattice.site(x, y, spacing);
+            if !inside(position) {
+                continue;
+            }
+
+            nodes.push(Node {
+                position,
+                velocity: Vec2::new(0.0, 0.0),
+                current_acceleration: Vec2::new(0.0, 0.0),
+                last_acceleration: Vec2::new(0.0, 0.0),
+                mass: mass,
+                drag: damping,
+                object_id: object_id,
+                is_boundary: is_boundary(position)
+            });
+        }
+    }
+    return nodes;
+}
+
+/// Fills closed polygon with nodes placed on lattice.
+/// Nodes closer to the outline than `spacing` are marked as boundary.
+pub fn build_polygon(
+    lattice: Lattice,
+    vertices: &[Vec2],
+    spacing: f32,
+    mass: f32,
+    damping: f32,
+    object_id: u32
+) -> Vec<Node> {
+    build_polygon_with_holes(lattice, vertices, &[], spacing, mass, damping, object_id)
+}
+
+/// Fills closed polygon with nodes, leaving out areas inside `holes`.
+/// Nodes closer than `spacing` to the outline or to any of the holes are marked as boundary.
+pub fn build_polygon_with_holes(
+    lattice: Lattice,
+    vertices: &[Vec2],
+    holes: &[Vec<Vec2>],
+    spacing: f32,
+    mass: f32,
+    damping: f32,
+    object_id: u32
+) -> Vec<Node> {
+    assert!(vertices.len() > 2);
+    assert!(holes.iter().all(|hole| hole.len() > 2));
+
+    let min = vertices.iter().fold(Vec2::new(f32::MAX, f32::MAX), |acc, v| acc.min(*v));
+    let max = vertices.iter().fold(Vec2::new(f32::MIN, f32::MIN), |acc, v| acc.max(*v));
+
+    fill_region(
+        lattice,
+        min,
+        max,
+        spacing,
+        mass,
+        damping,
+        object_id,
+        |position| {
+            point_in_polygon(position, vertices) && !holes.iter().any(|hole| point_in_polygon(position, hole))
+        },
+        |position| {
+            distance_to_outline(position, vertices) < spacing
+                || holes.iter().any(|hole| distance_to_outline(position, hole) < spacing)
+        }
+    )
+}
+
+/// Checks if point lies on set pixel of the mask.
+/// `mask[0]` is the top row, `offset` is the bottom left corner of the mask.
+pub fn point_in_mask(point: Vec2, mask: &[Vec<bool>], pixel_size: f32, offset: Vec2) -> bool {
+    let local = (point - offset) / pixel_size;
+    if local.x < 0.0 || local.y < 0.0 {
+        return false;
+    }
+
+    let x = local.x as usize;
+    let y_from_bottom = local.y as usize;
+    if y_from_bottom >= mask.len() {
+        return false;
+    }
+
+    let row = &mask[mask.len() - 1 - y_from_bottom];
+    x < row.len() && row[x]
+}
+
+/// Fills set pixels of binary mask with nodes, each pixel is a square with side `pixel_size`.
+/// Nodes with empty space closer than `spacing` in any direction are marked as boundary.
+pub fn build_mask(
+    lattice: Lattice,
+    mask: &[Vec<bool>],
+    pixel_size: f32,
+    offset_x: f32,
+    offset_y: f32,
+    spacing: f32,
+    mass: f32,
+    damping: f32,
+    object_id: u32
+) -> Vec<Node> {
+    let width = mask.iter().map(|row| row.len()).max().unwrap_or(0);
+    let offset = Vec2::new(offset_x, offset_y);
+    let max = offset + Vec2::new(width as f32, mask.len() as f32) * pixel_size;
+
+    // probing directions used to find empty space around the node
+    let directions: Vec<Vec2> = (0..8).map(|k| {
+        let angle = PI / 4.0 * k as f32;
+        Vec2::new(angle.cos(), angle.sin())
+    }).collect();
+
+    fill_region(
+        lattice,
+        offset,
+        max,
+        spacing,
+        mass,
+        damping,
+        object_id,
+        |position| point_in_mask(position, mask, pixel_size, offset),
+        |position| {
+            directions.iter().any(|d| !point_in_mask(position + *d * spacing, mask, pixel_size, offset))
+        }
+    )
+}
+
+/// Converts rows of text into mask, `#` marks set pixel
+pub fn mask_from_rows(rows: &[String]) -> Vec<Vec<bool>> {
+    rows.iter().map(|row| row.chars().map(|c| c == '#').collect()).collect()
+}
+
+/// Mass weighted centre of the nodes
+pub fn center_of_mass(nodes: &[Node]) -> Vec2 {
+    let total_mass: f32 = nodes.iter().map(|n| n.mass).sum();
+    nodes.iter().fold(Vec2::new(0.0, 0.0), |acc, n| acc + n.position * n.mass) / total_mass
+}
+
+/// Adds the same velocity to all nodes
+pub fn add_velocity(nodes: &mut [Node], velocity: Vec2) {
+    nodes.iter_mut().for_each(|n| n.velocity += velocity);
+}
+
+/// Adds velocity of rigid rotation around centre of mass, positive `angular_velocity` is counter clockwise
+pub fn add_angular_velocity(nodes: &mut [Node], angular_velocity: f32) {
+    let center = center_of_mass(nodes);
+    nodes.iter_mut().for_each(|n| {
+        let r = n.position - center;
+        n.velocity += Vec2::new(-r.y, r.x) * angular_velocity;
+    });
+}
+
+/// Scales positions relative to centre of mass by `1 + strain` along each axis.
+/// Applied after connections are built it stretches (positive strain) or compresses (negative strain)
+/// the object relative to rest lengths of its bonds.
+pub fn apply_strain(nodes: &mut [Node], strain: Vec2) {
+    let center = center_of_mass(nodes);
+    let scale = Vec2::new(1.0, 1.0) + strain;
+    nodes.iter_mut().for_each(|n| n.position = center + (n.position - center) * scale);
+}
+
+use std::{collections::{BTreeMap, HashMap}, f32::consts::PI};
 
 pub fn build_connections_map(
     nodes: &Vec<Node>,
     search_distance: f32,
     v0: f32,
     offset: usize,
-) -> HashMap<(usize, usize), (f32, f32)> {
+) -> BTreeMap<(usize, usize), (f32, f32)> {
     let mut connections: Vec<Vec<(usize, f32)>> = Vec::new();
 
     for i in 0..nodes.len() {
@@ -96,7 +423,7 @@ pub fn build_connections_map(
         connections.push(row);
     }
 
-    let mut connections_map = HashMap::new();
+    let mut connections_map = BTreeMap::new();
 
     connections.iter().enumerate().for_each(|(i, arr)| {
         arr.iter().for_each(|(j, dx)| {
@@ -1,8 +1,11 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::vec::Vec;
 
 use super::objects;
 use super::Scene;
+use crate::simulation::forces::ForceField;
+use crate::simulation::environment::Environment;
+use crate::simulation::material::MaterialLibrary;
 
 pub fn generate() -> Scene {
 
@@ -31,7 +34,7 @@ pub fn generate() -> Scene {
     // let mut nodes4 = objects::build_rectangle(object3_sx, object3_sy, spacing1, -0.4, -0.935, 200.0, 0.0, 3);
     // let connections_map_4 = objects::build_connections_map(&nodes4, spacing1 * 1.5, 1000.0, object1_st + object2_st + object3_st);
 
-    let mut connections_map: HashMap<(usize, usize), (f32, f32)> = HashMap::new();
+    let mut connections_map: BTreeMap<(usize, usize), (f32, f32)> = BTreeMap::new();
     connections_map.extend(connections_map_1);
     connections_map.extend(connections_map_2);
     // connections_map.extend(connections_map_3);
@@ -48,5 +51,8 @@ pub fn generate() -> Scene {
         connections: connections_map,
         object_repulsion_dx: 0.015,
         object_repulsion_v0: 100.0,
+        force_field: ForceField::default(),
+        environment: Environment::default(),
+        materials: MaterialLibrary::default(),
     }
 }
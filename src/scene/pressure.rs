@@ -1,8 +1,11 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::vec::Vec;
 
 use super::objects;
 use super::Scene;
+use crate::simulation::forces::ForceField;
+use crate::simulation::environment::Environment;
+use crate::simulation::material::MaterialLibrary;
 
 pub fn generate() -> Scene {
 
@@ -14,7 +17,7 @@ pub fn generate() -> Scene {
     let connections_map_1 = objects::build_connections_map(&nodes1, spacing1 * 1.5, 0.1, 0);
 
 
-    let mut connections_map: HashMap<(usize, usize), (f32, f32)> = HashMap::new();
+    let mut connections_map: BTreeMap<(usize, usize), (f32, f32)> = BTreeMap::new();
     connections_map.extend(connections_map_1);
 
     let mut nodes = Vec::new();
@@ -25,5 +28,8 @@ pub fn generate() -> Scene {
         connections: connections_map,
         object_repulsion_dx: 0.05,
         object_repulsion_v0: 100.0,
+        force_field: ForceField::default(),
+        environment: Environment::default(),
+        materials: MaterialLibrary::default(),
     }
 }
@@ -1,8 +1,11 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::vec::Vec;
 
 use super::objects;
 use super::Scene;
+use crate::simulation::forces::ForceField;
+use crate::simulation::environment::Environment;
+use crate::simulation::material::MaterialLibrary;
 
 pub fn generate() -> Scene {
 
@@ -19,7 +22,7 @@ pub fn generate() -> Scene {
     let mut nodes2 = objects::build_circle(4, spacing2, -0.12, 0.8, 8.0, 0.0, 2);
     let connections_map_2 = objects::build_connections_map(&nodes2, spacing2 * 1.5, 500.0, object1_st);
 
-    let mut connections_map: HashMap<(usize, usize), (f32, f32)> = HashMap::new();
+    let mut connections_map: BTreeMap<(usize, usize), (f32, f32)> = BTreeMap::new();
     connections_map.extend(connections_map_1);
     connections_map.extend(connections_map_2);
 
@@ -32,5 +35,8 @@ pub fn generate() -> Scene {
         connections: connections_map,
         object_repulsion_dx: 0.07,
         object_repulsion_v0: 100.0,
+        force_field: ForceField::default(),
+        environment: Environment::default(),
+        materials: MaterialLibrary::default(),
     }
 }
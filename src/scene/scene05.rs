@@ -1,8 +1,11 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::vec::Vec;
 
 use super::objects;
 use super::Scene;
+use crate::simulation::forces::ForceField;
+use crate::simulation::environment::Environment;
+use crate::simulation::material::MaterialLibrary;
 
 pub fn generate() -> Scene {
     let object1_sx = 180;
@@ -20,7 +23,7 @@ pub fn generate() -> Scene {
     let mut nodes3 = objects::build_rectangle(object1_sy / 2, object1_sx / 2, spacing * 1.2, 0.52, 0.4, 0.6, 0.4, 3);
     let connections_map_3 = objects::build_connections_map(&nodes3, spacing * 2.0, 300.0, nodes1.len() + nodes2.len());
 
-    let mut connections_map: HashMap<(usize, usize), (f32, f32)> = HashMap::new();
+    let mut connections_map: BTreeMap<(usize, usize), (f32, f32)> = BTreeMap::new();
     connections_map.extend(connections_map_1);
     connections_map.extend(connections_map_2);
     connections_map.extend(connections_map_3);
@@ -35,5 +38,8 @@ pub fn generate() -> Scene {
         connections: connections_map,
         object_repulsion_dx: 0.015,
         object_repulsion_v0: 100.0,
+        force_field: ForceField::default(),
+        environment: Environment::default(),
+        materials: MaterialLibrary::default(),
     }
 }
\ No newline at end of file
@@ -1,5 +1,8 @@
 use super::objects;
 use super::Scene;
+use crate::simulation::forces::ForceField;
+use crate::simulation::environment::Environment;
+use crate::simulation::material::MaterialLibrary;
 
 pub fn generate(object_size: usize) -> Scene {
     let spacing = 0.6 / object_size as f32;
@@ -14,5 +17,8 @@ pub fn generate(object_size: usize) -> Scene {
         connections: connections_map,
         object_repulsion_dx: spacing * 0.85,
         object_repulsion_v0: 10.0,
+        force_field: ForceField::default(),
+        environment: Environment::default(),
+        materials: MaterialLibrary::default(),
     }
 }
\ No newline at end of file
@@ -0,0 +1,292 @@
+use std::fmt;
+
+use super::Scene;
+use crate::simulation::collider::{Motion, Shape};
+
+/// Nodes closer than this are treated as lying in the same place
+pub const COINCIDENT_DISTANCE: f32 = 1e-6;
+
+/// Problem found in a scene by `Scene::validate`
+#[derive(Clone, PartialEq, Debug)]
+pub enum SceneError {
+    /// Connection refers to node that does not exist
+    ConnectionIndexOutOfRange { connection: (usize, usize), node_count: usize },
+    /// Connection key is not ordered as `(i, j)` with `i < j`
+    UnsortedConnection { connection: (usize, usize) },
+    /// Both `(i, j)` and `(j, i)` are present
+    DuplicateConnection { connection: (usize, usize) },
+    /// Rest length `dx` of connection is not positive
+    InvalidRestLength { connection: (usize, usize), dx: f32 },
+    NonFiniteConnection { connection: (usize, usize) },
+    NonFiniteNode { node: usize },
+    NonPositiveMass { node: usize, mass: f32 },
+    CoincidentNodes { a: usize, b: usize },
+    /// Two objects were given the same object id
+    DuplicateObjectId { object_id: u32 },
+    /// Object id is `u32::MAX`, ids of objects added later or of fragments would overflow
+    ObjectIdOutOfRange { object_id: u32 },
+    /// Object is assigned material that is not in the material list
+    UnknownMaterial { object_id: u32, material: usize },
+    /// Scene wide parameter like repulsion or wall settings is invalid
+    InvalidParameter { name: &'static str, value: f32 },
+    /// Parameter of object in `SceneDescription::objects` is invalid, `object` is its index in the list,
+    /// or in the order of adding for objects added directly to `SceneBuilder`
+    InvalidObjectParameter { object: usize, name: &'static str, value: f32 },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::ConnectionIndexOutOfRange { connection, node_count } =>
+                write!(f, "connection {:?} refers to node outside of {} nodes", connection, node_count),
+            SceneError::UnsortedConnection { connection } =>
+                write!(f, "connection {:?} is not ordered from lower to higher index", connection),
+            SceneError::DuplicateConnection { connection } =>
+                write!(f, "connection {:?} is present in both directions", connection),
+            SceneError::InvalidRestLength { connection, dx } =>
+                write!(f, "connection {:?} has invalid rest length {}", connection, dx),
+            SceneError::NonFiniteConnection { connection } =>
+                write!(f, "connection {:?} has non finite parameters", connection),
+            SceneError::NonFiniteNode { node } =>
+                write!(f, "node {} has non finite position, velocity or acceleration", node),
+            SceneError::NonPositiveMass { node, mass } =>
+                write!(f, "node {} has invalid mass {}", node, mass),
+            SceneError::CoincidentNodes { a, b } =>
+                write!(f, "nodes {} and {} are in the same place", a, b),
+            SceneError::DuplicateObjectId { object_id } =>
+                write!(f, "object id {} is used by more than one object", object_id),
+            SceneError::ObjectIdOutOfRange { object_id } =>
+                write!(f, "object id {} is too large, ids must be lower than {}", object_id, u32::MAX),
+            SceneError::UnknownMaterial { object_id, material } =>
+                write!(f, "object {} uses material {} which does not exist", object_id, material),
+            SceneError::InvalidParameter { name, value } =>
+                write!(f, "invalid value of {}: {}", name, value),
+            SceneError::InvalidObjectParameter { object, name, value } =>
+                write!(f, "object {} has invalid value of {}: {}", object, name, value),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+/// Formats list of errors returned by `Scene::validate`, one error per line
+pub fn format_errors(errors: &[SceneError]) -> String {
+    errors.iter().map(|e| e.to_string()).collect::<Vec<String>>().join("\n")
+}
+
+impl Scene {
+    /// Checks that scene can be simulated, returns all problems found
+    pub fn validate(&self) -> Result<(), Vec<SceneError>> {
+        let mut errors = Vec::new();
+
+        let mut check_parameter = |name: &'static str, value: f32| {
+            if !value.is_finite() || value <= 0.0 {
+                errors.push(SceneError::InvalidParameter { name, value });
+            }
+        };
+        check_parameter("object_repulsion_dx", self.object_repulsion_dx);
+        check_parameter("object_repulsion_v0", self.object_repulsion_v0);
+        if let Some(cutoff) = self.force_field.repulsion.cutoff {
+            check_parameter("repulsion cutoff", cutoff);
+        }
+        self.environment.walls.iter().for_each(|wall| {
+            check_parameter("wall dx", wall.dx);
+            check_parameter("wall v0", wall.v0);
+            check_parameter("wall normal length", wall.normal.length());
+        });
+        self.environment.colliders.iter().for_each(|collider| {
+            check_parameter("collider dx", collider.dx);
+            check_parameter("collider v0", collider.v0);
+            match &collider.shape {
+                Shape::Segment { a, b } => check_parameter("collider segment length", a.distance(*b)),
+                Shape::Polyline { points } => check_parameter("collider polyline segments", points.len().saturating_sub(1) as f32),
+                Shape::Box { half_extents } => {
+                    check_parameter("collider half width", half_extents.x);
+                    check_parameter("collider half height", half_extents.y);
+                }
+                Shape::Circle { radius } => check_parameter("collider radius", *radius),
+            }
+            if let Motion::Path { keyframes } = &collider.motion {
+                check_parameter("collider path keyframes", keyframes.len() as f32);
+                keyframes.windows(2).for_each(|k| check_parameter("collider keyframe interval", k[1].time - k[0].time));
+            }
+        });
+        self.materials.list.iter().for_each(|material| {
+            check_parameter("material density", material.density);
+            check_parameter("material bond stiffness", material.bond_stiffness);
+            check_parameter("material rest spacing", material.rest_spacing);
+            check_parameter("material break strain", material.break_strain);
+            check_parameter("material repulsion dx", material.repulsion_dx);
+            check_parameter("material repulsion v0", material.repulsion_v0);
+        });
+        self.materials.pair_repulsion.values().for_each(|(dx, v0)| {
+            check_parameter("material pair repulsion dx", *dx);
+            check_parameter("material pair repulsion v0", *v0);
+        });
+        self.materials.list.iter().filter(|m| !m.damping.is_finite() || m.damping < 0.0).for_each(|m| {
+            errors.push(SceneError::InvalidParameter { name: "material damping", value: m.damping });
+        });
+        if !self.environment.gravity.is_finite() {
+            errors.push(SceneError::InvalidParameter { name: "gravity", value: self.environment.gravity.length() });
+        }
+        self.environment.colliders.iter().filter(|c| !c.position.is_finite() || !c.angle.is_finite()).for_each(|c| {
+            errors.push(SceneError::InvalidParameter { name: "collider position", value: c.position.length() + c.angle });
+        });
+
+        let mut object_materials: Vec<(&u32, &usize)> = self.materials.object_materials.iter().collect();
+        object_materials.sort();
+        object_materials.iter().for_each(|(&object_id, &material)| {
+            if material >= self.materials.list.len() {
+                errors.push(SceneError::UnknownMaterial { object_id, material });
+            }
+        });
+        self.materials.pair_repulsion.keys().for_each(|&(a, b)| {
+            if a.max(b) >= self.materials.list.len() {
+                errors.push(SceneError::InvalidParameter { name: "material pair repulsion index", value: a.max(b) as f32 });
+            }
+        });
+
+        self.nodes.iter().enumerate().for_each(|(i, n)| {
+            let vectors_finite = n.position.is_finite()
+                && n.velocity.is_finite()
+                && n.last_acceleration.is_finite()
+                && n.current_acceleration.is_finite()
+                && n.drag.is_finite();
+            if !vectors_finite {
+                errors.push(SceneError::NonFiniteNode { node: i });
+            }
+            if !n.mass.is_finite() || n.mass <= 0.0 {
+                errors.push(SceneError::NonPositiveMass { node: i, mass: n.mass });
+            }
+        });
+
+        self.connections.iter().for_each(|(&(i, j), &(dx, v0))| {
+            let connection = (i, j);
+            if i >= self.nodes.len() || j >= self.nodes.len() {
+                errors.push(SceneError::ConnectionIndexOutOfRange { connection, node_count: self.nodes.len() });
+            }
+            if i >= j {
+                errors.push(SceneError::UnsortedConnection { connection });
+            }
+            if i > j && self.connections.contains_key(&(j, i)) {
+                errors.push(SceneError::DuplicateConnection { connection });
+            }
+            if !dx.is_finite() || !v0.is_finite() {
+                errors.push(SceneError::NonFiniteConnection { connection });
+            } else if dx <= 0.0 {
+                errors.push(SceneError::InvalidRestLength { connection, dx });
+            }
+        });
+
+        // sweep along x axis, only nodes with close x coordinates need to be compared
+        let mut order: Vec<usize> = (0..self.nodes.len())
+            .filter(|i| self.nodes[*i].position.is_finite())
+            .collect();
+        order.sort_by(|a, b| self.nodes[*a].position.x.partial_cmp(&self.nodes[*b].position.x).unwrap());
+
+        for (k, &a) in order.iter().enumerate() {
+            let pa = self.nodes[a].position;
+            for &b in order[k + 1..].iter() {
+                let pb = self.nodes[b].position;
+                if pb.x - pa.x >= COINCIDENT_DISTANCE {
+                    break;
+                }
+                if pa.distance(pb) < COINCIDENT_DISTANCE {
+                    errors.push(SceneError::CoincidentNodes { a: a.min(b), b: a.max(b) });
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use glam::Vec2;
+
+    use super::*;
+    use crate::simulation::environment::Environment;
+    use crate::simulation::forces::ForceField;
+    use crate::simulation::material::MaterialLibrary;
+    use crate::simulation::node::Node;
+
+    /// Three nodes in a row bonded to their neighbours
+    fn scene() -> Scene {
+        let node = |x: f32| Node { position: Vec2::new(x, 0.0), mass: 1.0, drag: 1.0, object_id: 1, ..Default::default() };
+        Scene {
+            nodes: vec![node(0.0), node(0.03), node(0.06)],
+            connections: BTreeMap::from([((0, 1), (0.03, 100.0)), ((1, 2), (0.03, 100.0))]),
+            object_repulsion_dx: 0.04,
+            object_repulsion_v0: 100.0,
+            force_field: ForceField::default(),
+            environment: Environment::default(),
+            materials: MaterialLibrary::default(),
+        }
+    }
+
+    #[test]
+    fn valid_scene_passes() {
+        assert_eq!(scene().validate(), Ok(()));
+    }
+
+    #[test]
+    fn invalid_connections_are_reported() {
+        let mut scene = scene();
+        scene.connections.insert((1, 0), (0.03, 100.0));
+        scene.connections.insert((0, 2), (0.0, 100.0));
+        scene.connections.insert((1, 5), (0.03, f32::NAN));
+
+        assert_eq!(scene.validate(), Err(vec![
+            SceneError::InvalidRestLength { connection: (0, 2), dx: 0.0 },
+            SceneError::UnsortedConnection { connection: (1, 0) },
+            SceneError::DuplicateConnection { connection: (1, 0) },
+            SceneError::ConnectionIndexOutOfRange { connection: (1, 5), node_count: 3 },
+            SceneError::NonFiniteConnection { connection: (1, 5) },
+        ]));
+    }
+
+    #[test]
+    fn invalid_nodes_are_reported() {
+        let mut scene = scene();
+        scene.nodes[0].velocity.x = f32::INFINITY;
+        scene.nodes[1].mass = 0.0;
+        scene.nodes[2].position = scene.nodes[0].position + Vec2::new(1e-7, 0.0);
+
+        assert_eq!(scene.validate(), Err(vec![
+            SceneError::NonFiniteNode { node: 0 },
+            SceneError::NonPositiveMass { node: 1, mass: 0.0 },
+            SceneError::CoincidentNodes { a: 0, b: 2 },
+        ]));
+    }
+
+    #[test]
+    fn invalid_parameters_and_materials_are_reported() {
+        let mut scene = scene();
+        scene.object_repulsion_dx = -0.04;
+        scene.environment.walls[0].v0 = f32::NAN;
+        scene.materials.object_materials.insert(1, 0);
+
+        let errors = scene.validate().unwrap_err();
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[0], SceneError::InvalidParameter { name: "object_repulsion_dx", value: -0.04 });
+        assert!(matches!(errors[1], SceneError::InvalidParameter { name: "wall v0", value } if value.is_nan()));
+        assert_eq!(errors[2], SceneError::UnknownMaterial { object_id: 1, material: 0 });
+    }
+
+    #[test]
+    fn errors_are_formatted_one_per_line() {
+        let errors = [
+            SceneError::NonPositiveMass { node: 1, mass: 0.0 },
+            SceneError::DuplicateObjectId { object_id: 3 },
+        ];
+
+        assert_eq!(format_errors(&errors), "node 1 has invalid mass 0\nobject id 3 is used by more than one object");
+    }
+}
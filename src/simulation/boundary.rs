@@ -0,0 +1,124 @@
+use std::f32::consts::PI;
+
+use super::node::Node;
+
+/// Node is on the surface when directions of its bonds leave an empty angle larger than this,
+/// interior nodes of square lattice have gaps of 45 degrees and of hexagonal lattice 60 degrees
+pub const BOUNDARY_ANGLE_GAP: f32 = 0.6 * PI;
+
+/// Nodes with fewer bonds than this are always on the surface
+pub const MIN_INTERIOR_COORDINATION: usize = 3;
+
+/// Largest angle between directions of consecutive bonds of the node to nodes of the same object
+pub fn largest_bond_gap(nodes: &[Node], connections_structure: &[Vec<(usize, f32, f32)>], i: usize) -> f32 {
+    let n = &nodes[i];
+    let mut angles: Vec<f32> = connections_structure[i].iter()
+        .filter(|(j, _dx, _v0)| nodes[*j].object_id == n.object_id)
+        .map(|(j, _dx, _v0)| {
+            let d = nodes[*j].position - n.position;
+            d.y.atan2(d.x)
+        })
+        // positions that are not finite are left out, such scene is restored from backup anyway
+        .filter(|angle| !angle.is_nan())
+        .collect();
+
+    if angles.len() < 2 {
+        return 2.0 * PI;
+    }
+
+    angles.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let wrap_gap = angles[0] + 2.0 * PI - angles[angles.len() - 1];
+    angles.windows(2).map(|w| w[1] - w[0]).fold(wrap_gap, f32::max)
+}
+
+/// Finds surface nodes from the current bond graph. Node is on the surface when it has few bonds
+/// within its object or its bonds leave a wide empty angle, so nodes along cracks are included
+/// and bonds welding different objects do not hide their contact surface.
+pub fn find_boundary_nodes(nodes: &[Node], connections_structure: &[Vec<(usize, f32, f32)>]) -> Vec<bool> {
+    (0..nodes.len()).map(|i| {
+        let coordination = connections_structure[i].iter()
+            .filter(|(j, _dx, _v0)| nodes[*j].object_id == nodes[i].object_id)
+            .count();
+        coordination < MIN_INTERIOR_COORDINATION || largest_bond_gap(nodes, connections_structure, i) > BOUNDARY_ANGLE_GAP
+    }).collect()
+}
+
+/// Sets `is_boundary` of all nodes from the current bond graph, returns number of boundary nodes
+pub fn update_boundary_nodes(nodes: &mut [Node], connections_structure: &[Vec<(usize, f32, f32)>]) -> usize {
+    let boundary = find_boundary_nodes(nodes, connections_structure);
+    nodes.iter_mut().zip(boundary.iter()).for_each(|(n, is_boundary)| n.is_boundary = *is_boundary);
+    boundary.iter().filter(|b| **b).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::*;
+    use crate::scene::builder::{NodeProperties, SceneBuilder};
+    use crate::scene::Scene;
+    use crate::simulation::general::calculate_connections_structure;
+
+    fn boundary(scene: &Scene) -> Vec<bool> {
+        find_boundary_nodes(&scene.nodes, &calculate_connections_structure(&scene.connections, &scene.nodes))
+    }
+
+    fn block() -> Scene {
+        let mut builder = SceneBuilder::new(0.04, 100.0);
+        builder.add_rectangle(5, 5, Vec2::new(0.0, 0.0), NodeProperties::new(0.03, 1.0, 1.0, 10.0));
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn outline_of_block_is_boundary() {
+        let scene = block();
+        let boundary = boundary(&scene);
+
+        assert_eq!(boundary.iter().filter(|b| **b).count(), 16);
+        scene.nodes.iter().zip(boundary.iter()).for_each(|(n, is_boundary)| {
+            let on_edge = n.position.x < 0.01 || n.position.x > 0.11 || n.position.y < 0.01 || n.position.y > 0.11;
+            assert_eq!(*is_boundary, on_edge, "{}", n);
+        });
+    }
+
+    #[test]
+    fn nodes_along_crack_become_boundary() {
+        let mut scene = block();
+        let below_crack = |p: glam::Vec2| p.y < 0.05;
+        let crossing: Vec<(usize, usize)> = scene.connections.keys()
+            .filter(|(i, j)| below_crack(scene.nodes[*i].position) != below_crack(scene.nodes[*j].position))
+            .copied()
+            .collect();
+        crossing.iter().for_each(|bond| {
+            scene.connections.remove(bond);
+        });
+
+        let boundary = boundary(&scene);
+        scene.nodes.iter().zip(boundary.iter())
+            .filter(|(n, _b)| (n.position.y - 0.03).abs() < 0.01 || (n.position.y - 0.06).abs() < 0.01)
+            .for_each(|(n, is_boundary)| assert!(*is_boundary, "{}", n));
+    }
+
+    #[test]
+    fn welding_bonds_do_not_hide_contact_surface() {
+        let mut builder = SceneBuilder::new(0.04, 100.0);
+        builder.add_rectangle(3, 3, Vec2::new(0.0, 0.0), NodeProperties::new(0.03, 1.0, 1.0, 10.0));
+        builder.add_rectangle(3, 3, Vec2::new(0.09, 0.0), NodeProperties::new(0.03, 1.0, 1.0, 10.0));
+        let mut scene = builder.build().unwrap();
+        // weld every node of the right edge of the first block to its neighbour in the second block
+        (0..scene.nodes.len()).filter(|i| scene.nodes[*i].object_id == 1 && scene.nodes[*i].position.x > 0.05).for_each(|i| {
+            let j = (0..scene.nodes.len()).find(|j| scene.nodes[*j].position.distance(scene.nodes[i].position + glam::Vec2::new(0.03, 0.0)) < 1e-4).unwrap();
+            scene.connections.insert((i, j), (0.03, 10.0));
+        });
+
+        // only centres of both blocks are inside
+        assert_eq!(boundary(&scene).iter().filter(|b| !**b).count(), 2);
+    }
+
+    #[test]
+    fn non_finite_positions_do_not_panic() {
+        let mut scene = block();
+        scene.nodes[12].position.x = f32::NAN;
+        assert_eq!(boundary(&scene).len(), 25);
+    }
+}
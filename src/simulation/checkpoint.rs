@@ -0,0 +1,529 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+use crate::scene::Scene;
+use crate::scene::validation::SceneError;
+
+use super::fracture::FractureState;
+use super::fragments::FragmentStats;
+use super::healing::BondHealEvent;
+use super::manager::{HistoryBackup, SimulationSettings};
+use super::neighbour_list::NeighbourList;
+use super::temperature::TemperatureCache;
+use super::timestep::DtRecord;
+
+/// First bytes of every checkpoint file
+pub const CHECKPOINT_MAGIC: [u8; 8] = *b"EOCHKPT\0";
+
+/// Version of the checkpoint written by `save`.
+///
+/// Checkpoint body is bincode of records independent of in-memory structs, like scene files in `scene::format`.
+/// When the records have to change, the current module is kept for reading old files, the version is increased
+/// and `decode` migrates the old records. Settings are stored as RON text, so added settings only get their default values.
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+/// Complete state of `SimulationManager`, structures derived from the scene are not stored
+/// and are rebuilt when the checkpoint is loaded
+#[derive(Clone, PartialEq, Debug)]
+pub struct Checkpoint {
+    pub scene: Scene,
+    pub scene_backup: Scene,
+    pub history_backup: HistoryBackup,
+    /// Settings including current dt changed by auto dt, adaptive dt and backups
+    pub settings: SimulationSettings,
+    pub total_simulation_time: f32,
+    pub current_backup_dt: f32,
+    pub last_frame_time: f32,
+    pub fracture_state: FractureState,
+    pub healing_events: Vec<BondHealEvent>,
+    pub fragment_history: Vec<FragmentStats>,
+    pub object_origins: BTreeMap<u32, u32>,
+    pub dt_history: Vec<DtRecord>,
+    pub temperature_cache: TemperatureCache,
+    /// Positions the collision list was built at, so the resumed simulation uses the same collisions
+    pub neighbour_list: NeighbourList,
+}
+
+/// On-disk schema of version 1, scenes are stored with records of the scene file format.
+///
+/// Records of a version are never changed once files were written with it.
+mod v1 {
+    use glam::Vec2;
+    use serde::{Serialize, Deserialize};
+
+    use crate::scene::Scene;
+    use crate::scene::format::v3::SceneFile;
+    use crate::simulation::collider::ColliderState;
+    use crate::simulation::fracture::{BondBreakEvent, BreakCause, FractureState};
+    use crate::simulation::fragments::FragmentStats;
+    use crate::simulation::healing::BondHealEvent;
+    use crate::simulation::manager::HistoryBackup;
+    use crate::simulation::neighbour_list::NeighbourList;
+    use crate::simulation::temperature::TemperatureCache;
+    use crate::simulation::timestep::DtRecord;
+    use super::{Checkpoint, CheckpointError};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct ColliderStateRecord {
+        pub position: [f32; 2],
+        pub angle: f32,
+        pub force: [f32; 2],
+        pub torque: f32,
+        pub impulse: [f32; 2],
+    }
+
+    /// Scene file with state that scene files do not keep, boundary flags set by the simulation
+    /// and current poses and reactions of colliders
+    #[derive(Serialize, Deserialize)]
+    pub struct SceneRecord {
+        pub file: SceneFile,
+        pub boundary: Vec<bool>,
+        pub colliders: Vec<ColliderStateRecord>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct HistoryBackupRecord {
+        pub damage: Vec<((u64, u64), f32)>,
+        pub break_events: u64,
+        pub heal_events: u64,
+        pub fragment_records: u64,
+        pub object_origins: Vec<(u32, u32)>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub enum BreakCauseRecord {
+        Strain,
+        Force,
+        Compression,
+        Fatigue,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct BondBreakRecord {
+        pub time: f32,
+        pub bond: (u64, u64),
+        pub cause: BreakCauseRecord,
+        pub strain: f32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct FractureRecord {
+        pub damage: Vec<((u64, u64), f32)>,
+        pub events: Vec<BondBreakRecord>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct BondHealRecord {
+        pub time: f32,
+        pub bond: (u64, u64),
+        pub welded: bool,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct FragmentsRecord {
+        pub time: f32,
+        pub sizes: Vec<u64>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct StepRecord {
+        pub time: f32,
+        pub dt: f32,
+        pub rejected_steps: u32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct TemperatureRecord {
+        pub records: Vec<Vec<f32>>,
+        pub current_record: u64,
+        pub current_dt: f32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct NeighbourListRecord {
+        pub positions: Vec<[f32; 2]>,
+        pub radius: f32,
+        pub skin: f32,
+        pub self_collision_hops: Option<u32>,
+        pub rebuilds: u64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct CheckpointFile {
+        pub scene: SceneRecord,
+        pub scene_backup: SceneRecord,
+        pub history_backup: HistoryBackupRecord,
+        /// `SimulationSettings` as RON text
+        pub settings: String,
+        pub total_simulation_time: f32,
+        pub current_backup_dt: f32,
+        pub last_frame_time: f32,
+        pub fracture_state: FractureRecord,
+        pub healing_events: Vec<BondHealRecord>,
+        pub fragment_history: Vec<FragmentsRecord>,
+        /// Pairs of `(fragment_id, original_id)` sorted by fragment id
+        pub object_origins: Vec<(u32, u32)>,
+        pub dt_history: Vec<StepRecord>,
+        pub temperature_cache: TemperatureRecord,
+        pub neighbour_list: NeighbourListRecord,
+    }
+
+    fn bond_record(bond: (usize, usize)) -> (u64, u64) {
+        (bond.0 as u64, bond.1 as u64)
+    }
+
+    fn bond(record: (u64, u64)) -> (usize, usize) {
+        (record.0 as usize, record.1 as usize)
+    }
+
+    /// Damage sorted by bond, so the same state is always written the same way
+    fn damage_record<'a>(damage: impl Iterator<Item = (&'a (usize, usize), &'a f32)>) -> Vec<((u64, u64), f32)> {
+        let mut record: Vec<((u64, u64), f32)> = damage.map(|(b, d)| (bond_record(*b), *d)).collect();
+        record.sort_by_key(|(b, _d)| *b);
+        record
+    }
+
+    impl From<&Scene> for SceneRecord {
+        fn from(scene: &Scene) -> Self {
+            SceneRecord {
+                file: SceneFile::from(scene),
+                boundary: scene.nodes.iter().map(|n| n.is_boundary).collect(),
+                colliders: scene.environment.colliders.iter().map(|c| ColliderStateRecord {
+                    position: c.state.position.to_array(),
+                    angle: c.state.angle,
+                    force: c.state.force.to_array(),
+                    torque: c.state.torque,
+                    impulse: c.state.impulse.to_array(),
+                }).collect(),
+            }
+        }
+    }
+
+    impl From<&SceneRecord> for Scene {
+        fn from(record: &SceneRecord) -> Self {
+            let mut scene = Scene::from(&record.file);
+            scene.nodes.iter_mut().zip(record.boundary.iter()).for_each(|(n, boundary)| n.is_boundary = *boundary);
+            scene.environment.colliders.iter_mut().zip(record.colliders.iter()).for_each(|(c, state)| {
+                c.state = ColliderState {
+                    position: Vec2::from(state.position),
+                    angle: state.angle,
+                    force: Vec2::from(state.force),
+                    torque: state.torque,
+                    impulse: Vec2::from(state.impulse),
+                };
+            });
+            scene
+        }
+    }
+
+    impl From<&Checkpoint> for CheckpointFile {
+        fn from(c: &Checkpoint) -> Self {
+            CheckpointFile {
+                scene: SceneRecord::from(&c.scene),
+                scene_backup: SceneRecord::from(&c.scene_backup),
+                history_backup: HistoryBackupRecord {
+                    damage: damage_record(c.history_backup.damage.iter()),
+                    break_events: c.history_backup.break_events as u64,
+                    heal_events: c.history_backup.heal_events as u64,
+                    fragment_records: c.history_backup.fragment_records as u64,
+                    object_origins: c.history_backup.object_origins.iter().map(|(id, origin)| (*id, *origin)).collect(),
+                },
+                settings: ron::to_string(&c.settings).unwrap(),
+                total_simulation_time: c.total_simulation_time,
+                current_backup_dt: c.current_backup_dt,
+                last_frame_time: c.last_frame_time,
+                fracture_state: FractureRecord {
+                    damage: damage_record(c.fracture_state.damage.iter()),
+                    events: c.fracture_state.events.iter().map(|e| BondBreakRecord {
+                        time: e.time,
+                        bond: bond_record(e.bond),
+                        cause: match e.cause {
+                            BreakCause::Strain => BreakCauseRecord::Strain,
+                            BreakCause::Force => BreakCauseRecord::Force,
+                            BreakCause::Compression => BreakCauseRecord::Compression,
+                            BreakCause::Fatigue => BreakCauseRecord::Fatigue,
+                        },
+                        strain: e.strain,
+                    }).collect(),
+                },
+                healing_events: c.healing_events.iter().map(|e| BondHealRecord {
+                    time: e.time,
+                    bond: bond_record(e.bond),
+                    welded: e.welded,
+                }).collect(),
+                fragment_history: c.fragment_history.iter().map(|f| FragmentsRecord {
+                    time: f.time,
+                    sizes: f.sizes.iter().map(|size| *size as u64).collect(),
+                }).collect(),
+                object_origins: c.object_origins.iter().map(|(id, origin)| (*id, *origin)).collect(),
+                dt_history: c.dt_history.iter().map(|r| StepRecord {
+                    time: r.time,
+                    dt: r.dt,
+                    rejected_steps: r.rejected_steps,
+                }).collect(),
+                temperature_cache: TemperatureRecord {
+                    records: c.temperature_cache.records.clone(),
+                    current_record: c.temperature_cache.current_record as u64,
+                    current_dt: c.temperature_cache.current_dt,
+                },
+                neighbour_list: NeighbourListRecord {
+                    positions: c.neighbour_list.positions.iter().map(|p| p.to_array()).collect(),
+                    radius: c.neighbour_list.radius,
+                    skin: c.neighbour_list.skin,
+                    self_collision_hops: c.neighbour_list.self_collision_hops,
+                    rebuilds: c.neighbour_list.rebuilds,
+                },
+            }
+        }
+    }
+
+    impl CheckpointFile {
+        pub fn into_checkpoint(self) -> Result<Checkpoint, CheckpointError> {
+            Ok(Checkpoint {
+                scene: Scene::from(&self.scene),
+                scene_backup: Scene::from(&self.scene_backup),
+                history_backup: HistoryBackup {
+                    damage: self.history_backup.damage.iter().map(|(b, d)| (bond(*b), *d)).collect(),
+                    break_events: self.history_backup.break_events as usize,
+                    heal_events: self.history_backup.heal_events as usize,
+                    fragment_records: self.history_backup.fragment_records as usize,
+                    object_origins: self.history_backup.object_origins.into_iter().collect(),
+                },
+                settings: ron::from_str(&self.settings).map_err(CheckpointError::Settings)?,
+                total_simulation_time: self.total_simulation_time,
+                current_backup_dt: self.current_backup_dt,
+                last_frame_time: self.last_frame_time,
+                fracture_state: FractureState {
+                    damage: self.fracture_state.damage.iter().map(|(b, d)| (bond(*b), *d)).collect(),
+                    events: self.fracture_state.events.iter().map(|e| BondBreakEvent {
+                        time: e.time,
+                        bond: bond(e.bond),
+                        cause: match e.cause {
+                            BreakCauseRecord::Strain => BreakCause::Strain,
+                            BreakCauseRecord::Force => BreakCause::Force,
+                            BreakCauseRecord::Compression => BreakCause::Compression,
+                            BreakCauseRecord::Fatigue => BreakCause::Fatigue,
+                        },
+                        strain: e.strain,
+                    }).collect(),
+                },
+                healing_events: self.healing_events.iter().map(|e| BondHealEvent {
+                    time: e.time,
+                    bond: bond(e.bond),
+                    welded: e.welded,
+                }).collect(),
+                fragment_history: self.fragment_history.iter().map(|f| FragmentStats {
+                    time: f.time,
+                    sizes: f.sizes.iter().map(|size| *size as usize).collect(),
+                }).collect(),
+                object_origins: self.object_origins.into_iter().collect(),
+                dt_history: self.dt_history.iter().map(|r| DtRecord {
+                    time: r.time,
+                    dt: r.dt,
+                    rejected_steps: r.rejected_steps,
+                }).collect(),
+                temperature_cache: TemperatureCache {
+                    records: self.temperature_cache.records,
+                    current_record: self.temperature_cache.current_record as usize,
+                    current_dt: self.temperature_cache.current_dt,
+                },
+                neighbour_list: NeighbourList {
+                    positions: self.neighbour_list.positions.iter().map(|p| Vec2::from(*p)).collect(),
+                    radius: self.neighbour_list.radius,
+                    skin: self.neighbour_list.skin,
+                    self_collision_hops: self.neighbour_list.self_collision_hops,
+                    rebuilds: self.neighbour_list.rebuilds,
+                },
+            })
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(std::io::Error),
+    Encode(bincode::Error),
+    Decode(bincode::Error),
+    Settings(ron::Error),
+    NotACheckpoint,
+    UnsupportedVersion(u32),
+    Invalid(Vec<SceneError>),
+}
+
+impl std::fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckpointError::Io(e) => write!(f, "cannot access checkpoint file: {}", e),
+            CheckpointError::Encode(e) => write!(f, "cannot encode checkpoint: {}", e),
+            CheckpointError::Decode(e) => write!(f, "cannot decode checkpoint: {}", e),
+            CheckpointError::Settings(e) => write!(f, "cannot parse checkpoint settings: {}", e),
+            CheckpointError::NotACheckpoint => write!(f, "file is not a checkpoint"),
+            CheckpointError::UnsupportedVersion(version) => write!(
+                f,
+                "checkpoint version {} is not supported, newest supported version is {}",
+                version, CHECKPOINT_VERSION
+            ),
+            CheckpointError::Invalid(errors) => write!(f, "invalid checkpoint scene:\n{}", crate::scene::validation::format_errors(errors)),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+/// Writes header with magic and version followed by the checkpoint
+pub fn write(writer: &mut impl Write, checkpoint: &Checkpoint) -> Result<(), CheckpointError> {
+    writer.write_all(&CHECKPOINT_MAGIC).map_err(CheckpointError::Io)?;
+    writer.write_all(&CHECKPOINT_VERSION.to_le_bytes()).map_err(CheckpointError::Io)?;
+    bincode::serialize_into(writer, &v1::CheckpointFile::from(checkpoint)).map_err(CheckpointError::Encode)
+}
+
+/// Reads checkpoint of any supported version
+pub fn read(reader: &mut impl Read) -> Result<Checkpoint, CheckpointError> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic).map_err(|_| CheckpointError::NotACheckpoint)?;
+    if magic != CHECKPOINT_MAGIC {
+        return Err(CheckpointError::NotACheckpoint);
+    }
+
+    let mut version = [0u8; 4];
+    reader.read_exact(&mut version).map_err(|_| CheckpointError::NotACheckpoint)?;
+    decode(u32::from_le_bytes(version), reader)
+}
+
+/// Decodes checkpoint body written with given version
+fn decode(version: u32, reader: &mut impl Read) -> Result<Checkpoint, CheckpointError> {
+    match version {
+        1 => bincode::deserialize_from(reader).map_err(CheckpointError::Decode).and_then(v1::CheckpointFile::into_checkpoint),
+        _ => Err(CheckpointError::UnsupportedVersion(version)),
+    }
+}
+
+/// Saves checkpoint to file, file is replaced only after the whole checkpoint was written
+pub fn save(path: &str, checkpoint: &Checkpoint) -> Result<(), CheckpointError> {
+    let temporary_path = format!("{}.tmp", path);
+    let file = std::fs::File::create(&temporary_path).map_err(CheckpointError::Io)?;
+    let mut writer = std::io::BufWriter::new(file);
+    write(&mut writer, checkpoint)?;
+    writer.flush().map_err(CheckpointError::Io)?;
+    drop(writer);
+    std::fs::rename(&temporary_path, path).map_err(CheckpointError::Io)
+}
+
+pub fn load(path: &str) -> Result<Checkpoint, CheckpointError> {
+    let file = std::fs::File::open(path).map_err(CheckpointError::Io)?;
+    read(&mut std::io::BufReader::new(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::*;
+    use crate::scene::builder::SceneBuilder;
+    use crate::scene::description;
+    use crate::simulation::collider::{Collider, Motion, Shape};
+    use crate::simulation::environment::{Environment, Wall};
+    use crate::simulation::fracture::FractureRule;
+    use crate::simulation::manager::SimulationManager;
+    use crate::simulation::material::Material;
+
+    /// Checkpoint of `manager()` after `FIXTURE_UPDATES` updates written with version 1, see `write_fixture`
+    const FIXTURE: &[u8] = include_bytes!("../../tests/data/checkpoint_v1.checkpoint");
+    const FIXTURE_UPDATES: usize = 250;
+
+    /// Brittle block thrown at the floor next to a rotating paddle, so the checkpoint has broken and healed bonds,
+    /// fragments, collider reactions and adaptive steps
+    fn manager() -> SimulationManager {
+        let paddle = Collider::new(Shape::Box { half_extents: Vec2::new(0.1, 0.01) }, Vec2::new(0.35, -0.85), 0.0, 100.0, 0.04)
+            .with_motion(Motion::Constant { velocity: Vec2::ZERO, angular_velocity: 5.0 });
+        let environment = Environment::default().with_wall(Wall::floor(-1.0, 100.0, 0.04)).with_collider(paddle);
+        let mut builder = SceneBuilder::new(0.04, 100.0).with_environment(environment);
+        let brittle = builder.add_material(Material {
+            name: "brittle".to_string(),
+            density: 1000.0,
+            bond_stiffness: 40.0,
+            rest_spacing: 0.03,
+            break_strain: 0.02,
+            fracture: FractureRule::default(),
+            damping: 1.0,
+            repulsion_dx: 0.04,
+            repulsion_v0: 100.0,
+        });
+        let block = builder.add_shape_of_material(&description::Shape::Rectangle { size_x: 6, size_y: 6 }, Vec2::new(0.0, -0.9), brittle);
+        builder.set_velocity(block, Vec2::new(0.0, -2.0));
+        let mut scene = builder.build().unwrap();
+        scene.update_boundary_nodes();
+
+        let settings = SimulationSettings {
+            use_adaptive_dt: true,
+            use_healing: true,
+            use_welding: true,
+            ..Default::default()
+        };
+        SimulationManager::new(settings, scene).unwrap()
+    }
+
+    fn encode(checkpoint: &Checkpoint) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write(&mut bytes, checkpoint).unwrap();
+        bytes
+    }
+
+    /// Regenerates the fixture, only needed when a new checkpoint version is added
+    #[test]
+    #[ignore]
+    fn write_fixture() {
+        let mut manager = manager();
+        (0..FIXTURE_UPDATES).for_each(|_| manager.update());
+        std::fs::write("tests/data/checkpoint_v1.checkpoint", encode(&manager.checkpoint())).unwrap();
+    }
+
+    #[test]
+    fn checkpoint_round_trip() {
+        let mut manager = manager();
+        (0..50).for_each(|_| manager.update());
+        let checkpoint = manager.checkpoint();
+
+        assert_eq!(read(&mut encode(&checkpoint).as_slice()).unwrap(), checkpoint);
+    }
+
+    #[test]
+    fn resumed_simulation_is_identical() {
+        let mut manager = manager();
+        (0..FIXTURE_UPDATES).for_each(|_| manager.update());
+        let checkpoint = read(&mut encode(&manager.checkpoint()).as_slice()).unwrap();
+        let mut resumed = SimulationManager::from_checkpoint(checkpoint).unwrap();
+
+        (0..100).for_each(|_| {
+            manager.update();
+            resumed.update();
+        });
+        assert_eq!(resumed.checkpoint(), manager.checkpoint());
+    }
+
+    #[test]
+    fn committed_checkpoint_is_readable() {
+        let checkpoint = read(&mut &FIXTURE[..]).unwrap();
+
+        assert_eq!(checkpoint.scene.nodes.len(), 36);
+        assert_eq!(checkpoint.scene.environment.colliders.len(), 1);
+        assert!(checkpoint.settings.use_healing && checkpoint.settings.use_adaptive_dt);
+        assert!(checkpoint.total_simulation_time > 0.0);
+        assert!(!checkpoint.fracture_state.events.is_empty());
+        assert!(!checkpoint.healing_events.is_empty());
+        assert_eq!(checkpoint.dt_history.len(), FIXTURE_UPDATES);
+        assert_eq!(checkpoint.neighbour_list.positions.len(), 36);
+
+        let manager = SimulationManager::from_checkpoint(checkpoint.clone()).unwrap();
+        assert_eq!(manager.checkpoint(), checkpoint);
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        assert!(matches!(read(&mut &b"EOSCENE\0\x03\0\0\0"[..]), Err(CheckpointError::NotACheckpoint)));
+
+        let mut bytes = encode(&manager().checkpoint());
+        bytes[CHECKPOINT_MAGIC.len()..CHECKPOINT_MAGIC.len() + 4].copy_from_slice(&99u32.to_le_bytes());
+        assert!(matches!(read(&mut bytes.as_slice()), Err(CheckpointError::UnsupportedVersion(99))));
+    }
+}
@@ -0,0 +1,389 @@
+use glam::Vec2;
+use serde::{Serialize, Deserialize};
+
+use super::forces::{bond_stiffness, ForceField, Interaction};
+use super::node::Node;
+
+/// Shortest distance, relative to `dx`, used by repulsion of colliders, so nodes that got inside
+/// of a box or a circle are pushed out with finite force
+const MIN_REPULSION_DISTANCE: f32 = 0.5;
+
+/// Geometry of a collider in its own coordinates, placed in the scene by the collider position and angle.
+/// Segments and polylines repel nodes on both sides, boxes and circles are solid and push nodes out.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum Shape {
+    Segment { a: Vec2, b: Vec2 },
+    /// Open chain of segments through the points
+    Polyline { points: Vec<Vec2> },
+    /// Rectangle centered at the collider position
+    Box { half_extents: Vec2 },
+    /// Disc centered at the collider position
+    Circle { radius: f32 },
+}
+
+/// Point on the collider along the path of a kinematic collider, relative to its start pose
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Keyframe {
+    pub time: f32,
+    pub offset: Vec2,
+    pub angle: f32,
+}
+
+/// Prescribed movement of a collider, nodes do not move colliders
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum Motion {
+    Static,
+    /// Constant velocity and angular velocity, e.g. rotating paddle
+    Constant { velocity: Vec2, angular_velocity: f32 },
+    /// Offset from the start pose by `amplitude * sin(2 * pi * frequency * t)`, e.g. indenter
+    Oscillating { amplitude: Vec2, angular_amplitude: f32, frequency: f32 },
+    /// Linear interpolation between keyframes sorted by time, pose is held before the first and after the last one
+    Path { keyframes: Vec<Keyframe> },
+}
+
+/// Force between a node and the collider surface as a function of their distance
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum ContactLaw {
+    /// Repulsion of the scene force field with the collider `dx` and `v0`, the same as walls
+    Repulsion,
+    /// Linear spring with stiffness of bonds with the collider `dx` and `v0`, acting closer than `dx`
+    Linear,
+    /// Hertzian contact, force grows with the depth below `dx` to the power of 1.5
+    Hertz,
+}
+
+/// Pose at the current time and reaction of nodes to the collider
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub struct ColliderState {
+    pub position: Vec2,
+    pub angle: f32,
+    /// Force acting on the collider from all nodes after the last step
+    pub force: Vec2,
+    /// Torque of `force` about the collider position
+    pub torque: f32,
+    /// `force` integrated over time since the start of the simulation
+    pub impulse: Vec2,
+}
+
+/// Rigid obstacle, static or moving along prescribed motion
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Collider {
+    pub shape: Shape,
+    /// Pose at time 0
+    pub position: Vec2,
+    #[serde(default)]
+    pub angle: f32,
+    #[serde(default = "static_motion")]
+    pub motion: Motion,
+    #[serde(default = "repulsion_law")]
+    pub law: ContactLaw,
+    pub v0: f32,
+    pub dx: f32,
+    #[serde(default)]
+    pub state: ColliderState,
+}
+
+fn static_motion() -> Motion {
+    Motion::Static
+}
+
+fn repulsion_law() -> ContactLaw {
+    ContactLaw::Repulsion
+}
+
+fn rotate(v: Vec2, angle: f32) -> Vec2 {
+    let (sin, cos) = angle.sin_cos();
+    Vec2::new(cos * v.x - sin * v.y, sin * v.x + cos * v.y)
+}
+
+fn closest_point_on_segment(p: Vec2, a: Vec2, b: Vec2) -> Vec2 {
+    let ab = b - a;
+    let t = if ab.length_squared() > 0.0 { ((p - a).dot(ab) / ab.length_squared()).clamp(0.0, 1.0) } else { 0.0 };
+    a + ab * t
+}
+
+/// Distance and unit normal pointing from the closest surface point to `p`, distance is negative inside of solid shapes
+fn shape_distance(shape: &Shape, p: Vec2) -> (f32, Vec2) {
+    let from_point = |closest: Vec2| {
+        let d = p - closest;
+        let length = d.length();
+        (length, if length > 0.0 { d / length } else { Vec2::new(0.0, 1.0) })
+    };
+
+    match shape {
+        Shape::Segment { a, b } => from_point(closest_point_on_segment(p, *a, *b)),
+        Shape::Polyline { points } => {
+            points.windows(2)
+                .map(|s| from_point(closest_point_on_segment(p, s[0], s[1])))
+                .fold((f32::INFINITY, Vec2::new(0.0, 1.0)), |closest, current| if current.0 < closest.0 { current } else { closest })
+        }
+        Shape::Circle { radius } => {
+            let (length, normal) = from_point(Vec2::ZERO);
+            (length - radius, normal)
+        }
+        Shape::Box { half_extents } => {
+            let q = p.abs() - *half_extents;
+            let sign = Vec2::new(p.x.signum(), p.y.signum());
+            if q.x > 0.0 || q.y > 0.0 {
+                let outside = q.max(Vec2::ZERO);
+                (outside.length(), sign * outside / outside.length())
+            } else if q.x > q.y {
+                (q.x, Vec2::new(sign.x, 0.0))
+            } else {
+                (q.y, Vec2::new(0.0, sign.y))
+            }
+        }
+    }
+}
+
+impl Motion {
+    /// Offset and rotation from the start pose at time `t`
+    pub fn displacement(&self, t: f32) -> (Vec2, f32) {
+        match self {
+            Motion::Static => (Vec2::ZERO, 0.0),
+            Motion::Constant { velocity, angular_velocity } => (*velocity * t, angular_velocity * t),
+            Motion::Oscillating { amplitude, angular_amplitude, frequency } => {
+                let phase = (2.0 * std::f32::consts::PI * frequency * t).sin();
+                (*amplitude * phase, angular_amplitude * phase)
+            }
+            Motion::Path { keyframes } => {
+                let next = keyframes.iter().position(|k| k.time > t);
+                match next {
+                    None => keyframes.last().map_or((Vec2::ZERO, 0.0), |k| (k.offset, k.angle)),
+                    Some(0) => (keyframes[0].offset, keyframes[0].angle),
+                    Some(i) => {
+                        let (a, b) = (keyframes[i - 1], keyframes[i]);
+                        let s = (t - a.time) / (b.time - a.time);
+                        (a.offset.lerp(b.offset, s), a.angle + (b.angle - a.angle) * s)
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Collider {
+    pub fn new(shape: Shape, position: Vec2, angle: f32, v0: f32, dx: f32) -> Collider {
+        Collider {
+            shape,
+            position,
+            angle,
+            motion: Motion::Static,
+            law: ContactLaw::Repulsion,
+            v0,
+            dx,
+            state: ColliderState { position, angle, ..Default::default() },
+        }
+    }
+
+    pub fn with_motion(mut self, motion: Motion) -> Collider {
+        self.motion = motion;
+        self
+    }
+
+    pub fn with_law(mut self, law: ContactLaw) -> Collider {
+        self.law = law;
+        self
+    }
+
+    /// Places the collider where its motion is at time `t`
+    pub fn move_to_time(&mut self, t: f32) {
+        let (offset, angle) = self.motion.displacement(t);
+        self.state.position = self.position + offset;
+        self.state.angle = self.angle + angle;
+    }
+
+    /// Distance from the surface in the current pose, normal pointing to `p` and the closest surface point
+    fn contact(&self, p: Vec2) -> (f32, Vec2, Vec2) {
+        let local = rotate(p - self.state.position, -self.state.angle);
+        let (distance, normal) = shape_distance(&self.shape, local);
+        let normal = rotate(normal, self.state.angle);
+        (distance, normal, p - normal * distance)
+    }
+
+    /// Force magnitude pushing node at distance `d` away from the surface
+    fn force_magnitude(&self, force_field: &ForceField, d: f32) -> f32 {
+        let depth = self.dx - d;
+        match self.law {
+            ContactLaw::Repulsion => force_field.repulsion.force(d.max(MIN_REPULSION_DISTANCE * self.dx), self.dx, self.v0),
+            ContactLaw::Linear if depth > 0.0 => bond_stiffness(self.dx, self.v0) * depth,
+            ContactLaw::Hertz if depth > 0.0 => bond_stiffness(self.dx, self.v0) * depth * (depth / self.dx).sqrt(),
+            _ => 0.0,
+        }
+    }
+
+    pub fn force(&self, force_field: &ForceField, p: Vec2) -> Vec2 {
+        let (distance, normal, _closest) = self.contact(p);
+        normal * self.force_magnitude(force_field, distance)
+    }
+
+    pub fn potential(&self, force_field: &ForceField, p: Vec2) -> f32 {
+        let (d, _normal, _closest) = self.contact(p);
+        let depth = self.dx - d;
+        match self.law {
+            ContactLaw::Repulsion => {
+                // continued linearly below the shortest distance to match the constant force there
+                let min_distance = MIN_REPULSION_DISTANCE * self.dx;
+                let l = d.max(min_distance);
+                force_field.repulsion.potential(l, self.dx, self.v0) + (l - d) * force_field.repulsion.force(l, self.dx, self.v0)
+            }
+            ContactLaw::Linear if depth > 0.0 => 0.5 * bond_stiffness(self.dx, self.v0) * depth * depth,
+            ContactLaw::Hertz if depth > 0.0 => 0.4 * bond_stiffness(self.dx, self.v0) * depth * depth * (depth / self.dx).sqrt(),
+            _ => 0.0,
+        }
+    }
+
+    /// Sums reaction of all nodes in the current pose and adds it to the impulse over `dt`
+    pub fn update_reaction(&mut self, force_field: &ForceField, nodes: &[Node], dt: f32) {
+        let (force, torque) = nodes.iter().fold((Vec2::ZERO, 0.0), |(force, torque), n| {
+            let (distance, normal, closest) = self.contact(n.position);
+            let reaction = -normal * self.force_magnitude(force_field, distance);
+            (force + reaction, torque + (closest - self.state.position).perp_dot(reaction))
+        });
+        self.state.force = force;
+        self.state.torque = torque;
+        self.state.impulse += force * dt;
+    }
+
+    /// Segments of the outline in the current pose, circles are approximated by polygons
+    pub fn outline(&self) -> Vec<(Vec2, Vec2)> {
+        const CIRCLE_SEGMENTS: usize = 32;
+        let local: Vec<(Vec2, Vec2)> = match &self.shape {
+            Shape::Segment { a, b } => vec![(*a, *b)],
+            Shape::Polyline { points } => points.windows(2).map(|s| (s[0], s[1])).collect(),
+            Shape::Box { half_extents } => {
+                let h = *half_extents;
+                let corners = [Vec2::new(-h.x, -h.y), Vec2::new(h.x, -h.y), Vec2::new(h.x, h.y), Vec2::new(-h.x, h.y)];
+                (0..4).map(|k| (corners[k], corners[(k + 1) % 4])).collect()
+            }
+            Shape::Circle { radius } => {
+                let point = |k: usize| rotate(Vec2::new(*radius, 0.0), k as f32 * 2.0 * std::f32::consts::PI / CIRCLE_SEGMENTS as f32);
+                (0..CIRCLE_SEGMENTS).map(|k| (point(k), point(k + 1))).collect()
+            }
+        };
+        local.iter().map(|(a, b)| {
+            (self.state.position + rotate(*a, self.state.angle), self.state.position + rotate(*b, self.state.angle))
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: (f32, Vec2), expected: (f32, Vec2)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-5 && actual.1.distance(expected.1) < 1e-5,
+            "distance and normal {:?}, expected {:?}", actual, expected,
+        );
+    }
+
+    #[test]
+    fn distance_to_open_shapes() {
+        let segment = Shape::Segment { a: Vec2::new(-1.0, 0.0), b: Vec2::new(1.0, 0.0) };
+        assert_close(shape_distance(&segment, Vec2::new(0.5, -0.2)), (0.2, Vec2::new(0.0, -1.0)));
+        assert_close(shape_distance(&segment, Vec2::new(1.3, 0.4)), (0.5, Vec2::new(0.6, 0.8)));
+
+        let polyline = Shape::Polyline { points: vec![Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), Vec2::new(1.0, 1.0)] };
+        assert_close(shape_distance(&polyline, Vec2::new(0.9, 0.5)), (0.1, Vec2::new(-1.0, 0.0)));
+    }
+
+    #[test]
+    fn distance_is_negative_inside_of_solid_shapes() {
+        let circle = Shape::Circle { radius: 0.5 };
+        assert_close(shape_distance(&circle, Vec2::new(0.0, 0.8)), (0.3, Vec2::new(0.0, 1.0)));
+        assert_close(shape_distance(&circle, Vec2::new(-0.2, 0.0)), (-0.3, Vec2::new(-1.0, 0.0)));
+
+        let rectangle = Shape::Box { half_extents: Vec2::new(1.0, 0.5) };
+        assert_close(shape_distance(&rectangle, Vec2::new(1.3, 0.9)), (0.5, Vec2::new(0.6, 0.8)));
+        assert_close(shape_distance(&rectangle, Vec2::new(0.0, -0.6)), (0.1, Vec2::new(0.0, -1.0)));
+        assert_close(shape_distance(&rectangle, Vec2::new(0.8, 0.1)), (-0.2, Vec2::new(1.0, 0.0)));
+    }
+
+    #[test]
+    fn contact_uses_current_pose() {
+        let mut collider = Collider::new(Shape::Box { half_extents: Vec2::new(1.0, 0.1) }, Vec2::new(2.0, 0.0), 0.0, 100.0, 0.04)
+            .with_motion(Motion::Constant { velocity: Vec2::new(0.0, 1.0), angular_velocity: std::f32::consts::FRAC_PI_2 });
+        collider.move_to_time(1.0);
+
+        // box is now vertical and centered at (2, 1)
+        let (distance, normal, closest) = collider.contact(Vec2::new(2.3, 1.5));
+        assert!((distance - 0.2).abs() < 1e-5);
+        assert!(normal.distance(Vec2::new(1.0, 0.0)) < 1e-5);
+        assert!(closest.distance(Vec2::new(2.1, 1.5)) < 1e-5);
+    }
+
+    #[test]
+    fn force_follows_contact_law() {
+        let force_field = ForceField::default();
+        let (dx, v0) = (0.04, 100.0);
+        let floor = |law| Collider::new(Shape::Segment { a: Vec2::new(-1.0, 0.0), b: Vec2::new(1.0, 0.0) }, Vec2::ZERO, 0.0, v0, dx)
+            .with_law(law);
+        let at = |y: f32| Vec2::new(0.0, y);
+
+        let repulsion = floor(ContactLaw::Repulsion);
+        assert_eq!(repulsion.force(&force_field, at(0.03)), Vec2::new(0.0, force_field.repulsion.force(0.03, dx, v0)));
+        // capped below the shortest distance, so nodes that got too close are not shot away
+        assert_eq!(repulsion.force(&force_field, at(0.001)), repulsion.force(&force_field, at(MIN_REPULSION_DISTANCE * dx)));
+
+        let linear = floor(ContactLaw::Linear);
+        assert_eq!(linear.force(&force_field, at(0.05)), Vec2::ZERO);
+        assert!((linear.force(&force_field, at(0.01)).y - bond_stiffness(dx, v0) * 0.03).abs() < 1e-2);
+        // segments push nodes away on both sides
+        assert_eq!(linear.force(&force_field, at(-0.01)), -linear.force(&force_field, at(0.01)));
+
+        let hertz = floor(ContactLaw::Hertz);
+        assert_eq!(hertz.force(&force_field, at(0.05)), Vec2::ZERO);
+        let (shallow, deep) = (hertz.force(&force_field, at(0.03)).y, hertz.force(&force_field, at(0.0)).y);
+        assert!((deep / shallow - 4f32.powf(1.5)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn force_is_negative_derivative_of_potential() {
+        let force_field = ForceField::default();
+        for law in [ContactLaw::Repulsion, ContactLaw::Linear, ContactLaw::Hertz] {
+            let collider = Collider::new(Shape::Circle { radius: 0.2 }, Vec2::ZERO, 0.0, 100.0, 0.04).with_law(law);
+            for d in [0.01, 0.025, 0.035] {
+                let p = Vec2::new(0.0, 0.2 + d);
+                let h = Vec2::new(0.0, 1e-4);
+                let derivative = (collider.potential(&force_field, p + h) - collider.potential(&force_field, p - h)) / (2.0 * h.y);
+                let force = collider.force(&force_field, p).y;
+                assert!((force + derivative).abs() <= 1e-2 * force.abs(), "{:?} at {}: force {}, derivative {}", law, d, force, derivative);
+            }
+        }
+    }
+
+    #[test]
+    fn reaction_is_opposite_to_forces_on_nodes() {
+        let force_field = ForceField::default();
+        let mut collider = Collider::new(Shape::Box { half_extents: Vec2::new(0.5, 0.1) }, Vec2::ZERO, 0.0, 100.0, 0.04)
+            .with_law(ContactLaw::Linear);
+        let nodes: Vec<Node> = [Vec2::new(-0.4, 0.12), Vec2::new(0.4, 0.13), Vec2::new(0.0, 1.0)].iter()
+            .map(|p| Node { position: *p, mass: 1.0, ..Default::default() })
+            .collect();
+
+        collider.update_reaction(&force_field, &nodes, 0.5);
+        collider.update_reaction(&force_field, &nodes, 0.5);
+
+        let on_nodes: Vec<Vec2> = nodes.iter().map(|n| collider.force(&force_field, n.position)).collect();
+        let total = on_nodes.iter().fold(Vec2::ZERO, |sum, f| sum + *f);
+        assert!(total.y > 0.0);
+        assert!((collider.state.force + total).length() < 1e-5 * total.length());
+        // the left node is pushed harder, so the collider is turned counterclockwise
+        let torque = Vec2::new(-0.4, 0.1).perp_dot(-on_nodes[0]) + Vec2::new(0.4, 0.1).perp_dot(-on_nodes[1]);
+        assert!(torque > 0.0);
+        assert!((collider.state.torque - torque).abs() < 1e-5 * torque.abs());
+        assert!((collider.state.impulse - collider.state.force).length() < 1e-5 * total.length());
+    }
+
+    #[test]
+    fn path_is_interpolated_and_held_at_ends() {
+        let keyframe = |time, x, angle| Keyframe { time, offset: Vec2::new(x, 0.0), angle };
+        let motion = Motion::Path { keyframes: vec![keyframe(1.0, 0.0, 0.0), keyframe(2.0, 1.0, 0.5), keyframe(4.0, 1.0, 1.5)] };
+
+        assert_eq!(motion.displacement(0.0), (Vec2::ZERO, 0.0));
+        assert_eq!(motion.displacement(1.5), (Vec2::new(0.5, 0.0), 0.25));
+        assert_eq!(motion.displacement(3.0), (Vec2::new(1.0, 0.0), 1.0));
+        assert_eq!(motion.displacement(10.0), (Vec2::new(1.0, 0.0), 1.5));
+    }
+}
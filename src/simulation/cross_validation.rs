@@ -0,0 +1,152 @@
+use crate::scene::Scene;
+use crate::scene::validation::SceneError;
+
+use super::manager::{SimulationManager, SimulationSettings, SimulationEngineEnum};
+
+/// Largest allowed differences between an engine and the reference engine
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Tolerances {
+    /// Largest distance between positions of the same node
+    pub position: f32,
+    /// Largest difference of velocities of the same node
+    pub velocity: f32,
+    /// Difference of total energies relative to the total energy of the reference
+    pub energy: f32,
+}
+
+impl Default for Tolerances {
+    fn default() -> Self {
+        Tolerances {
+            position: 1e-4,
+            velocity: 1e-2,
+            energy: 1e-3,
+        }
+    }
+}
+
+/// Differences of the final state of an engine from the reference engine
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct EngineDivergence {
+    pub engine: SimulationEngineEnum,
+    pub max_position: f32,
+    pub max_velocity: f32,
+    pub relative_energy: f32,
+}
+
+impl EngineDivergence {
+    pub fn is_within(&self, tolerances: &Tolerances) -> bool {
+        self.max_position <= tolerances.position
+            && self.max_velocity <= tolerances.velocity
+            && self.relative_energy <= tolerances.energy
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct CrossValidationReport {
+    pub reference: SimulationEngineEnum,
+    pub steps: u32,
+    pub divergences: Vec<EngineDivergence>,
+}
+
+impl CrossValidationReport {
+    /// Engines that diverged from the reference more than tolerances allow
+    pub fn failures(&self, tolerances: &Tolerances) -> Vec<EngineDivergence> {
+        self.divergences.iter().filter(|d| !d.is_within(tolerances)).copied().collect()
+    }
+
+    pub fn is_within(&self, tolerances: &Tolerances) -> bool {
+        self.failures(tolerances).is_empty()
+    }
+}
+
+impl std::fmt::Display for CrossValidationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} steps compared with {:?}", self.steps, self.reference)?;
+        for d in &self.divergences {
+            writeln!(
+                f,
+                "{:?}: position {:e}, velocity {:e}, energy {:e}",
+                d.engine, d.max_position, d.max_velocity, d.relative_energy
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Engines that can run on this machine, OpenCL only when built with it and a device is present
+pub fn available_engines() -> Vec<SimulationEngineEnum> {
+    #[allow(unused_mut)]
+    let mut engines = vec![
+        SimulationEngineEnum::Cpu,
+        SimulationEngineEnum::CpuMultithread,
+        SimulationEngineEnum::CpuMultithreadSingleKernel,
+    ];
+    #[cfg(feature = "opencl3")]
+    if super::gpu::gpu::SimulationEngine::is_available() {
+        engines.push(SimulationEngineEnum::OpenCl);
+    }
+    engines
+}
+
+/// Runs `steps` updates of the manager with given engine and returns the manager in the final state
+pub fn run_engine(
+    settings: SimulationSettings,
+    scene: Scene,
+    engine: SimulationEngineEnum,
+    steps: u32
+) -> Result<SimulationManager, Vec<SceneError>> {
+    let mut manager = SimulationManager::new(SimulationSettings { engine, ..settings }, scene)?;
+    for _i in 0..steps {
+        manager.update();
+    }
+    Ok(manager)
+}
+
+fn total_energy(manager: &SimulationManager) -> f32 {
+    let (kinetic, gravity, bonds, walls, repulsion) = manager.total_energy();
+    kinetic + gravity + bonds + walls + repulsion
+}
+
+/// Compares final states of two runs of the same scene
+pub fn divergence(engine: SimulationEngineEnum, reference_manager: &SimulationManager, result_manager: &SimulationManager) -> EngineDivergence {
+    let (reference, result) = (&reference_manager.scene, &result_manager.scene);
+    let max_position = reference.nodes.iter().zip(result.nodes.iter())
+        .map(|(a, b)| (a.position - b.position).length())
+        .fold(0.0, f32::max);
+    let max_velocity = reference.nodes.iter().zip(result.nodes.iter())
+        .map(|(a, b)| (a.velocity - b.velocity).length())
+        .fold(0.0, f32::max);
+
+    let reference_energy = total_energy(reference_manager);
+    let relative_energy = (total_energy(result_manager) - reference_energy).abs() / reference_energy.abs().max(f32::EPSILON);
+
+    // non-finite results never pass tolerances
+    let finite_or_infinity = |value: f32| if value.is_nan() { f32::INFINITY } else { value };
+    EngineDivergence {
+        engine,
+        max_position: finite_or_infinity(max_position),
+        max_velocity: finite_or_infinity(max_velocity),
+        relative_energy: finite_or_infinity(relative_energy),
+    }
+}
+
+/// Runs the scene for `steps` updates with every engine of `engines` and compares each final state
+/// with the state of the first engine. Backups are disabled so engines are compared without corrections.
+pub fn cross_validate(
+    settings: SimulationSettings,
+    scene: &Scene,
+    engines: &[SimulationEngineEnum],
+    steps: u32
+) -> Result<CrossValidationReport, Vec<SceneError>> {
+    let settings = SimulationSettings { use_backup: false, ..settings };
+    let reference = engines.first().copied().unwrap_or(SimulationEngineEnum::Cpu);
+    let reference_manager = run_engine(settings, scene.clone(), reference, steps)?;
+
+    let mut divergences = Vec::new();
+    for engine in engines.iter().skip(1) {
+        let result = run_engine(settings, scene.clone(), *engine, steps)?;
+        divergences.push(divergence(*engine, &reference_manager, &result));
+    }
+
+    Ok(CrossValidationReport { reference, steps, divergences })
+}
@@ -1,63 +1,42 @@
 use crate::{simulation::node::Node, scene::Scene};
-use std::collections::HashMap;
-use rayon::prelude::*;
+use super::environment::Environment;
+use super::forces::{ForceField, Interaction};
+use glam::Vec2;
+use std::collections::BTreeMap;
 
-const M_01: f32 = 1.12246204831;
-
-fn object_repulsion_energy(scene: &Scene) -> f32 {
-    let v0 = scene.object_repulsion_v0;
-    let dx = scene.object_repulsion_dx;
-    let sigma = (1.0 / M_01) * dx;
-
-    scene.nodes.par_iter().enumerate().fold(||0.0, |acc_i, (i, node_i)| {
-        let energy_for_node_i = scene.nodes.iter()
-        .enumerate().filter(|(j, _node_j)| i >= *j)
-        .fold(0.0, |acc_j, (_j, node_j)| {
-            if node_i.object_id == node_j.object_id {
-                // no repulsion between the same object nodes
-                // takes care of counting node energy with itself
-                acc_j
-            } else {
-                let dist = (node_j.position - node_i.position).length();
-                let inner = sigma / dist;
-                acc_j + v0 * inner.powf(12.0)
-            }
-        });
-
-        acc_i + energy_for_node_i
-    }).sum()
+/// Repulsion energy of pairs in `collisions_structure`, the same pairs and parameters the forces are computed with.
+/// Every pair is listed for both of its nodes, so the sum is halved.
+fn object_repulsion_energy(nodes: &[Node], collisions_structure: &[Vec<(usize, f32, f32)>], force_field: &ForceField) -> f32 {
+    let repulsion = force_field.repulsion;
+    nodes.iter().zip(collisions_structure).fold(0.0, |acc, (node_i, collisions)| {
+        collisions.iter().fold(acc, |acc_j, (j, dx, v0)| {
+            let dist = (nodes[*j].position - node_i.position).length();
+            acc_j + 0.5 * repulsion.potential(dist, *dx, *v0)
+        })
+    })
 }
 
-fn bond_energy(nodes: &[Node], connections: &HashMap<(usize, usize), (f32, f32)>) -> f32 {
+fn bond_energy(nodes: &[Node], connections: &BTreeMap<(usize, usize), (f32, f32)>, force_field: &ForceField) -> f32 {
     connections.keys().copied().fold(0.0, |acc, (a, b)| {
         let dist = (nodes[b].position - nodes[a].position).length();
 
         let (dx, v0) = *connections.get(&(a, b)).unwrap();
-        let sigma = (1.0 / M_01) * dx;
-        let inner = sigma / dist;
 
-        acc + v0 * (inner.powf(12.0) - inner.powf(6.0))
+        acc + force_field.bond.potential(dist, dx, v0)
     })
 }
 
-fn wall_repulsion_energy(nodes: &[Node]) -> f32 {
-    let v0 = super::general::WALL_REPULSION_V0;
-    let dx = super::general::WALL_REPULSION_DX;
-    let m01 = 1.12246204831;
-    let sigma = (1.0 / M_01) * dx;
-
+pub fn wall_repulsion_energy(nodes: &[Node], environment: &Environment, force_field: &ForceField) -> f32 {
     nodes.iter().fold(0.0, |acc, n| {
-        let dist = (-1.0 - n.position.y).abs();
-        let inner = sigma / dist;
-
-        acc + v0 * inner.powf(12.0)
+        acc + environment.wall_potential(force_field, n.position)
     })
 }
 
-fn gravity_energy(nodes: &[Node]) -> f32 {
-    static GRAVITY_CONST: f32 = 9.81;
+fn gravity_energy(nodes: &[Node], gravity: Vec2) -> f32 {
+    // zero of potential energy is at y = -0.5
+    let reference = Vec2::new(0.0, -0.5);
     nodes.iter().fold(0.0, |acc, n| {
-        acc + GRAVITY_CONST * n.mass * (n.position.y + 0.5)
+        acc - gravity.dot(n.position - reference) * n.mass
     })
 }
 
@@ -67,12 +46,14 @@ fn kinetic_energy(nodes: &[Node]) -> f32 {
     })
 }
 
-pub fn calculate_total_energy(scene: &Scene) -> (f32, f32, f32, f32, f32) {
+/// Kinetic, gravity, bond, wall and object repulsion energies, repulsion between objects is summed over
+/// the collision pairs of the simulation
+pub fn calculate_total_energy(scene: &Scene, collisions_structure: &[Vec<(usize, f32, f32)>]) -> (f32, f32, f32, f32, f32) {
     let total_kinetic: f32 = kinetic_energy(&scene.nodes);
-    let total_gravity: f32 = gravity_energy(&scene.nodes);
-    let total_lennjon: f32 = bond_energy(&scene.nodes, &scene.connections);
-    let total_wallrep: f32 = wall_repulsion_energy(&scene.nodes);
-    let total_objrepu: f32 = object_repulsion_energy(scene);
+    let total_gravity: f32 = gravity_energy(&scene.nodes, scene.environment.gravity);
+    let total_lennjon: f32 = bond_energy(&scene.nodes, &scene.connections, &scene.force_field);
+    let total_wallrep: f32 = wall_repulsion_energy(&scene.nodes, &scene.environment, &scene.force_field);
+    let total_objrepu: f32 = object_repulsion_energy(&scene.nodes, collisions_structure, &scene.force_field);
 
     (
         total_kinetic,
@@ -82,3 +63,40 @@ pub fn calculate_total_energy(scene: &Scene) -> (f32, f32, f32, f32, f32) {
         total_objrepu,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::builder::{NodeProperties, SceneBuilder};
+    use crate::simulation::general::{calculate_collisions_structure_simple, calculate_connections_structure};
+    use crate::simulation::material::MaterialTable;
+
+    /// Two single node objects 0.03 apart
+    fn pair() -> Scene {
+        let mut builder = SceneBuilder::new(0.04, 100.0);
+        builder.add_rectangle(1, 1, Vec2::new(0.0, 0.0), NodeProperties::new(0.03, 1.0, 0.0, 10.0));
+        builder.add_rectangle(1, 1, Vec2::new(0.03, 0.0), NodeProperties::new(0.03, 1.0, 0.0, 10.0));
+        let mut scene = builder.build().unwrap();
+        scene.nodes.iter_mut().for_each(|n| n.is_boundary = true);
+        scene
+    }
+
+    fn repulsion_energy(scene: &Scene) -> f32 {
+        let connections_structure = calculate_connections_structure(&scene.connections, &scene.nodes);
+        let collisions_structure = calculate_collisions_structure_simple(&scene.nodes, &MaterialTable::new(scene), &connections_structure, None);
+        calculate_total_energy(scene, &collisions_structure).4
+    }
+
+    #[test]
+    fn repulsion_is_counted_once_per_pair() {
+        let scene = pair();
+        assert_eq!(repulsion_energy(&scene), scene.force_field.repulsion.potential(0.03, 0.04, 100.0));
+    }
+
+    #[test]
+    fn welded_pair_has_no_repulsion_energy() {
+        let mut scene = pair();
+        scene.connections.insert((0, 1), (0.03, 10.0));
+        assert_eq!(repulsion_energy(&scene), 0.0);
+    }
+}
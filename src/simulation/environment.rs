@@ -0,0 +1,146 @@
+use glam::Vec2;
+use serde::{Serialize, Deserialize};
+
+use super::collider::Collider;
+use super::forces::{ForceField, Interaction};
+use super::general::{GRAVITY_CONSTANT, WALL_REPULSION_DX, WALL_REPULSION_V0};
+
+/// Infinite straight wall repelling nodes on both of its sides.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[repr(C)]
+pub struct Wall {
+    /// Any point lying on the wall
+    pub point: Vec2,
+    /// Unit vector pointing from the wall into the simulation area
+    pub normal: Vec2,
+    pub v0: f32,
+    pub dx: f32,
+}
+
+impl Wall {
+    pub fn new(point: Vec2, normal: Vec2, v0: f32, dx: f32) -> Wall {
+        Wall { point, normal: normal.normalize(), v0, dx }
+    }
+
+    pub fn floor(y: f32, v0: f32, dx: f32) -> Wall {
+        Wall::new(Vec2::new(0.0, y), Vec2::new(0.0, 1.0), v0, dx)
+    }
+
+    pub fn ceiling(y: f32, v0: f32, dx: f32) -> Wall {
+        Wall::new(Vec2::new(0.0, y), Vec2::new(0.0, -1.0), v0, dx)
+    }
+
+    pub fn left(x: f32, v0: f32, dx: f32) -> Wall {
+        Wall::new(Vec2::new(x, 0.0), Vec2::new(1.0, 0.0), v0, dx)
+    }
+
+    pub fn right(x: f32, v0: f32, dx: f32) -> Wall {
+        Wall::new(Vec2::new(x, 0.0), Vec2::new(-1.0, 0.0), v0, dx)
+    }
+
+    /// Signed distance from the wall, positive on the side the normal points to
+    pub fn distance(&self, position: Vec2) -> f32 {
+        (position - self.point).dot(self.normal)
+    }
+
+    pub fn force(&self, force_field: &ForceField, position: Vec2) -> Vec2 {
+        let d = self.distance(position);
+        self.normal * d.signum() * force_field.repulsion.force(d.abs(), self.dx, self.v0)
+    }
+
+    pub fn potential(&self, force_field: &ForceField, position: Vec2) -> f32 {
+        force_field.repulsion.potential(self.distance(position).abs(), self.dx, self.v0)
+    }
+}
+
+/// Physical surroundings of the scene: gravity, static walls and colliders.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct Environment {
+    pub gravity: Vec2,
+    pub walls: Vec<Wall>,
+    pub colliders: Vec<Collider>,
+}
+
+impl Default for Environment {
+    /// Earth gravity with floor at `y = -1.0`
+    fn default() -> Self {
+        Environment {
+            gravity: Vec2::new(0.0, GRAVITY_CONSTANT),
+            walls: vec![Wall::floor(-1.0, WALL_REPULSION_V0, WALL_REPULSION_DX)],
+            colliders: Vec::new(),
+        }
+    }
+}
+
+impl Environment {
+    /// Environment without gravity and walls
+    pub fn empty() -> Environment {
+        Environment {
+            gravity: Vec2::new(0.0, 0.0),
+            walls: Vec::new(),
+            colliders: Vec::new(),
+        }
+    }
+
+    /// Closed rectangular container with given gravity
+    pub fn closed_box(gravity: Vec2, min: Vec2, max: Vec2, v0: f32, dx: f32) -> Environment {
+        Environment {
+            gravity,
+            walls: Vec::new(),
+            colliders: Vec::new(),
+        }.with_box(min, max, v0, dx)
+    }
+
+    pub fn with_gravity(mut self, gravity: Vec2) -> Environment {
+        self.gravity = gravity;
+        self
+    }
+
+    pub fn with_wall(mut self, wall: Wall) -> Environment {
+        self.walls.push(wall);
+        self
+    }
+
+    pub fn with_collider(mut self, collider: Collider) -> Environment {
+        self.colliders.push(collider);
+        self
+    }
+
+    /// Moves kinematic colliders to their poses at time `t`
+    pub fn move_colliders(&mut self, t: f32) {
+        self.colliders.iter_mut().for_each(|c| c.move_to_time(t));
+    }
+
+    /// Adds four walls enclosing the rectangle from `min` to `max`
+    pub fn with_box(mut self, min: Vec2, max: Vec2, v0: f32, dx: f32) -> Environment {
+        self.walls.push(Wall::floor(min.y, v0, dx));
+        self.walls.push(Wall::ceiling(max.y, v0, dx));
+        self.walls.push(Wall::left(min.x, v0, dx));
+        self.walls.push(Wall::right(max.x, v0, dx));
+        self
+    }
+
+    /// Sum of forces from all walls and colliders acting on a node at `position`
+    pub fn wall_force(&self, force_field: &ForceField, position: Vec2) -> Vec2 {
+        let walls = self.walls.iter().fold(Vec2::new(0.0, 0.0), |accum, wall| {
+            accum + wall.force(force_field, position)
+        });
+        walls + self.collider_force(force_field, position)
+    }
+
+    pub fn wall_potential(&self, force_field: &ForceField, position: Vec2) -> f32 {
+        self.walls.iter().fold(0.0, |accum, wall| {
+            accum + wall.potential(force_field, position)
+        }) + self.colliders.iter().fold(0.0, |accum, collider| {
+            accum + collider.potential(force_field, position)
+        })
+    }
+
+    /// Sum of forces from colliders only, at their current poses
+    pub fn collider_force(&self, force_field: &ForceField, position: Vec2) -> Vec2 {
+        self.colliders.iter().fold(Vec2::new(0.0, 0.0), |accum, collider| {
+            accum + collider.force(force_field, position)
+        })
+    }
+}
@@ -0,0 +1,282 @@
+use glam::Vec2;
+use serde::{Serialize, Deserialize};
+
+/// Radial interaction between two points at distance `l`.
+///
+/// `dx` is the characteristic distance of the interaction (rest length for bonds)
+/// and `v0` its strength.
+pub trait Interaction {
+    /// Force magnitude along the line between the points, positive values push points apart.
+    fn force(&self, l: f32, dx: f32, v0: f32) -> f32;
+
+    /// Potential energy of the pair, `force` is its negative derivative.
+    fn potential(&self, l: f32, dx: f32, v0: f32) -> f32;
+}
+
+/// Potential used for bonds between nodes of the same object.
+///
+/// All variants have minimum `-v0 / 4` at `l = dx` and the same stiffness `18 * v0 / dx^2` near it,
+/// so scenes behave similarly for small deformations regardless of the chosen potential.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum BondPotential {
+    // https://en.wikipedia.org/wiki/Lennard-Jones_potential
+    LennardJones,
+    // Hookean spring
+    Harmonic,
+    // https://en.wikipedia.org/wiki/Morse_potential
+    Morse,
+    // https://en.wikipedia.org/wiki/FENE, `max_extension` is maximal change of length relative to `dx`
+    Fene { max_extension: f32 },
+}
+
+/// Stiffness of bonds of every potential near the rest length
+pub fn bond_stiffness(dx: f32, v0: f32) -> f32 {
+    18.0 * v0 / (dx * dx)
+}
+
+impl Interaction for BondPotential {
+    fn force(&self, l: f32, dx: f32, v0: f32) -> f32 {
+        match *self {
+            BondPotential::LennardJones => {
+                3.0 * (v0 / dx) * ((dx / l).powi(13) - (dx / l).powi(7))
+            }
+            BondPotential::Harmonic => {
+                -bond_stiffness(dx, v0) * (l - dx)
+            }
+            BondPotential::Morse => {
+                let a = 6.0 / dx;
+                let e = (-a * (l - dx)).exp();
+                -0.5 * v0 * a * e * (1.0 - e)
+            }
+            BondPotential::Fene { max_extension } => {
+                // stretching past maximal extension is clamped so force stays finite
+                let r_max = max_extension * dx;
+                let r = (l - dx).clamp(-0.99 * r_max, 0.99 * r_max);
+                -bond_stiffness(dx, v0) * r / (1.0 - (r / r_max).powi(2))
+            }
+        }
+    }
+
+    fn potential(&self, l: f32, dx: f32, v0: f32) -> f32 {
+        match *self {
+            BondPotential::LennardJones => {
+                let inner = (dx / l).powi(6);
+                v0 * (0.25 * inner * inner - 0.5 * inner)
+            }
+            BondPotential::Harmonic => {
+                0.5 * bond_stiffness(dx, v0) * (l - dx).powi(2) - 0.25 * v0
+            }
+            BondPotential::Morse => {
+                let a = 6.0 / dx;
+                let e = (-a * (l - dx)).exp();
+                0.25 * v0 * ((1.0 - e).powi(2) - 1.0)
+            }
+            BondPotential::Fene { max_extension } => {
+                let r_max = max_extension * dx;
+                let r = (l - dx).clamp(-0.99 * r_max, 0.99 * r_max);
+                -0.5 * bond_stiffness(dx, v0) * r_max * r_max * (1.0 - (r / r_max).powi(2)).ln() - 0.25 * v0
+            }
+        }
+    }
+}
+
+/// How repulsion is changed near its cutoff
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum CutoffShift {
+    /// Interaction is cut at the cutoff, energy jumps when a pair crosses it
+    Truncated,
+    /// Potential is shifted to zero at the cutoff, force still jumps there
+    ShiftedPotential,
+    /// Force and potential are both shifted to zero at the cutoff
+    ShiftedForce,
+}
+
+impl CutoffShift {
+    pub const ALL: [CutoffShift; 3] = [CutoffShift::Truncated, CutoffShift::ShiftedPotential, CutoffShift::ShiftedForce];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CutoffShift::Truncated => "truncated",
+            CutoffShift::ShiftedPotential => "shifted-potential",
+            CutoffShift::ShiftedForce => "shifted-force",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<CutoffShift> {
+        CutoffShift::ALL.iter().copied().find(|shift| shift.name() == name)
+    }
+}
+
+/// Repulsive part of Lennard-Jones potential, used for collisions between objects and with walls.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(default)]
+pub struct Repulsion {
+    /// Distance relative to `dx` from which points do not repel each other, `None` for unlimited range
+    pub cutoff: Option<f32>,
+    pub shift: CutoffShift,
+}
+
+impl Default for Repulsion {
+    fn default() -> Self {
+        Repulsion::UNLIMITED
+    }
+}
+
+fn unlimited_repulsion_force(l: f32, dx: f32, v0: f32) -> f32 {
+    3.0 * (v0 / dx) * (dx / l).powi(13)
+}
+
+fn unlimited_repulsion_potential(l: f32, dx: f32, v0: f32) -> f32 {
+    0.25 * v0 * (dx / l).powi(12)
+}
+
+impl Repulsion {
+    pub const UNLIMITED: Repulsion = Repulsion { cutoff: None, shift: CutoffShift::Truncated };
+
+    pub fn with_cutoff(cutoff: f32, shift: CutoffShift) -> Repulsion {
+        Repulsion { cutoff: Some(cutoff), shift }
+    }
+
+    /// Distance from which repulsion with characteristic distance `dx` vanishes
+    pub fn range(&self, dx: f32) -> Option<f32> {
+        self.cutoff.map(|cutoff| cutoff * dx)
+    }
+}
+
+impl Interaction for Repulsion {
+    fn force(&self, l: f32, dx: f32, v0: f32) -> f32 {
+        match self.range(dx) {
+            None => unlimited_repulsion_force(l, dx, v0),
+            Some(range) if l >= range => 0.0,
+            Some(range) if self.shift == CutoffShift::ShiftedForce => {
+                unlimited_repulsion_force(l, dx, v0) - unlimited_repulsion_force(range, dx, v0)
+            }
+            Some(_range) => unlimited_repulsion_force(l, dx, v0),
+        }
+    }
+
+    fn potential(&self, l: f32, dx: f32, v0: f32) -> f32 {
+        match self.range(dx) {
+            None => unlimited_repulsion_potential(l, dx, v0),
+            Some(range) if l >= range => 0.0,
+            Some(range) => match self.shift {
+                CutoffShift::Truncated => unlimited_repulsion_potential(l, dx, v0),
+                CutoffShift::ShiftedPotential => {
+                    unlimited_repulsion_potential(l, dx, v0) - unlimited_repulsion_potential(range, dx, v0)
+                }
+                // potential of the shifted force, so that the force stays its negative derivative
+                CutoffShift::ShiftedForce => {
+                    unlimited_repulsion_potential(l, dx, v0) - unlimited_repulsion_potential(range, dx, v0)
+                        + (l - range) * unlimited_repulsion_force(range, dx, v0)
+                }
+            },
+        }
+    }
+}
+
+/// Set of interactions used by simulation, stored per scene.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(default)]
+pub struct ForceField {
+    pub bond: BondPotential,
+    pub repulsion: Repulsion,
+}
+
+impl Default for ForceField {
+    fn default() -> Self {
+        ForceField {
+            bond: BondPotential::LennardJones,
+            repulsion: Repulsion::UNLIMITED,
+        }
+    }
+}
+
+impl ForceField {
+    /// Force acting on point `a` from bond with point `b`
+    pub fn bond_force(&self, a: Vec2, b: Vec2, dx: f32, v0: f32) -> Vec2 {
+        let dir = b - a;
+        -dir.normalize() * self.bond.force(dir.length(), dx, v0)
+    }
+
+    /// Force acting on point `a` from repulsion with point `b`
+    pub fn repulsion_force(&self, a: Vec2, b: Vec2, dx: f32, v0: f32) -> Vec2 {
+        let dir = b - a;
+        -dir.normalize() * self.repulsion.force(dir.length(), dx, v0)
+    }
+}
+
+/// Quadratic drag, returned value is acceleration
+pub fn drag_acceleration(velocity: Vec2, drag: f32) -> Vec2 {
+    -velocity * velocity.length() * drag
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DX: f32 = 0.04;
+    const V0: f32 = 100.0;
+    const CUTOFF: f32 = 1.5;
+
+    /// Values just inside and just outside of the cutoff distance
+    fn across_cutoff(f: impl Fn(f32) -> f32) -> (f32, f32) {
+        let range = CUTOFF * DX;
+        (f(range * (1.0 - 1e-5)), f(range * (1.0 + 1e-5)))
+    }
+
+    #[test]
+    fn shifted_force_is_continuous_at_cutoff() {
+        let repulsion = Repulsion::with_cutoff(CUTOFF, CutoffShift::ShiftedForce);
+        let peak_force = repulsion.force(DX, DX, V0);
+        let peak_potential = repulsion.potential(DX, DX, V0);
+
+        let (inside, outside) = across_cutoff(|l| repulsion.force(l, DX, V0));
+        assert_eq!(outside, 0.0);
+        assert!(inside.abs() < 1e-4 * peak_force, "force {} inside of cutoff", inside);
+
+        let (inside, outside) = across_cutoff(|l| repulsion.potential(l, DX, V0));
+        assert_eq!(outside, 0.0);
+        assert!(inside.abs() < 1e-4 * peak_potential, "potential {} inside of cutoff", inside);
+    }
+
+    #[test]
+    fn shifted_potential_is_continuous_but_force_jumps_at_cutoff() {
+        let repulsion = Repulsion::with_cutoff(CUTOFF, CutoffShift::ShiftedPotential);
+        let unshifted_force_at_cutoff = Repulsion::UNLIMITED.force(CUTOFF * DX, DX, V0);
+
+        let (inside, outside) = across_cutoff(|l| repulsion.potential(l, DX, V0));
+        assert_eq!(outside, 0.0);
+        assert!(inside.abs() < 1e-4 * repulsion.potential(DX, DX, V0), "potential {} inside of cutoff", inside);
+
+        let (inside, outside) = across_cutoff(|l| repulsion.force(l, DX, V0));
+        assert_eq!(outside, 0.0);
+        assert!((inside - unshifted_force_at_cutoff).abs() < 1e-3 * unshifted_force_at_cutoff);
+    }
+
+    #[test]
+    fn truncated_potential_jumps_at_cutoff() {
+        let repulsion = Repulsion::with_cutoff(CUTOFF, CutoffShift::Truncated);
+        let unshifted_potential_at_cutoff = Repulsion::UNLIMITED.potential(CUTOFF * DX, DX, V0);
+
+        let (inside, outside) = across_cutoff(|l| repulsion.potential(l, DX, V0));
+        assert_eq!(outside, 0.0);
+        assert!((inside - unshifted_potential_at_cutoff).abs() < 1e-3 * unshifted_potential_at_cutoff);
+    }
+
+    #[test]
+    fn force_is_negative_derivative_of_potential_inside_of_cutoff() {
+        let repulsions = CutoffShift::ALL.iter().map(|shift| Repulsion::with_cutoff(CUTOFF, *shift)).chain([Repulsion::UNLIMITED]);
+        for repulsion in repulsions {
+            for k in 0..10 {
+                let l = DX * (0.9 + 0.05 * k as f32);
+                let h = 1e-3 * DX;
+                let derivative = (repulsion.potential(l + h, DX, V0) - repulsion.potential(l - h, DX, V0)) / (2.0 * h);
+                let force = repulsion.force(l, DX, V0);
+                assert!(
+                    (force + derivative).abs() <= 1e-2 * force.abs().max(1.0),
+                    "{:?} at {}: force {}, derivative of potential {}", repulsion, l, force, derivative,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde::{Serialize, Deserialize};
+
+use super::forces::{ForceField, Interaction};
+use super::material::MaterialTable;
+use super::node::Node;
+
+/// Cumulative damage of a bond, stretching above `threshold_strain` damages the bond
+/// with speed `rate * (strain - threshold_strain)` per second, bond breaks when damage reaches 1.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Fatigue {
+    pub threshold_strain: f32,
+    pub rate: f32,
+}
+
+/// Additional conditions under which bonds of a material break,
+/// stretching past break strain of the material always breaks the bond.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct FractureRule {
+    /// Largest tensile force the bond can carry
+    pub max_force: Option<f32>,
+    /// Largest relative shortening of the bond
+    pub max_compression: Option<f32>,
+    pub fatigue: Option<Fatigue>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BreakCause {
+    Strain,
+    Force,
+    Compression,
+    Fatigue,
+}
+
+impl BreakCause {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BreakCause::Strain => "strain",
+            BreakCause::Force => "force",
+            BreakCause::Compression => "compression",
+            BreakCause::Fatigue => "fatigue",
+        }
+    }
+}
+
+/// Record of a single broken bond
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct BondBreakEvent {
+    pub time: f32,
+    pub bond: (usize, usize),
+    pub cause: BreakCause,
+    /// Relative change of bond length at the moment of breaking, negative for compression
+    pub strain: f32,
+}
+
+/// Damage accumulated by bonds and log of broken bonds, kept by `SimulationManager`
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub struct FractureState {
+    pub damage: HashMap<(usize, usize), f32>,
+    pub events: Vec<BondBreakEvent>,
+}
+
+impl FractureState {
+    /// Bonds that broke at any time, including bonds that were healed since
+    pub fn broken_bonds(&self) -> BTreeSet<(usize, usize)> {
+        self.events.iter().map(|e| e.bond).collect()
+    }
+}
+
+/// Checks all bonds against fracture rules of their materials, updates fatigue damage
+/// and returns bonds that should break together with the cause.
+/// `dt` is time elapsed since the previous check.
+pub fn find_broken_bonds(
+    nodes: &[Node],
+    connections: &BTreeMap<(usize, usize), (f32, f32)>,
+    force_field: &ForceField,
+    material_table: &MaterialTable,
+    state: &mut FractureState,
+    dt: f32,
+) -> Vec<((usize, usize), BreakCause, f32)> {
+    let mut broken = Vec::new();
+
+    for (&(i, j), &(dx, v0)) in connections.iter() {
+        let l = (nodes[j].position - nodes[i].position).length();
+        let strain = (l - dx) / dx;
+
+        let material = material_table.material_of(nodes[i].object_id);
+        let rule = material_table.fracture(material);
+
+        let cause = if strain > material_table.break_strain(material) {
+            Some(BreakCause::Strain)
+        } else if rule.max_force.map_or(false, |max_force| -force_field.bond.force(l, dx, v0) > max_force) {
+            Some(BreakCause::Force)
+        } else if rule.max_compression.map_or(false, |max_compression| -strain > max_compression) {
+            Some(BreakCause::Compression)
+        } else if let Some(fatigue) = rule.fatigue {
+            if strain > fatigue.threshold_strain {
+                let damage = state.damage.entry((i, j)).or_insert(0.0);
+                *damage += fatigue.rate * (strain - fatigue.threshold_strain) * dt;
+                if *damage >= 1.0 { Some(BreakCause::Fatigue) } else { None }
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        if let Some(cause) = cause {
+            broken.push(((i, j), cause, strain));
+        }
+    }
+
+    broken
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::*;
+    use crate::scene::builder::{NodeProperties, SceneBuilder};
+    use crate::scene::Scene;
+    use crate::simulation::material::Material;
+
+    const SPACING: f32 = 0.03;
+    const BOND_V0: f32 = 10.0;
+
+    /// Two nodes of material with the rule joined by single bond stretched by `strain`
+    fn bond(rule: FractureRule, strain: f32) -> (Scene, MaterialTable) {
+        let mut builder = SceneBuilder::new(0.04, 100.0);
+        let material = builder.add_material(Material {
+            name: "test".to_string(),
+            density: 1.0,
+            bond_stiffness: BOND_V0,
+            rest_spacing: SPACING,
+            break_strain: 0.5,
+            fracture: rule,
+            damping: 0.0,
+            repulsion_dx: 0.04,
+            repulsion_v0: 100.0,
+        });
+        let object = builder.add_rectangle(2, 1, Vec2::new(0.0, 0.0), NodeProperties::new(SPACING, 1.0, 0.0, BOND_V0));
+        builder.assign_material(object, material);
+        let mut scene = builder.build().unwrap();
+        scene.nodes[1].position.x = SPACING * (1.0 + strain);
+        let table = MaterialTable::new(&scene);
+        (scene, table)
+    }
+
+    fn broken(rule: FractureRule, strain: f32, state: &mut FractureState) -> Vec<((usize, usize), BreakCause, f32)> {
+        let (scene, table) = bond(rule, strain);
+        find_broken_bonds(&scene.nodes, &scene.connections, &scene.force_field, &table, state, 1.0)
+    }
+
+    fn cause(rule: FractureRule, strain: f32) -> Option<BreakCause> {
+        let found = broken(rule, strain, &mut FractureState::default());
+        assert!(found.len() <= 1);
+        found.first().map(|(_bond, cause, _strain)| *cause)
+    }
+
+    #[test]
+    fn break_strain_always_applies() {
+        assert_eq!(cause(FractureRule::default(), 0.4), None);
+        assert_eq!(cause(FractureRule::default(), 0.6), Some(BreakCause::Strain));
+    }
+
+    #[test]
+    fn max_force_breaks_stretched_bond() {
+        let strain = 0.1;
+        let (scene, _table) = bond(FractureRule::default(), strain);
+        let l = SPACING * (1.0 + strain);
+        let force = -scene.force_field.bond.force(l, SPACING, BOND_V0);
+        assert!(force > 0.0);
+
+        assert_eq!(cause(FractureRule { max_force: Some(1.1 * force), ..Default::default() }, strain), None);
+        assert_eq!(cause(FractureRule { max_force: Some(0.9 * force), ..Default::default() }, strain), Some(BreakCause::Force));
+    }
+
+    #[test]
+    fn max_compression_breaks_shortened_bond() {
+        let rule = FractureRule { max_compression: Some(0.1), ..Default::default() };
+        assert_eq!(cause(rule, -0.05), None);
+
+        let found = broken(rule, -0.2, &mut FractureState::default());
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].1, BreakCause::Compression);
+        assert!((found[0].2 + 0.2).abs() < 1e-4);
+    }
+
+    #[test]
+    fn fatigue_accumulates_until_bond_breaks() {
+        let rule = FractureRule { fatigue: Some(Fatigue { threshold_strain: 0.05, rate: 10.0 }), ..Default::default() };
+        let mut state = FractureState::default();
+
+        assert!(broken(rule, 0.01, &mut state).is_empty());
+        assert!(state.damage.is_empty());
+
+        assert!(broken(rule, 0.1, &mut state).is_empty());
+        assert!((state.damage[&(0, 1)] - 0.5).abs() < 1e-3);
+
+        let found = broken(rule, 0.1, &mut state);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].1, BreakCause::Fatigue);
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde::{Serialize, Deserialize};
+
+use crate::scene::Scene;
+
+/// Number and sizes of fragments at given time
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct FragmentStats {
+    pub time: f32,
+    /// Node counts of fragments sorted from the largest
+    pub sizes: Vec<usize>,
+}
+
+impl FragmentStats {
+    pub fn count(&self) -> usize {
+        self.sizes.len()
+    }
+}
+
+fn find_root(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    // path compression
+    let mut current = i;
+    while parents[current] != root {
+        let next = parents[current];
+        parents[current] = root;
+        current = next;
+    }
+    root
+}
+
+/// Index of connected component of the bond graph for each node, components are numbered
+/// from 0 in order of their lowest node index
+pub fn connected_components(node_count: usize, connections: &BTreeMap<(usize, usize), (f32, f32)>) -> Vec<usize> {
+    let mut parents: Vec<usize> = (0..node_count).collect();
+
+    connections.keys().for_each(|&(i, j)| {
+        let root_i = find_root(&mut parents, i);
+        let root_j = find_root(&mut parents, j);
+        if root_i != root_j {
+            parents[root_i.max(root_j)] = root_i.min(root_j);
+        }
+    });
+
+    let mut component_of_root: HashMap<usize, usize> = HashMap::new();
+    (0..node_count).map(|i| {
+        let root = find_root(&mut parents, i);
+        let next = component_of_root.len();
+        *component_of_root.entry(root).or_insert(next)
+    }).collect()
+}
+
+/// Node counts of connected components sorted from the largest
+pub fn fragment_sizes(node_count: usize, connections: &BTreeMap<(usize, usize), (f32, f32)>) -> Vec<usize> {
+    let components = connected_components(node_count, connections);
+    let mut sizes = vec![0; components.iter().max().map_or(0, |max| max + 1)];
+    components.iter().for_each(|c| sizes[*c] += 1);
+    sizes.sort_unstable_by(|a, b| b.cmp(a));
+    sizes
+}
+
+/// Gives fresh object ids to parts of objects that are no longer connected by bonds.
+///
+/// Nodes are grouped by object id and connected component, for each object the largest group keeps
+/// its id and other groups get new ids which inherit material of the object, so objects welded
+/// together keep their own ids. Returns pairs of `(old_id, new_id)` for every created object.
+pub fn relabel_fragments(scene: &mut Scene) -> Vec<(u32, u32)> {
+    let components = connected_components(scene.nodes.len(), &scene.connections);
+
+    // node indexes of every (object, component) group, ordered for deterministic numbering
+    let mut groups: HashMap<(u32, usize), Vec<usize>> = HashMap::new();
+    scene.nodes.iter().enumerate().for_each(|(i, n)| {
+        groups.entry((n.object_id, components[i])).or_default().push(i);
+    });
+
+    let mut groups_of_object: HashMap<u32, Vec<Vec<usize>>> = HashMap::new();
+    let mut keys: Vec<(u32, usize)> = groups.keys().copied().collect();
+    keys.sort();
+    keys.iter().for_each(|key| {
+        groups_of_object.entry(key.0).or_default().push(groups.remove(key).unwrap());
+    });
+
+    let mut next_id = scene.nodes.iter().map(|n| n.object_id).max().unwrap_or(0) + 1;
+    let mut created = Vec::new();
+
+    let mut objects: Vec<u32> = groups_of_object.keys().copied().collect();
+    objects.sort();
+    for object_id in objects {
+        let mut object_groups = groups_of_object.remove(&object_id).unwrap();
+        if object_groups.len() < 2 {
+            continue;
+        }
+
+        // stable sort keeps lower node indexes first among groups of equal size
+        object_groups.sort_by_key(|group| std::cmp::Reverse(group.len()));
+        let material = scene.materials.object_materials.get(&object_id).copied();
+
+        object_groups.iter().skip(1).for_each(|group| {
+            group.iter().for_each(|i| scene.nodes[*i].object_id = next_id);
+            if let Some(material) = material {
+                scene.materials.object_materials.insert(next_id, material);
+            }
+            created.push((object_id, next_id));
+            next_id += 1;
+        });
+    }
+
+    created
+}
+
+/// Id of the object of the original scene the object was split from, `origins` lists the original
+/// object of every fragment created by `relabel_fragments`
+pub fn original_object(origins: &BTreeMap<u32, u32>, object_id: u32) -> u32 {
+    origins.get(&object_id).copied().unwrap_or(object_id)
+}
+
+/// Joins fragments of the same original object that are connected by bonds again after healing.
+///
+/// Every fragment of a connected component gets the smallest id among fragments of its original object
+/// in that component, so fragments welded to other objects keep their own ids. Materials of ids that
+/// are no longer used are removed. Returns pairs of `(merged_id, kept_id)`.
+pub fn merge_healed_fragments(scene: &mut Scene, origins: &BTreeMap<u32, u32>) -> Vec<(u32, u32)> {
+    let components = connected_components(scene.nodes.len(), &scene.connections);
+
+    let mut kept_ids: HashMap<(usize, u32), u32> = HashMap::new();
+    scene.nodes.iter().enumerate().for_each(|(i, n)| {
+        let kept = kept_ids.entry((components[i], original_object(origins, n.object_id))).or_insert(n.object_id);
+        *kept = (*kept).min(n.object_id);
+    });
+
+    let mut merged = BTreeSet::new();
+    scene.nodes.iter_mut().enumerate().for_each(|(i, n)| {
+        let kept = kept_ids[&(components[i], original_object(origins, n.object_id))];
+        if n.object_id != kept {
+            merged.insert((n.object_id, kept));
+            n.object_id = kept;
+        }
+    });
+
+    merged.iter().for_each(|(merged_id, _kept_id)| {
+        scene.materials.object_materials.remove(merged_id);
+    });
+    merged.into_iter().collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::*;
+    use crate::scene::builder::{NodeProperties, SceneBuilder};
+    use crate::simulation::material::Material;
+
+    /// Row of `length` nodes and separate row of two nodes, the first row has material
+    fn rows(length: usize) -> Scene {
+        let mut builder = SceneBuilder::new(0.04, 100.0);
+        let material = builder.add_material(Material {
+            name: "test".to_string(),
+            density: 1.0,
+            bond_stiffness: 10.0,
+            rest_spacing: 0.03,
+            break_strain: 0.5,
+            fracture: Default::default(),
+            damping: 0.0,
+            repulsion_dx: 0.04,
+            repulsion_v0: 100.0,
+        });
+        let row = builder.add_rectangle(length, 1, Vec2::new(0.0, 0.0), NodeProperties::new(0.03, 1.0, 0.0, 10.0));
+        builder.assign_material(row, material);
+        builder.add_rectangle(2, 1, Vec2::new(0.0, 1.0), NodeProperties::new(0.03, 1.0, 0.0, 10.0));
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn components_and_sizes_follow_bonds() {
+        let mut scene = rows(5);
+        assert_eq!(connected_components(7, &scene.connections), vec![0, 0, 0, 0, 0, 1, 1]);
+        assert_eq!(fragment_sizes(7, &scene.connections), vec![5, 2]);
+
+        scene.connections.remove(&(1, 2));
+        assert_eq!(connected_components(7, &scene.connections), vec![0, 0, 1, 1, 1, 2, 2]);
+        assert_eq!(fragment_sizes(7, &scene.connections), vec![3, 2, 2]);
+    }
+
+    #[test]
+    fn smaller_fragments_get_new_ids_with_material() {
+        let mut scene = rows(5);
+        assert!(relabel_fragments(&mut scene).is_empty());
+
+        scene.connections.remove(&(1, 2));
+        let created = relabel_fragments(&mut scene);
+
+        assert_eq!(created, vec![(1, 3)]);
+        let ids: Vec<u32> = scene.nodes.iter().map(|n| n.object_id).collect();
+        assert_eq!(ids, vec![3, 3, 1, 1, 1, 2, 2]);
+        assert_eq!(scene.materials.object_materials.get(&3), scene.materials.object_materials.get(&1));
+        assert_eq!(scene.materials.object_materials.get(&2), None);
+    }
+
+    #[test]
+    fn welded_objects_keep_their_ids() {
+        let mut scene = rows(2);
+        scene.connections.insert((1, 2), (1.0, 10.0));
+
+        assert_eq!(fragment_sizes(4, &scene.connections), vec![4]);
+        assert!(relabel_fragments(&mut scene).is_empty());
+    }
+
+    #[test]
+    fn healed_fragments_of_one_object_are_merged() {
+        let mut scene = rows(5);
+        scene.connections.remove(&(1, 2));
+        scene.connections.remove(&(3, 4));
+        let origins: BTreeMap<u32, u32> = relabel_fragments(&mut scene).into_iter().map(|(old, new)| (new, old)).collect();
+        assert_eq!(origins, BTreeMap::from([(3, 1), (4, 1)]));
+
+        scene.connections.insert((3, 4), (0.03, 10.0));
+        let merged = merge_healed_fragments(&mut scene, &origins);
+
+        assert_eq!(merged, vec![(4, 3)]);
+        let ids: Vec<u32> = scene.nodes.iter().map(|n| n.object_id).collect();
+        assert_eq!(ids, vec![1, 1, 3, 3, 3, 2, 2]);
+        assert_eq!(scene.materials.object_materials.get(&4), None);
+        assert!(scene.materials.object_materials.contains_key(&3));
+    }
+
+    #[test]
+    fn fragments_welded_to_other_objects_are_not_merged() {
+        let mut scene = rows(2);
+        scene.connections.insert((1, 2), (1.0, 10.0));
+
+        assert!(merge_healed_fragments(&mut scene, &BTreeMap::new()).is_empty());
+        let ids: Vec<u32> = scene.nodes.iter().map(|n| n.object_id).collect();
+        assert_eq!(ids, vec![1, 1, 2, 2]);
+    }
+}
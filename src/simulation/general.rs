@@ -1,48 +1,35 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use super::node::Node;
-use glam::Vec2;
+use super::material::MaterialTable;
+use super::forces::ForceField;
+use super::fracture::{FractureState, BondBreakEvent, find_broken_bonds};
+use super::grid::Grid;
 use rayon::prelude::*;
 
 pub const WALL_REPULSION_V0: f32 = 100.0;
 pub const WALL_REPULSION_DX: f32 = 0.04;
 pub const GRAVITY_CONSTANT: f32 = -9.81;
 
-fn nodes_too_far(nodes: &mut Vec<Node>, connections: &mut HashMap<(usize, usize), (f32, f32)>) -> Vec<(usize, usize)> {
-
-    let mut to_remove: Vec<(usize, usize)> = Vec::new();
-
-    for (k, v) in connections.iter() {
-
-        let (i, j) = *k;
-        let (dx, _v0) = *v;
-
-        let dir = nodes[j].position - nodes[i].position;
-        let l = dir.length();
-        if l > dx * 1.5 {
-            to_remove.push(*k);
-        }
-    }
-
-    to_remove
-}
-
+/// Removes bonds that break according to fracture rules of their materials and records them in `fracture_state`.
+/// Returns true if any bond was broken, boundary nodes should be recomputed then.
 pub fn handle_connection_break(
-    nodes: &mut Vec<Node>,
-    connections: &mut HashMap<(usize, usize), (f32, f32)>,
+    nodes: &[Node],
+    connections: &mut BTreeMap<(usize, usize), (f32, f32)>,
+    force_field: &ForceField,
+    material_table: &MaterialTable,
+    fracture_state: &mut FractureState,
+    time: f32,
+    dt: f32,
 ) -> bool {
 
-    let connections_to_break = nodes_too_far(nodes, connections);
+    let connections_to_break = find_broken_bonds(nodes, connections, force_field, material_table, fracture_state, dt);
     let recalculate_objects_interactions = connections_to_break.len() > 0;
 
-    for k in connections_to_break {
-        connections.iter().filter(|(&i, _j)| {
-            i.0 == k.0 || i.0 == k.1 || i.1 == k.0 || i.1 == k.1
-        }).for_each(|(i, _j)| {
-            nodes[i.0].is_boundary = true;
-            nodes[i.1].is_boundary = true;
-        });
+    for (k, cause, strain) in connections_to_break {
         connections.remove(&k);
+        fracture_state.damage.remove(&k);
+        fracture_state.events.push(BondBreakEvent { time, bond: k, cause, strain });
     }
 
     recalculate_objects_interactions
@@ -67,7 +54,7 @@ pub fn calculate_objects_interactions_structure(nodes: &mut Vec<Node>) -> HashMa
     objects_interactions_structure
 }
 
-pub fn calculate_connections_structure(connections_map: &HashMap<(usize, usize), (f32, f32)>, nodes: &Vec<Node>) -> Vec<Vec<(usize, f32, f32)>> {
+pub fn calculate_connections_structure(connections_map: &BTreeMap<(usize, usize), (f32, f32)>, nodes: &Vec<Node>) -> Vec<Vec<(usize, f32, f32)>> {
     let mut connections_structure: Vec<Vec<(usize, f32, f32)>> = vec![Vec::new(); nodes.len()];
     connections_map.iter().for_each(|(k, v)| {
         connections_structure[k.0].push((k.1, v.0, v.1));
@@ -76,127 +63,64 @@ pub fn calculate_connections_structure(connections_map: &HashMap<(usize, usize),
     connections_structure
 }
 
-pub struct Grid {
-    pub top_left: Vec2,
-    pub bottom_right: Vec2,
-    pub cells: Vec<Vec<Vec<usize>>>,
-    pub cell_size: f32,
-    pub cell_count_x: usize,
-    pub cell_count_y: usize,
+pub fn are_connected(connections_structure: &[Vec<(usize, f32, f32)>], i: usize, j: usize) -> bool {
+    connections_structure[i].iter().any(|(k, _dx, _v0)| *k == j)
 }
 
-impl Grid {
-    // return index of the cell that contains the point
-    pub fn get_cell_index(&self, point: &Vec2) -> (usize, usize) {
-        let mut x = ((point.x - self.top_left.x) / self.cell_size) as i32;
-        let mut y = ((self.top_left.y - point.y) / self.cell_size) as i32;
-        if x >= self.cell_count_x as i32 {
-            x = self.cell_count_x as i32 - 1;
-        }
-        else if x < 0 {
-            x = 0;
-        }
-        if y >= self.cell_count_y as i32 {
-            y = self.cell_count_y as i32 - 1;
-        }
-        else if y < 0 {
-            y = 0;
-        }
-        (x as usize, y as usize)
+/// Sorted indices of nodes reachable from `start` through at most `hops` bonds, including `start`
+pub fn nodes_within_hops(connections_structure: &[Vec<(usize, f32, f32)>], start: usize, hops: u32) -> Vec<usize> {
+    let mut reached = HashSet::from([start]);
+    let mut frontier = vec![start];
+    for _hop in 0..hops {
+        let mut next = Vec::new();
+        frontier.iter().for_each(|i| {
+            connections_structure[*i].iter().for_each(|(j, _dx, _v0)| {
+                if reached.insert(*j) {
+                    next.push(*j);
+                }
+            });
+        });
+        frontier = next;
     }
+    let mut reached: Vec<usize> = reached.into_iter().collect();
+    reached.sort_unstable();
+    reached
+}
 
-    // return values of cells that contain the point and the cells that are adjacent to it including the cell itself and diagonals
-    pub fn get_node_indexes_from_neighbours(&self, point: &Vec2) -> Vec<usize> {
-        let (x, y) = self.get_cell_index(point);
-        let mut neighbours: Vec<usize> = Vec::new();
-        neighbours.append(&mut self.cells[x][y].clone());
-        if y > 0 {
-            neighbours.append(&mut self.cells[x][y - 1].clone());
-        }
-        if y < self.cell_count_y - 1 {
-            neighbours.append(&mut self.cells[x][y + 1].clone());
-        }
-        if x > 0 {
-            neighbours.append(&mut self.cells[x - 1][y].clone());
-        }
-        if x < self.cell_count_x - 1 {
-            neighbours.append(&mut self.cells[x + 1][y].clone());
-        }
-        if x > 0 && y > 0 {
-            neighbours.append(&mut self.cells[x - 1][y - 1].clone());
-        }
-        if x > 0 && y < self.cell_count_y - 1 {
-            neighbours.append(&mut self.cells[x - 1][y + 1].clone());
-        }
-        if x < self.cell_count_x - 1 && y > 0 {
-            neighbours.append(&mut self.cells[x + 1][y - 1].clone());
-        }
-        if x < self.cell_count_x - 1 && y < self.cell_count_y - 1 {
-            neighbours.append(&mut self.cells[x + 1][y + 1].clone());
-        }
-        neighbours
+/// Whether boundary nodes `i` and `j` repel each other. Nodes of different objects repel unless they are bonded,
+/// with `self_collision_hops` also boundary nodes of the same object farther than that many bonds apart,
+/// `near_i` are nodes within `self_collision_hops` from `i`.
+fn can_collide(nodes: &[Node], i: usize, j: usize, connections_structure: &[Vec<(usize, f32, f32)>], near_i: Option<&Vec<usize>>) -> bool {
+    if nodes[i].object_id != nodes[j].object_id {
+        return !are_connected(connections_structure, i, j);
     }
-
-    pub fn new(nodes: &Vec<Node>, cell_size: f32) -> Grid {
-        // get the largest and lowest position x and y from nodes
-        let mut top_left = Vec2::new(std::f32::MAX, std::f32::MIN);
-        let mut bottom_right = Vec2::new(std::f32::MIN, std::f32::MAX);
-        nodes.iter().for_each(|n| {
-            if n.position.x < top_left.x {
-                top_left.x = n.position.x;
-            }
-            if n.position.y > top_left.y {
-                top_left.y = n.position.y;
-            }
-            if n.position.x > bottom_right.x {
-                bottom_right.x = n.position.x;
-            }
-            if n.position.y < bottom_right.y {
-                bottom_right.y = n.position.y;
-            }
-        });
-        
-        const MAX_SIZE: f32 = 2.0;
-        if top_left.x < -MAX_SIZE { top_left.x = -MAX_SIZE };
-        if top_left.y > MAX_SIZE { top_left.y = MAX_SIZE };
-        if bottom_right.x > MAX_SIZE { bottom_right.x = MAX_SIZE };
-        if bottom_right.y < -MAX_SIZE { bottom_right.y = -MAX_SIZE };
-        // let top_left = Vec2::new(-1.0, 1.0);
-        // let bottom_right = Vec2::new(1.0, -1.0);
-    
-        let width = bottom_right.x - top_left.x;
-        let height = top_left.y - bottom_right.y;
-    
-        let cell_count_x = (width / cell_size).ceil() as usize;
-        let cell_count_y = (height / cell_size).ceil() as usize;
-    
-        let cells: Vec<Vec<Vec<usize>>> = vec![vec![Vec::new(); cell_count_y]; cell_count_x];
-    
-        let mut grid = Grid {
-            top_left,
-            bottom_right,
-            cells,
-            cell_size,
-            cell_count_x,
-            cell_count_y,
-        };
-     
-        nodes.iter().enumerate().for_each(|(index, n)| {
-            if n.is_boundary {
-                let (x, y) = grid.get_cell_index(&n.position);
-                grid.cells[x][y].push(index);
-            }
-        });
-    
-        grid
+    match near_i {
+        Some(near_i) => nodes[j].is_boundary && near_i.binary_search(&j).is_err(),
+        None => false,
     }
 }
 
-
-pub fn calculate_collisions_structure_with_grid(nodes: &Vec<Node>, grid: &Grid) -> Vec<Vec<usize>> {
-    nodes.par_iter().map(|n| {
+/// For each node list of `(j, dx, v0)`, other objects nodes it is repelled by with parameters of the repulsion.
+/// Nodes of different objects welded together by a bond do not repel each other.
+/// With `self_collision_hops` boundary nodes also repel boundary nodes of their own object that are
+/// more than that many bonds away.
+pub fn calculate_collisions_structure_with_grid(
+    nodes: &[Node],
+    grid: &Grid,
+    material_table: &MaterialTable,
+    connections_structure: &[Vec<(usize, f32, f32)>],
+    self_collision_hops: Option<u32>
+) -> Vec<Vec<(usize, f32, f32)>> {
+    let materials = material_table.node_materials(nodes);
+    nodes.par_iter().enumerate().map(|(i, n)| {
         if n.is_boundary {
-            grid.get_node_indexes_from_neighbours(&n.position).iter().copied().filter(|j| nodes[*j].object_id != n.object_id).collect()
+            let near_i = self_collision_hops.map(|hops| nodes_within_hops(connections_structure, i, hops));
+            grid.neighbours(&n.position).filter(|j| {
+                can_collide(nodes, i, *j, connections_structure, near_i.as_ref())
+            }).map(|j| {
+                let (dx, v0) = material_table.repulsion(materials[i], materials[j]);
+                (j, dx, v0)
+            }).collect()
         }
         else {
             vec![]
@@ -204,12 +128,22 @@ pub fn calculate_collisions_structure_with_grid(nodes: &Vec<Node>, grid: &Grid)
     }).collect()
 }
 
-pub fn calculate_collisions_structure_simple(nodes: &Vec<Node>) -> Vec<Vec<usize>> {
-    nodes.par_iter().map(|n| {
+pub fn calculate_collisions_structure_simple(
+    nodes: &Vec<Node>,
+    material_table: &MaterialTable,
+    connections_structure: &[Vec<(usize, f32, f32)>],
+    self_collision_hops: Option<u32>
+) -> Vec<Vec<(usize, f32, f32)>> {
+    let materials = material_table.node_materials(nodes);
+    nodes.par_iter().enumerate().map(|(i, n)| {
         if n.is_boundary {
-            nodes.iter().enumerate().filter(|(_j, n2)| {
-                n2.is_boundary && n.object_id != n2.object_id
-            }).map(|(j, _n2)| j).collect()
+            let near_i = self_collision_hops.map(|hops| nodes_within_hops(connections_structure, i, hops));
+            nodes.iter().enumerate().filter(|(j, n2)| {
+                n2.is_boundary && can_collide(nodes, i, *j, connections_structure, near_i.as_ref())
+            }).map(|(j, _n2)| {
+                let (dx, v0) = material_table.repulsion(materials[i], materials[j]);
+                (j, dx, v0)
+            }).collect()
         }
         else {
             vec![]
@@ -1,7 +1,7 @@
 #[cfg(feature = "opencl3")]
 pub mod gpu {
     use glam::Vec2;
-    use crate::{simulation::{node::Node, cpu::{start_integrate_velocity_verlet, end_integrate_velocity_verlet}}, scene::Scene};
+    use crate::{simulation::{node::Node, forces::{ForceField, BondPotential, CutoffShift}, environment::{Environment, Wall}, integrator::{Integrator, integrate}}, scene::Scene};
 
     pub fn flat_with_indexes<T: Copy>(nested_slice: &[Vec<T>]) -> (Vec<T>, Vec<usize>) {
         let flat: Vec<T> = nested_slice.iter().flatten().copied().collect();
@@ -19,7 +19,7 @@ pub mod gpu {
 
     use opencl3::{command_queue::{CommandQueue, CL_QUEUE_PROFILING_ENABLE, CL_QUEUE_OUT_OF_ORDER_EXEC_MODE_ENABLE}, types::cl_bool};
     use opencl3::context::Context;
-    use opencl3::device::{get_all_devices, Device, CL_DEVICE_TYPE_GPU};
+    use opencl3::device::{get_all_devices, Device, CL_DEVICE_TYPE_ALL, CL_DEVICE_TYPE_GPU};
     use opencl3::kernel::{ExecuteKernel, Kernel};
     use opencl3::memory::{Buffer, CL_MEM_READ_ONLY, CL_MEM_WRITE_ONLY};
     use opencl3::program::Program;
@@ -38,20 +38,30 @@ pub mod gpu {
         node_count: usize,
         node_buffer: Buffer<Node>,
         collision_index_buffer: Buffer<usize>,
-        collision_buffer: Buffer<usize>,
+        collision_buffer: Buffer<(usize, f32, f32)>,
         connection_index_buffer: Buffer<usize>,
         connection_buffer: Buffer<(usize, f32, f32)>,
+        wall_count: usize,
+        wall_buffer: Buffer<Wall>,
         result_buffer: Buffer<Vec2>,
     }
 
+    /// GPU when there is one, otherwise any OpenCL device such as CPU implementation of pocl
+    fn find_device() -> Option<opencl3::types::cl_device_id> {
+        let gpu = get_all_devices(CL_DEVICE_TYPE_GPU).unwrap_or_default().first().copied();
+        gpu.or_else(|| get_all_devices(CL_DEVICE_TYPE_ALL).unwrap_or_default().first().copied())
+    }
+
     impl SimulationEngine {
 
+        /// True when there is an OpenCL device the engine can run on
+        pub fn is_available() -> bool {
+            find_device().is_some()
+        }
+
         pub fn new() -> SimulationEngine {
             // Find a usable device for this application
-            let device_id = *get_all_devices(CL_DEVICE_TYPE_GPU)
-                .unwrap()
-                .first()
-                .expect("No OpenCL GPU found");
+            let device_id = find_device().expect("No OpenCL device found");
         
             let device = Device::new(device_id);
 
@@ -85,7 +95,7 @@ pub mod gpu {
                 ptr::null_mut(),
             ).unwrap();
 
-            let collision_buffer = Buffer::<usize>::create(
+            let collision_buffer = Buffer::<(usize, f32, f32)>::create(
                 &context,
                 CL_MEM_READ_ONLY,
                 BLANK_BUFFER_SIZE,
@@ -106,6 +116,13 @@ pub mod gpu {
                 ptr::null_mut(),
             ).unwrap();
 
+            let wall_buffer = Buffer::<Wall>::create(
+                &context,
+                CL_MEM_READ_ONLY,
+                BLANK_BUFFER_SIZE,
+                ptr::null_mut(),
+            ).unwrap();
+
             let result_buffer = Buffer::<Vec2>::create(
                 &context,
                 CL_MEM_WRITE_ONLY,
@@ -123,6 +140,8 @@ pub mod gpu {
                 collision_buffer,
                 connection_index_buffer,
                 connection_buffer,
+                wall_count: 0,
+                wall_buffer,
                 result_buffer,
             }
         }
@@ -154,11 +173,11 @@ pub mod gpu {
         //     self.command_queue.enqueue_write_buffer(&mut self.collision_index_buffer, WRITE_TYPE, 0, &index, &[]).unwrap();
         // }
 
-        pub fn update_collision_buffer(&mut self, data: &[Vec<usize>]) {
+        pub fn update_collision_buffer(&mut self, data: &[Vec<(usize, f32, f32)>]) {
             let (flat, index) = flat_with_indexes(&data);
 
             if flat.len() > 0 {
-                self.collision_buffer = Buffer::<usize>::create(
+                self.collision_buffer = Buffer::<(usize, f32, f32)>::create(
                     &self.context,
                     CL_MEM_READ_ONLY,
                     flat.len(),
@@ -204,13 +223,47 @@ pub mod gpu {
             self.command_queue.enqueue_write_buffer(&mut self.connection_index_buffer, WRITE_TYPE, 0, &index, &[]).unwrap();
         }
 
-        fn run_kernel(&self, collision_dx: f32, collision_v0: f32) -> Vec<Vec2> {
+        pub fn update_wall_buffer(&mut self, environment: &Environment) {
+            self.wall_count = environment.walls.len();
+
+            if self.wall_count > 0 {
+                self.wall_buffer = Buffer::<Wall>::create(
+                    &self.context,
+                    CL_MEM_READ_ONLY,
+                    self.wall_count,
+                    ptr::null_mut(),
+                ).unwrap();
+                self.command_queue.enqueue_write_buffer(&mut self.wall_buffer, WRITE_TYPE, 0, &environment.walls, &[]).unwrap();
+            }
+        }
+
+        fn run_kernel(&self, force_field: &ForceField, gravity: Vec2) -> Vec<Vec2> {
 
             self.command_queue.finish().unwrap();
 
+            // bond potential is passed as index of the variant and its parameter
+            let (bond_potential, bond_parameter): (u32, f32) = match force_field.bond {
+                BondPotential::LennardJones => (0, 0.0),
+                BondPotential::Harmonic => (1, 0.0),
+                BondPotential::Morse => (2, 0.0),
+                BondPotential::Fene { max_extension } => (3, max_extension),
+            };
+            // cutoff 0 means unlimited range
+            let repulsion_cutoff: f32 = force_field.repulsion.cutoff.unwrap_or(0.0);
+            let repulsion_shift: u32 = match force_field.repulsion.shift {
+                CutoffShift::Truncated => 0,
+                CutoffShift::ShiftedPotential => 1,
+                CutoffShift::ShiftedForce => 2,
+            };
+
             let kernel_event = ExecuteKernel::new(&self.kernel)
-                .set_arg(&collision_dx)
-                .set_arg(&collision_v0)
+                .set_arg(&bond_potential)
+                .set_arg(&bond_parameter)
+                .set_arg(&repulsion_shift)
+                .set_arg(&repulsion_cutoff)
+                .set_arg(&gravity)
+                .set_arg(&self.wall_count)
+                .set_arg(&self.wall_buffer)
                 .set_arg(&self.node_count)
                 .set_arg(&self.node_buffer)
                 .set_arg(&self.collision_index_buffer)
@@ -247,23 +300,22 @@ pub mod gpu {
         pub fn simulate_opencl(
             &mut self,
             dt: f32,
+            integrator: Integrator,
             scene: &mut Scene,
         ) {
-    
-            start_integrate_velocity_verlet(dt, &mut scene.nodes);
-
-            {
+            integrate(integrator, dt, scene, |scene| {
                 self.write_node_buffer(&mut scene.nodes);
 
-                let result = self.run_kernel(scene.object_repulsion_dx, scene.object_repulsion_v0);
+                let result = self.run_kernel(&scene.force_field, scene.environment.gravity);
                 assert_eq!(result.len(), scene.nodes.len());
 
+                // colliders are shapes of different kinds and sizes, their forces are added on the host
+                let force_field = scene.force_field;
+                let environment = &scene.environment;
                 scene.nodes.iter_mut().enumerate().for_each(|(i, n)| {
-                    n.current_acceleration += result[i];
+                    n.current_acceleration += result[i] + environment.collider_force(&force_field, n.position) / n.mass;
                 });
-            }
-    
-            end_integrate_velocity_verlet(dt, &mut scene.nodes);
+            });
         }
     }
 }
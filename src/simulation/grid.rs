@@ -0,0 +1,125 @@
+use glam::Vec2;
+
+use super::node::Node;
+
+/// Offsets of the cell itself and its eight neighbours
+const NEIGHBOUR_CELLS: [(i32, i32); 9] = [(0, 0), (0, -1), (0, 1), (-1, 0), (1, 0), (-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+/// Cell list of boundary nodes over unbounded plane.
+///
+/// Cells are hashed into buckets and node indices are sorted by bucket with counting sort,
+/// so the whole grid lives in a few flat arrays that are reused between updates.
+pub struct Grid {
+    pub cell_size: f32,
+    /// Cell of every node, `None` for nodes that are not boundary nodes
+    node_cells: Vec<Option<(i32, i32)>>,
+    /// Start of every bucket in `entries`, the last element is the number of entries
+    bucket_starts: Vec<u32>,
+    /// Indices of boundary nodes ordered by bucket, in order of nodes within a bucket
+    entries: Vec<u32>,
+    /// Next free position of every bucket while sorting
+    bucket_fill: Vec<u32>,
+}
+
+fn hash_cell(cell: (i32, i32), mask: usize) -> usize {
+    // https://matthias-research.github.io/pages/publications/tetraederCollision.pdf
+    ((cell.0 as u32).wrapping_mul(73856093) ^ (cell.1 as u32).wrapping_mul(19349663)) as usize & mask
+}
+
+fn cell_of(point: &Vec2, cell_size: f32) -> (i32, i32) {
+    ((point.x / cell_size).floor() as i32, (point.y / cell_size).floor() as i32)
+}
+
+impl Grid {
+    pub fn new(nodes: &[Node], cell_size: f32) -> Grid {
+        let mut grid = Grid {
+            cell_size,
+            node_cells: Vec::new(),
+            bucket_starts: vec![0, 0],
+            entries: Vec::new(),
+            bucket_fill: Vec::new(),
+        };
+        grid.update(nodes, cell_size);
+        grid
+    }
+
+    /// Cell containing the point, cell `(x, y)` spans `[x, x + 1) * cell_size` horizontally
+    pub fn cell_of(&self, point: &Vec2) -> (i32, i32) {
+        cell_of(point, self.cell_size)
+    }
+
+    /// Sorts nodes into cells again, buffers are reused and nothing is sorted when no node changed its cell.
+    /// Returns whether any node changed its cell.
+    pub fn update(&mut self, nodes: &[Node], cell_size: f32) -> bool {
+        let mut changed = self.cell_size != cell_size || self.node_cells.len() != nodes.len();
+        self.cell_size = cell_size;
+        self.node_cells.resize(nodes.len(), None);
+
+        for (node, node_cell) in nodes.iter().zip(self.node_cells.iter_mut()) {
+            let cell = if node.is_boundary { Some(cell_of(&node.position, cell_size)) } else { None };
+            if *node_cell != cell {
+                *node_cell = cell;
+                changed = true;
+            }
+        }
+
+        if changed {
+            self.sort();
+        }
+        changed
+    }
+
+    fn sort(&mut self) {
+        let count = self.node_cells.iter().filter(|cell| cell.is_some()).count();
+        let bucket_count = (2 * count).next_power_of_two();
+        let mask = bucket_count - 1;
+
+        self.bucket_starts.clear();
+        self.bucket_starts.resize(bucket_count + 1, 0);
+        self.node_cells.iter().flatten().for_each(|cell| {
+            self.bucket_starts[hash_cell(*cell, mask) + 1] += 1;
+        });
+        for b in 0..bucket_count {
+            self.bucket_starts[b + 1] += self.bucket_starts[b];
+        }
+
+        self.bucket_fill.clear();
+        self.bucket_fill.extend_from_slice(&self.bucket_starts[..bucket_count]);
+        self.entries.resize(count, 0);
+        self.node_cells.iter().enumerate().for_each(|(i, cell)| {
+            if let Some(cell) = cell {
+                let bucket = hash_cell(*cell, mask);
+                self.entries[self.bucket_fill[bucket] as usize] = i as u32;
+                self.bucket_fill[bucket] += 1;
+            }
+        });
+    }
+
+    /// Boundary nodes inside the cell, in order of their indices
+    pub fn nodes_in_cell(&self, cell: (i32, i32)) -> impl Iterator<Item = usize> + '_ {
+        let bucket = hash_cell(cell, self.bucket_starts.len() - 2);
+        let range = self.bucket_starts[bucket] as usize..self.bucket_starts[bucket + 1] as usize;
+        // other cells can share the bucket
+        self.entries[range].iter().map(|i| *i as usize).filter(move |i| self.node_cells[*i] == Some(cell))
+    }
+
+    /// Boundary nodes in the cell containing the point and in the eight cells around it
+    pub fn neighbours(&self, point: &Vec2) -> impl Iterator<Item = usize> + '_ {
+        let (x, y) = self.cell_of(point);
+        NEIGHBOUR_CELLS.iter().flat_map(move |(dx, dy)| {
+            self.nodes_in_cell((x.saturating_add(*dx), y.saturating_add(*dy)))
+        })
+    }
+
+    pub fn get_node_indexes_from_neighbours(&self, point: &Vec2) -> Vec<usize> {
+        self.neighbours(point).collect()
+    }
+
+    /// Cells containing at least one node, sorted
+    pub fn occupied_cells(&self) -> Vec<(i32, i32)> {
+        let mut cells: Vec<(i32, i32)> = self.node_cells.iter().flatten().copied().collect();
+        cells.sort();
+        cells.dedup();
+        cells
+    }
+}
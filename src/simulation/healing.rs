@@ -0,0 +1,177 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Serialize, Deserialize};
+
+use super::fragments::original_object;
+use super::general::are_connected;
+use super::grid::Grid;
+use super::node::Node;
+
+/// Record of a bond created between nodes that came back into contact
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct BondHealEvent {
+    pub time: f32,
+    pub bond: (usize, usize),
+    /// True when the bond joins two different objects of the original scene, not fragments of one object
+    pub welded: bool,
+}
+
+/// Nodes closer than `distance` moving relative to each other slower than `max_relative_speed` can be bonded
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Capture {
+    pub distance: f32,
+    pub max_relative_speed: f32,
+}
+
+/// Average strength of bonds of the node, `None` for nodes without bonds
+fn mean_bond_v0(connections_structure: &[Vec<(usize, f32, f32)>], i: usize) -> Option<f32> {
+    let bonds = &connections_structure[i];
+    if bonds.is_empty() {
+        None
+    } else {
+        Some(bonds.iter().map(|(_j, _dx, v0)| *v0).sum::<f32>() / bonds.len() as f32)
+    }
+}
+
+/// Finds pairs of boundary nodes within `capture` that are not bonded yet.
+///
+/// Bonds listed in `broken_bonds` are always re-formed. Other pairs of the same original object
+/// (`origins` maps fragment ids to the object they were split from) are bonded only when the nodes lie
+/// in different fragments (`fragments` holds connected component of every node), so neighbours inside
+/// an intact object that were never bonded stay unbonded. Pairs of different original objects are bonded
+/// only when `welding` is enabled.
+///
+/// Returned bonds have rest length equal to the current distance, so they are created without tension,
+/// and strength equal to the average strength of existing bonds of both nodes.
+pub fn find_new_bonds(
+    nodes: &[Node],
+    connections_structure: &[Vec<(usize, f32, f32)>],
+    fragments: &[usize],
+    origins: &BTreeMap<u32, u32>,
+    broken_bonds: &BTreeSet<(usize, usize)>,
+    capture: Capture,
+    welding: bool,
+) -> BTreeMap<(usize, usize), (f32, f32)> {
+    let grid = Grid::new(nodes, capture.distance);
+    let mut new_bonds = BTreeMap::new();
+
+    nodes.iter().enumerate().filter(|(_i, n)| n.is_boundary).for_each(|(i, n)| {
+        grid.neighbours(&n.position).filter(|j| *j > i).for_each(|j| {
+            let other = &nodes[j];
+            let allowed = if broken_bonds.contains(&(i, j)) {
+                true
+            } else if original_object(origins, n.object_id) == original_object(origins, other.object_id) {
+                fragments[i] != fragments[j]
+            } else {
+                welding
+            };
+            if !allowed {
+                return;
+            }
+
+            let distance = Node::distance(n, other);
+            let relative_speed = (other.velocity - n.velocity).length();
+            if distance >= capture.distance || relative_speed >= capture.max_relative_speed || are_connected(connections_structure, i, j) {
+                return;
+            }
+
+            let v0 = match (mean_bond_v0(connections_structure, i), mean_bond_v0(connections_structure, j)) {
+                (Some(a), Some(b)) => 0.5 * (a + b),
+                (Some(a), None) | (None, Some(a)) => a,
+                (None, None) => return,
+            };
+
+            new_bonds.insert((i, j), (distance, v0));
+        });
+    });
+
+    new_bonds
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::*;
+    use crate::scene::builder::{NodeProperties, SceneBuilder};
+    use crate::scene::Scene;
+    use crate::simulation::fragments::{connected_components, relabel_fragments};
+    use crate::simulation::general::calculate_connections_structure;
+
+    const SPACING: f32 = 0.01;
+    const CAPTURE_DISTANCE: f32 = 0.025;
+
+    fn new_bonds(scene: &Scene, broken_bonds: &BTreeSet<(usize, usize)>, welding: bool) -> BTreeMap<(usize, usize), (f32, f32)> {
+        new_bonds_of_fragments(scene, &BTreeMap::new(), broken_bonds, welding)
+    }
+
+    fn new_bonds_of_fragments(
+        scene: &Scene,
+        origins: &BTreeMap<u32, u32>,
+        broken_bonds: &BTreeSet<(usize, usize)>,
+        welding: bool,
+    ) -> BTreeMap<(usize, usize), (f32, f32)> {
+        let connections_structure = calculate_connections_structure(&scene.connections, &scene.nodes);
+        let fragments = connected_components(scene.nodes.len(), &scene.connections);
+        let capture = Capture { distance: CAPTURE_DISTANCE, max_relative_speed: 1.0 };
+        find_new_bonds(&scene.nodes, &connections_structure, &fragments, origins, broken_bonds, capture, welding)
+    }
+
+    fn block() -> Scene {
+        let mut builder = SceneBuilder::new(SPACING, 1.0);
+        builder.add_rectangle(10, 10, Vec2::new(0.0, 0.0), NodeProperties::new(SPACING, 1.0, 1.0, 1.0));
+        let mut scene = builder.build().unwrap();
+        scene.update_boundary_nodes();
+        scene
+    }
+
+    #[test]
+    fn intact_object_gains_no_bonds() {
+        let scene = block();
+        assert!(new_bonds(&scene, &BTreeSet::new(), false).is_empty());
+        assert!(new_bonds(&scene, &BTreeSet::new(), true).is_empty());
+    }
+
+    #[test]
+    fn only_recorded_broken_bonds_are_reformed() {
+        let mut scene = block();
+        let (&bond, &(dx, _v0)) = scene.connections.iter().next().unwrap();
+        scene.connections.remove(&bond);
+        scene.update_boundary_nodes();
+
+        assert!(new_bonds(&scene, &BTreeSet::new(), false).is_empty());
+
+        let healed = new_bonds(&scene, &BTreeSet::from([bond]), false);
+        assert_eq!(healed.keys().copied().collect::<Vec<_>>(), vec![bond]);
+        assert!((healed[&bond].0 - dx).abs() < 1e-6);
+    }
+
+    #[test]
+    fn objects_are_joined_only_with_welding() {
+        let mut builder = SceneBuilder::new(SPACING, 1.0);
+        builder.add_rectangle(5, 5, Vec2::new(0.0, 0.0), NodeProperties::new(SPACING, 1.0, 1.0, 1.0));
+        builder.add_rectangle(5, 5, Vec2::new(5.0 * SPACING, 0.0), NodeProperties::new(SPACING, 1.0, 1.0, 1.0));
+        let mut scene = builder.build().unwrap();
+        scene.update_boundary_nodes();
+
+        assert!(new_bonds(&scene, &BTreeSet::new(), false).is_empty());
+        let welded = new_bonds(&scene, &BTreeSet::new(), true);
+        assert!(!welded.is_empty());
+        assert!(welded.keys().all(|&(i, j)| scene.nodes[i].object_id != scene.nodes[j].object_id));
+    }
+
+    #[test]
+    fn fragments_of_one_object_heal_without_welding() {
+        let mut scene = block();
+        let cut = 4.5 * SPACING;
+        let nodes = scene.nodes.clone();
+        scene.connections.retain(|(i, j), _| (nodes[*i].position.x < cut) == (nodes[*j].position.x < cut));
+        let origins: BTreeMap<u32, u32> = relabel_fragments(&mut scene).into_iter().map(|(old, new)| (new, old)).collect();
+        scene.update_boundary_nodes();
+
+        assert!(new_bonds(&scene, &BTreeSet::new(), false).is_empty());
+        let healed = new_bonds_of_fragments(&scene, &origins, &BTreeSet::new(), false);
+        assert!(!healed.is_empty());
+        assert!(healed.keys().all(|&(i, j)| (nodes[i].position.x < cut) != (nodes[j].position.x < cut)));
+    }
+}
@@ -0,0 +1,171 @@
+use glam::Vec2;
+use serde::{Serialize, Deserialize};
+
+use crate::scene::Scene;
+
+use super::forces::bond_stiffness;
+use super::node::Node;
+
+/// Time integration scheme used by all simulation engines
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+pub enum Integrator {
+    // https://en.wikipedia.org/wiki/Semi-implicit_Euler_method
+    SymplecticEuler,
+    // drift-kick-drift leapfrog, https://en.wikipedia.org/wiki/Leapfrog_integration
+    PositionVerlet,
+    // https://en.wikipedia.org/wiki/Verlet_integration#Velocity_Verlet
+    VelocityVerlet,
+    // https://en.wikipedia.org/wiki/Runge%E2%80%93Kutta_methods
+    RungeKutta4,
+    /// Backward Euler with bonds and drag linearised per node, stays stable for stiff bonds
+    /// at the cost of damping fast oscillations
+    ImplicitEuler,
+}
+
+impl Default for Integrator {
+    fn default() -> Self {
+        Integrator::VelocityVerlet
+    }
+}
+
+impl Integrator {
+    pub const ALL: [Integrator; 5] = [
+        Integrator::SymplecticEuler,
+        Integrator::PositionVerlet,
+        Integrator::VelocityVerlet,
+        Integrator::RungeKutta4,
+        Integrator::ImplicitEuler,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Integrator::SymplecticEuler => "euler",
+            Integrator::PositionVerlet => "position-verlet",
+            Integrator::VelocityVerlet => "velocity-verlet",
+            Integrator::RungeKutta4 => "rk4",
+            Integrator::ImplicitEuler => "implicit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Integrator> {
+        Integrator::ALL.iter().copied().find(|integrator| integrator.name() == name)
+    }
+
+    /// Order of accuracy of positions, used by error estimates of adaptive dt
+    pub fn order(&self) -> i32 {
+        match self {
+            Integrator::SymplecticEuler | Integrator::ImplicitEuler => 1,
+            Integrator::PositionVerlet | Integrator::VelocityVerlet => 2,
+            Integrator::RungeKutta4 => 4,
+        }
+    }
+}
+
+pub fn start_integrate_velocity_verlet(dt: f32, nodes: &mut [Node]) {
+    nodes.iter_mut().for_each(|n| {
+        n.position += (n.velocity * dt) + (0.5 * n.current_acceleration * dt * dt);
+
+        n.last_acceleration = n.current_acceleration;
+        n.current_acceleration *= 0.0;
+    });
+}
+
+pub fn end_integrate_velocity_verlet(dt: f32, nodes: &mut [Node]) {
+    nodes.iter_mut().for_each(|n| {
+        n.velocity += 0.5 * (n.last_acceleration + n.current_acceleration) * dt;
+    });
+}
+
+/// Calls `accelerations` on current state of nodes, it adds acceleration of every node to `current_acceleration`
+fn evaluate(scene: &mut Scene, accelerations: &mut impl FnMut(&mut Scene)) -> Vec<Vec2> {
+    scene.nodes.iter_mut().for_each(|n| {
+        n.last_acceleration = n.current_acceleration;
+        n.current_acceleration *= 0.0;
+    });
+    accelerations(scene);
+    scene.nodes.iter().map(|n| n.current_acceleration).collect()
+}
+
+/// For every node sum of stiffness of its bonds divided by its mass, squared angular frequency of the node
+/// oscillating against fixed neighbours, and sum of `velocities` of its neighbours weighted the same way
+fn bond_coupling(scene: &Scene, velocities: &[Vec2]) -> (Vec<f32>, Vec<Vec2>) {
+    let mut frequencies = vec![0.0; scene.nodes.len()];
+    let mut coupled_velocities = vec![Vec2::ZERO; scene.nodes.len()];
+    scene.connections.iter().for_each(|(&(i, j), &(dx, v0))| {
+        let k = bond_stiffness(dx, v0);
+        frequencies[i] += k / scene.nodes[i].mass;
+        frequencies[j] += k / scene.nodes[j].mass;
+        coupled_velocities[i] += k / scene.nodes[i].mass * velocities[j];
+        coupled_velocities[j] += k / scene.nodes[j].mass * velocities[i];
+    });
+    (frequencies, coupled_velocities)
+}
+
+/// Advances nodes of the scene by `dt`. `accelerations` is the force evaluation of the engine,
+/// it must add acceleration of every node at its current position and velocity to `current_acceleration`.
+pub fn integrate(integrator: Integrator, dt: f32, scene: &mut Scene, mut accelerations: impl FnMut(&mut Scene)) {
+    match integrator {
+        Integrator::VelocityVerlet => {
+            start_integrate_velocity_verlet(dt, &mut scene.nodes);
+            accelerations(scene);
+            end_integrate_velocity_verlet(dt, &mut scene.nodes);
+        }
+        Integrator::SymplecticEuler => {
+            evaluate(scene, &mut accelerations);
+            scene.nodes.iter_mut().for_each(|n| {
+                n.velocity += n.current_acceleration * dt;
+                n.position += n.velocity * dt;
+            });
+        }
+        Integrator::PositionVerlet => {
+            scene.nodes.iter_mut().for_each(|n| n.position += 0.5 * n.velocity * dt);
+            evaluate(scene, &mut accelerations);
+            scene.nodes.iter_mut().for_each(|n| {
+                n.velocity += n.current_acceleration * dt;
+                n.position += 0.5 * n.velocity * dt;
+            });
+        }
+        Integrator::RungeKutta4 => {
+            let start: Vec<(Vec2, Vec2)> = scene.nodes.iter().map(|n| (n.position, n.velocity)).collect();
+            let mut position_rate = vec![Vec2::ZERO; start.len()];
+            let mut velocity_rate = vec![Vec2::ZERO; start.len()];
+
+            for (stage, (offset, weight)) in [(0.0, 1.0), (0.5, 2.0), (0.5, 2.0), (1.0, 1.0)].iter().enumerate() {
+                if stage > 0 {
+                    // state of the stage is moved from the start along derivatives of the previous stage
+                    scene.nodes.iter_mut().enumerate().for_each(|(i, n)| {
+                        let (position, velocity) = start[i];
+                        let acceleration = n.current_acceleration;
+                        n.position = position + offset * dt * n.velocity;
+                        n.velocity = velocity + offset * dt * acceleration;
+                    });
+                }
+                let stage_accelerations = evaluate(scene, &mut accelerations);
+                scene.nodes.iter().enumerate().for_each(|(i, n)| {
+                    position_rate[i] += *weight * n.velocity;
+                    velocity_rate[i] += *weight * stage_accelerations[i];
+                });
+            }
+
+            scene.nodes.iter_mut().enumerate().for_each(|(i, n)| {
+                let (position, velocity) = start[i];
+                n.position = position + position_rate[i] * dt / 6.0;
+                n.velocity = velocity + velocity_rate[i] * dt / 6.0;
+                n.current_acceleration = velocity_rate[i] / 6.0;
+            });
+        }
+        Integrator::ImplicitEuler => {
+            let accelerations = evaluate(scene, &mut accelerations);
+            let predicted: Vec<Vec2> = scene.nodes.iter().zip(accelerations.iter()).map(|(n, a)| n.velocity + *a * dt).collect();
+            let (frequencies, coupled_velocities) = bond_coupling(scene, &predicted);
+            scene.nodes.iter_mut().enumerate().for_each(|(i, n)| {
+                // v' = v + dt * a(x + dt * v', v') with bond forces linearised against explicitly predicted
+                // velocities of neighbours and drag linearised around current velocity, single Jacobi iteration
+                let drag_rate = 2.0 * n.drag * n.velocity.length();
+                let denominator = 1.0 + dt * dt * frequencies[i] + dt * drag_rate;
+                n.velocity = (n.velocity * (1.0 + dt * drag_rate) + accelerations[i] * dt + dt * dt * coupled_velocities[i]) / denominator;
+                n.position += n.velocity * dt;
+            });
+        }
+    }
+}
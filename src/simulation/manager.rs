@@ -1,11 +1,16 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Serialize, Deserialize};
+
 use crate::scene::Scene;
+use crate::scene::validation::SceneError;
 
-use super::{general::Grid};
+use super::{grid::Grid, neighbour_list::{NeighbourList, CollisionRules}, material::MaterialTable, fracture::FractureState, healing::BondHealEvent, fragments::FragmentStats, integrator::Integrator, timestep::{AdaptiveDtMethod, DtRecord}, temperature::TemperatureCache, checkpoint::{Checkpoint, CheckpointError}};
 use crate::simulation;
 #[cfg(feature = "opencl3")]
 use crate::simulation::gpu::gpu::SimulationEngine;
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
 pub enum SimulationEngineEnum {
     Cpu,
     CpuMultithread,
@@ -14,62 +19,271 @@ pub enum SimulationEngineEnum {
     None,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(default)]
 pub struct SimulationSettings {
     pub dt: f32,
     pub steps_per_frame: u32,
     pub engine: SimulationEngineEnum,
+    pub integrator: Integrator,
     pub use_grid: bool,
+    /// Size of grid cells, replaced by the range of repulsion when the scene has repulsion cutoff
     pub cell_size: f32,
+    /// With grid keep collision candidates in a Verlet list rebuilt only after nodes moved more than half of the skin
+    pub use_neighbour_list: bool,
+    pub neighbour_skin: f32,
     pub log_to_csv: bool,
     pub log_interval: f32,
     pub use_backup: bool,
     pub backup_interval: f32,
     pub use_auto_dt: bool,
     pub auto_dt_factor: f32,
+    /// Choose dt before every step with `adaptive_dt_method`, replaces auto dt
+    pub use_adaptive_dt: bool,
+    pub adaptive_dt_method: AdaptiveDtMethod,
+    /// Fraction of the shortest bond a node may travel in one step, used by CFL method
+    pub courant_number: f32,
+    /// Allowed position error of a step relative to the shortest bond, used by step doubling
+    pub error_tolerance: f32,
+    pub min_dt: f32,
+    pub max_dt: f32,
+    /// Create bonds between boundary nodes that come back into contact
+    pub use_healing: bool,
+    pub healing_distance: f32,
+    pub healing_max_speed: f32,
+    /// Allow healing to bond nodes of different objects
+    pub use_welding: bool,
+    /// Boundary nodes of the same object repel each other when they are not within `self_collision_hops` bonds
+    pub use_self_collision: bool,
+    pub self_collision_hops: u32,
+    /// Single threaded CPU engine sums bonds per node like the multithreaded engines, so all CPU engines
+    /// add forces in the same fixed order and runs are bit-identical regardless of the engine and the number of threads
+    pub deterministic: bool,
+}
+
+impl Default for SimulationSettings {
+    /// Settings of the headless runner, `cell_size` and `healing_distance` suit the default repulsion distance 0.04
+    fn default() -> Self {
+        SimulationSettings {
+            dt: 0.00002,
+            steps_per_frame: 5,
+            engine: SimulationEngineEnum::CpuMultithreadSingleKernel,
+            integrator: Integrator::VelocityVerlet,
+            use_grid: true,
+            cell_size: 0.1,
+            use_neighbour_list: true,
+            neighbour_skin: 0.02,
+            log_to_csv: false,
+            log_interval: 0.01,
+            use_backup: true,
+            backup_interval: 0.1,
+            use_auto_dt: false,
+            auto_dt_factor: 1.1,
+            use_adaptive_dt: false,
+            adaptive_dt_method: AdaptiveDtMethod::Cfl,
+            courant_number: 0.01,
+            error_tolerance: 0.001,
+            min_dt: 0.000001,
+            max_dt: MAX_DT,
+            use_healing: false,
+            healing_distance: 0.04,
+            healing_max_speed: 0.5,
+            use_welding: false,
+            use_self_collision: false,
+            self_collision_hops: 3,
+            deterministic: false,
+        }
+    }
+}
+
+impl SimulationSettings {
+    /// Bond distance within which nodes of the same object do not collide, `None` without self-collision
+    pub fn self_collision_hops(&self) -> Option<u32> {
+        if self.use_self_collision {
+            Some(self.self_collision_hops)
+        } else {
+            None
+        }
+    }
+}
+
+/// Fracture and healing history at the time `scene_backup` was taken, restored together with it.
+/// Event logs only grow, so their lengths are enough to cut off events recorded after the backup.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct HistoryBackup {
+    pub damage: HashMap<(usize, usize), f32>,
+    pub break_events: usize,
+    pub heal_events: usize,
+    pub fragment_records: usize,
+    pub object_origins: BTreeMap<u32, u32>,
 }
 
 pub struct SimulationManager {
     pub scene: Scene,
     pub scene_backup: Scene,
+    pub history_backup: HistoryBackup,
     pub current_backup_dt: f32,
     pub total_simulation_time: f32,
     pub connections_structure: Vec<Vec<(usize, f32, f32)>>,
-    pub collisions_structure: Vec<Vec<usize>>,
+    pub collisions_structure: Vec<Vec<(usize, f32, f32)>>,
+    pub material_table: MaterialTable,
+    pub fracture_state: FractureState,
+    pub healing_events: Vec<BondHealEvent>,
+    /// Fragment count and sizes at start and after every change of the count
+    pub fragment_history: Vec<FragmentStats>,
+    /// Object id of the original scene every fragment was split from, fragments healed back are removed
+    pub object_origins: BTreeMap<u32, u32>,
+    /// Time step at the end of every update, recorded when adaptive dt is enabled
+    pub dt_history: Vec<DtRecord>,
+    /// Simulated time of the last update
+    pub last_frame_time: f32,
+    /// Length scale of adaptive dt, shortest bond or repulsion distance
+    pub shortest_bond: f32,
+    /// Highest frequency of bond oscillations, limits dt chosen by CFL method
+    pub max_bond_frequency: f32,
+    /// Per node history used by temperature coloring
+    pub temperature_cache: TemperatureCache,
     pub grid: Grid,
+    /// Collision candidates used with grid when `use_neighbour_list` is set
+    pub neighbour_list: NeighbourList,
     pub settings: SimulationSettings,
-    #[cfg(feature = "opencl3")] pub opencl_simulation_engine: SimulationEngine,
+    #[cfg(feature = "opencl3")] pub opencl_simulation_engine: Option<SimulationEngine>,
 }
 
 pub const MAX_DT: f32 = 0.00005;
 
 impl SimulationManager {
-    pub fn new(simulation_settings: SimulationSettings, scene: Scene) -> Self {
+    /// Creates manager for the scene, scene that fails validation is rejected
+    pub fn new(simulation_settings: SimulationSettings, scene: Scene) -> Result<Self, Vec<SceneError>> {
+        scene.validate()?;
+        Ok(Self::from_scene(simulation_settings, scene))
+    }
 
+    fn from_scene(simulation_settings: SimulationSettings, scene: Scene) -> Self {
+        let material_table = MaterialTable::new(&scene);
         let connections_structure = simulation::general::calculate_connections_structure(&scene.connections, &scene.nodes);
-        let collisions_structure = simulation::general::calculate_collisions_structure_simple(&scene.nodes);
-        let grid = simulation::general::Grid::new(&scene.nodes, simulation_settings.cell_size);
+        let collisions_structure = simulation::general::calculate_collisions_structure_simple(&scene.nodes, &material_table, &connections_structure, simulation_settings.self_collision_hops());
+        let grid = Grid::new(&scene.nodes, simulation_settings.cell_size);
+        let shortest_bond = simulation::timestep::shortest_bond(&scene.connections, material_table.max_repulsion_dx());
+        let max_bond_frequency = simulation::timestep::max_bond_frequency(&scene.nodes, &scene.connections);
+        let fragment_history = vec![FragmentStats {
+            time: 0.0,
+            sizes: simulation::fragments::fragment_sizes(scene.nodes.len(), &scene.connections),
+        }];
 
+        // OpenCL context is created only when it is needed, so the manager can run on machines without GPU
         #[cfg(feature = "opencl3")]
-        let mut opencl_simulation_engine =  {
-            let mut engine = simulation::gpu::gpu::SimulationEngine::new();
-            engine.update_node_buffer(&scene.nodes);
-            engine.update_connection_buffer(&connections_structure);
-            engine.update_collision_buffer(&collisions_structure);
-            engine
+        let opencl_simulation_engine = if simulation_settings.engine == SimulationEngineEnum::OpenCl {
+            Some(Self::create_opencl_engine(&scene, &connections_structure, &collisions_structure))
+        } else {
+            None
         };
 
-        return SimulationManager {
+        SimulationManager {
             scene: scene.clone(),
             scene_backup: scene,
+            history_backup: HistoryBackup { fragment_records: fragment_history.len(), ..Default::default() },
             current_backup_dt: 0.0,
             total_simulation_time: 0.0,
             connections_structure: connections_structure,
             collisions_structure: collisions_structure,
+            material_table: material_table,
+            fracture_state: FractureState::default(),
+            healing_events: Vec::new(),
+            fragment_history: fragment_history,
+            object_origins: BTreeMap::new(),
+            dt_history: Vec::new(),
+            last_frame_time: simulation_settings.dt * simulation_settings.steps_per_frame as f32,
+            shortest_bond: shortest_bond,
+            max_bond_frequency: max_bond_frequency,
+            temperature_cache: TemperatureCache::default(),
             grid: grid,
+            neighbour_list: NeighbourList::default(),
             settings: simulation_settings,
             #[cfg(feature = "opencl3")] opencl_simulation_engine: opencl_simulation_engine
+        }
+    }
+
+    /// State needed to resume the simulation exactly where it is now
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            scene: self.scene.clone(),
+            scene_backup: self.scene_backup.clone(),
+            history_backup: self.history_backup.clone(),
+            settings: self.settings,
+            total_simulation_time: self.total_simulation_time,
+            current_backup_dt: self.current_backup_dt,
+            last_frame_time: self.last_frame_time,
+            fracture_state: self.fracture_state.clone(),
+            healing_events: self.healing_events.clone(),
+            fragment_history: self.fragment_history.clone(),
+            object_origins: self.object_origins.clone(),
+            dt_history: self.dt_history.clone(),
+            temperature_cache: self.temperature_cache.clone(),
+            neighbour_list: self.neighbour_list.clone(),
+        }
+    }
+
+    /// Creates manager continuing from the checkpoint, structures derived from the scene are rebuilt.
+    /// Only the backup is validated, scene saved just before restoring from backup may be broken.
+    pub fn from_checkpoint(checkpoint: Checkpoint) -> Result<Self, Vec<SceneError>> {
+        checkpoint.scene_backup.validate()?;
+        let mut manager = Self::from_scene(checkpoint.settings, checkpoint.scene);
+        manager.scene_backup = checkpoint.scene_backup;
+        manager.history_backup = checkpoint.history_backup;
+        manager.total_simulation_time = checkpoint.total_simulation_time;
+        manager.current_backup_dt = checkpoint.current_backup_dt;
+        manager.last_frame_time = checkpoint.last_frame_time;
+        manager.fracture_state = checkpoint.fracture_state;
+        manager.healing_events = checkpoint.healing_events;
+        manager.fragment_history = checkpoint.fragment_history;
+        manager.object_origins = checkpoint.object_origins;
+        manager.dt_history = checkpoint.dt_history;
+        manager.temperature_cache = checkpoint.temperature_cache;
+        manager.restore_neighbour_list(checkpoint.neighbour_list);
+        Ok(manager)
+    }
+
+    /// Continues with the list saved in checkpoint, so that collisions are the same as they were before saving
+    fn restore_neighbour_list(&mut self, neighbour_list: NeighbourList) {
+        if !self.settings.use_grid || !self.settings.use_neighbour_list || neighbour_list.positions.len() != self.scene.nodes.len() {
+            self.neighbour_list.rebuilds = neighbour_list.rebuilds;
+            return;
+        }
+        let rules = CollisionRules {
+            material_table: &self.material_table,
+            connections_structure: &self.connections_structure,
+            self_collision_hops: neighbour_list.self_collision_hops,
         };
+        self.collisions_structure = neighbour_list.rebuild_last(&self.scene.nodes, &mut self.grid, rules);
+        self.neighbour_list = neighbour_list;
+        #[cfg(feature = "opencl3")]
+        if let Some(engine) = self.opencl_simulation_engine.as_mut() {
+            engine.update_collision_buffer(&self.collisions_structure);
+        }
+    }
+
+    pub fn save_checkpoint(&self, path: &str) -> Result<(), CheckpointError> {
+        simulation::checkpoint::save(path, &self.checkpoint())
+    }
+
+    pub fn load_checkpoint(path: &str) -> Result<Self, CheckpointError> {
+        let checkpoint = simulation::checkpoint::load(path)?;
+        Self::from_checkpoint(checkpoint).map_err(CheckpointError::Invalid)
+    }
+
+    #[cfg(feature = "opencl3")]
+    fn create_opencl_engine(
+        scene: &Scene,
+        connections_structure: &[Vec<(usize, f32, f32)>],
+        collisions_structure: &[Vec<(usize, f32, f32)>]
+    ) -> SimulationEngine {
+        let mut engine = simulation::gpu::gpu::SimulationEngine::new();
+        engine.update_node_buffer(&scene.nodes);
+        engine.update_wall_buffer(&scene.environment);
+        engine.update_connection_buffer(connections_structure);
+        engine.update_collision_buffer(collisions_structure);
+        engine
     }
 
     pub fn grid_check(&mut self) {
@@ -77,7 +291,7 @@ impl SimulationManager {
             unsafe {
                 static mut LAST_ITERATION_USE_GRID: bool = false;
                 if self.settings.use_grid != LAST_ITERATION_USE_GRID && self.settings.use_grid == false {
-                    self.collisions_structure = simulation::general::calculate_collisions_structure_simple(&self.scene.nodes);
+                    self.collisions_structure = simulation::general::calculate_collisions_structure_simple(&self.scene.nodes, &self.material_table, &self.connections_structure, self.settings.self_collision_hops());
                 }
                 LAST_ITERATION_USE_GRID = self.settings.use_grid;
             }
@@ -85,78 +299,290 @@ impl SimulationManager {
     }
 
     pub fn connection_break(&mut self) {
-        if simulation::general::handle_connection_break(&mut self.scene.nodes, &mut self.scene.connections) {
-            self.connections_structure = simulation::general::calculate_connections_structure(&self.scene.connections, &self.scene.nodes);
-            #[cfg(feature = "opencl3")]
-            if self.settings.engine == SimulationEngineEnum::OpenCl {
-                self.opencl_simulation_engine.update_connection_buffer(&self.connections_structure);
-            }
+        let dt = self.last_step_dt();
+        let broken = simulation::general::handle_connection_break(
+            &self.scene.nodes,
+            &mut self.scene.connections,
+            &self.scene.force_field,
+            &self.material_table,
+            &mut self.fracture_state,
+            self.total_simulation_time,
+            dt
+        );
+        if broken {
+            self.detect_fragments();
+            self.connections_changed();
+        }
+    }
 
-            if !self.settings.use_grid {
-                self.collisions_structure = simulation::general::calculate_collisions_structure_simple(&self.scene.nodes);
-                #[cfg(feature = "opencl3")]
-                if self.settings.engine == SimulationEngineEnum::OpenCl {
-                    self.opencl_simulation_engine.update_collision_buffer(&self.collisions_structure);
-                }
+    /// Gives new object ids to parts of objects separated by broken bonds and records fragment sizes
+    fn detect_fragments(&mut self) {
+        let created = simulation::fragments::relabel_fragments(&mut self.scene);
+        if !created.is_empty() {
+            created.iter().for_each(|(old_id, new_id)| {
+                let origin = simulation::fragments::original_object(&self.object_origins, *old_id);
+                self.object_origins.insert(*new_id, origin);
+            });
+            self.material_table = MaterialTable::new(&self.scene);
+        }
+
+        self.record_fragments();
+    }
+
+    /// Adds fragment sizes to the history when the number of fragments changed
+    fn record_fragments(&mut self) {
+        let sizes = simulation::fragments::fragment_sizes(self.scene.nodes.len(), &self.scene.connections);
+        if self.fragment_history.last().map_or(true, |last| last.count() != sizes.len()) {
+            self.fragment_history.push(FragmentStats { time: self.total_simulation_time, sizes });
+        }
+    }
+
+    pub fn connection_heal(&mut self) {
+        if !self.settings.use_healing {
+            return;
+        }
+
+        let fragments = simulation::fragments::connected_components(self.scene.nodes.len(), &self.scene.connections);
+        let broken_bonds = self.fracture_state.broken_bonds();
+        let new_bonds = simulation::healing::find_new_bonds(
+            &self.scene.nodes,
+            &self.connections_structure,
+            &fragments,
+            &self.object_origins,
+            &broken_bonds,
+            simulation::healing::Capture { distance: self.settings.healing_distance, max_relative_speed: self.settings.healing_max_speed },
+            self.settings.use_welding
+        );
+        if new_bonds.is_empty() {
+            return;
+        }
+
+        let mut bonds: Vec<(usize, usize)> = new_bonds.keys().copied().collect();
+        bonds.sort();
+        bonds.iter().for_each(|&(i, j)| {
+            self.healing_events.push(BondHealEvent {
+                time: self.total_simulation_time,
+                bond: (i, j),
+                welded: simulation::fragments::original_object(&self.object_origins, self.scene.nodes[i].object_id)
+                    != simulation::fragments::original_object(&self.object_origins, self.scene.nodes[j].object_id),
+            });
+        });
+
+        self.scene.connections.extend(new_bonds);
+        self.merge_fragments();
+        self.record_fragments();
+        self.connections_changed();
+    }
+
+    /// Gives fragments of one object joined by healing a single id again, so they collide and have boundary as one object
+    fn merge_fragments(&mut self) {
+        let merged = simulation::fragments::merge_healed_fragments(&mut self.scene, &self.object_origins);
+        if !merged.is_empty() {
+            merged.iter().for_each(|(merged_id, _kept_id)| {
+                self.object_origins.remove(merged_id);
+            });
+            self.material_table = MaterialTable::new(&self.scene);
+        }
+    }
+
+    /// Rebuilds structures derived from connections and boundary nodes after bonds were added or removed
+    fn connections_changed(&mut self) {
+        self.connections_structure = simulation::general::calculate_connections_structure(&self.scene.connections, &self.scene.nodes);
+        simulation::boundary::update_boundary_nodes(&mut self.scene.nodes, &self.connections_structure);
+        self.shortest_bond = simulation::timestep::shortest_bond(&self.scene.connections, self.material_table.max_repulsion_dx());
+        self.max_bond_frequency = simulation::timestep::max_bond_frequency(&self.scene.nodes, &self.scene.connections);
+        #[cfg(feature = "opencl3")]
+        if let Some(engine) = self.opencl_simulation_engine.as_mut() {
+            engine.update_connection_buffer(&self.connections_structure);
+        }
+
+        // with grid collisions are rebuilt in the next update
+        self.neighbour_list.positions.clear();
+        if !self.settings.use_grid {
+            self.collisions_structure = simulation::general::calculate_collisions_structure_simple(&self.scene.nodes, &self.material_table, &self.connections_structure, self.settings.self_collision_hops());
+            #[cfg(feature = "opencl3")]
+            if let Some(engine) = self.opencl_simulation_engine.as_mut() {
+                engine.update_collision_buffer(&self.collisions_structure);
             }
         }
     }
 
+    /// Kinetic, gravity, bond, wall and object repulsion energies, repulsion over the current collision pairs
+    pub fn total_energy(&self) -> (f32, f32, f32, f32, f32) {
+        simulation::energy::calculate_total_energy(&self.scene, &self.collisions_structure)
+    }
+
+    /// Distance within which the grid has to find colliding nodes, nodes farther apart do not repel
+    /// each other with repulsion cutoff, otherwise their repulsion is neglected from the cell size
+    pub fn collision_radius(&self) -> f32 {
+        self.scene.force_field.repulsion.range(self.material_table.max_repulsion_dx()).unwrap_or(self.settings.cell_size)
+    }
+
     pub fn update_grid(&mut self) {
         if self.settings.use_grid {
-            self.grid = simulation::general::Grid::new(&self.scene.nodes, self.settings.cell_size);
-            self.collisions_structure = simulation::general::calculate_collisions_structure_with_grid(&self.scene.nodes, &self.grid);
+            let radius = self.collision_radius();
+            if self.settings.use_neighbour_list {
+                let (skin, hops) = (self.settings.neighbour_skin, self.settings.self_collision_hops());
+                if !self.neighbour_list.needs_rebuild(&self.scene.nodes, radius, skin, hops) {
+                    return;
+                }
+                let rules = CollisionRules {
+                    material_table: &self.material_table,
+                    connections_structure: &self.connections_structure,
+                    self_collision_hops: hops,
+                };
+                self.collisions_structure = self.neighbour_list.build(&self.scene.nodes, &mut self.grid, rules, radius, skin);
+            } else {
+                self.neighbour_list.positions.clear();
+                self.grid.update(&self.scene.nodes, radius);
+                self.collisions_structure = simulation::general::calculate_collisions_structure_with_grid(&self.scene.nodes, &self.grid, &self.material_table, &self.connections_structure, self.settings.self_collision_hops());
+            }
             #[cfg(feature = "opencl3")]
-            if self.settings.engine == SimulationEngineEnum::OpenCl {
-                self.opencl_simulation_engine.update_collision_buffer(&self.collisions_structure);
+            if let Some(engine) = self.opencl_simulation_engine.as_mut() {
+                engine.update_collision_buffer(&self.collisions_structure);
             }
+        } else {
+            self.neighbour_list.positions.clear();
         }
     }
 
-    pub fn next_step(&mut self) {
+    /// Moves colliders to their poses at `time` and sums reactions of nodes in the new poses.
+    /// Colliders stay in place during a step, they are moved after every step.
+    fn update_colliders(&mut self, time: f32, dt: f32) {
+        let scene = &mut self.scene;
+        scene.environment.move_colliders(time);
+        scene.environment.colliders.iter_mut().for_each(|c| {
+            c.update_reaction(&scene.force_field, &scene.nodes, dt);
+        });
+    }
+
+    /// Runs single step of the selected engine
+    fn simulate_step(&mut self, dt: f32) {
         match self.settings.engine {
             SimulationEngineEnum::Cpu => {
-                for _i in 0..self.settings.steps_per_frame {
-                    simulation::cpu::simulate_single_thread_cpu(
-                        self.settings.dt,
-                        &mut self.scene,
-                        &self.collisions_structure
-                    );
-                }
+                simulation::cpu::simulate_single_thread_cpu(
+                    dt,
+                    self.settings.integrator,
+                    &mut self.scene,
+                    &self.connections_structure,
+                    &self.collisions_structure,
+                    self.settings.deterministic
+                );
             }
             SimulationEngineEnum::CpuMultithread => {
-                for _i in 0..self.settings.steps_per_frame {
-                    simulation::cpu::simulate_multi_thread_cpu(
-                        self.settings.dt,
-                        &mut self.scene,
-                        &self.connections_structure,
-                        &self.collisions_structure
-                    );
-                }
+                simulation::cpu::simulate_multi_thread_cpu(
+                    dt,
+                    self.settings.integrator,
+                    &mut self.scene,
+                    &self.connections_structure,
+                    &self.collisions_structure
+                );
             }
             SimulationEngineEnum::CpuMultithreadSingleKernel => {
-                for _i in 0..self.settings.steps_per_frame {
-                    simulation::cpu::simulate_multi_thread_cpu_enchanced(
-                        self.settings.dt,
-                        &mut self.scene,
-                        &self.connections_structure,
-                        &self.collisions_structure
-                    );
-                }
+                simulation::cpu::simulate_multi_thread_cpu_enchanced(
+                    dt,
+                    self.settings.integrator,
+                    &mut self.scene,
+                    &self.connections_structure,
+                    &self.collisions_structure
+                );
             }
             #[cfg(feature = "opencl3")]
             SimulationEngineEnum::OpenCl => {
-                for _i in 0..self.settings.steps_per_frame {
-                    self.opencl_simulation_engine.simulate_opencl(
-                        self.settings.dt,
-                        &mut self.scene,
-                    );
+                if self.opencl_simulation_engine.is_none() {
+                    self.opencl_simulation_engine = Some(Self::create_opencl_engine(
+                        &self.scene,
+                        &self.connections_structure,
+                        &self.collisions_structure
+                    ));
                 }
+                let engine = self.opencl_simulation_engine.as_mut().unwrap();
+                engine.simulate_opencl(
+                    dt,
+                    self.settings.integrator,
+                    &mut self.scene,
+                );
             }
             _ => {}
         }
     }
 
+    fn is_engine_running(&self) -> bool {
+        match self.settings.engine {
+            SimulationEngineEnum::None => false,
+            #[cfg(not(feature = "opencl3"))]
+            SimulationEngineEnum::OpenCl => false,
+            _ => true,
+        }
+    }
+
+    /// Makes one step as two half steps, repeated with smaller dt while they differ from a single full step
+    /// more than the tolerance allows. Returns length of the step and number of rejected attempts.
+    fn step_doubling_step(&mut self) -> (f32, u32) {
+        let start = self.scene.nodes.clone();
+        let tolerance = self.settings.error_tolerance * self.shortest_bond;
+        let mut rejected_steps = 0;
+
+        loop {
+            let dt = self.settings.dt;
+            self.simulate_step(dt);
+            let full_step = std::mem::replace(&mut self.scene.nodes, start.clone());
+            self.simulate_step(0.5 * dt);
+            self.simulate_step(0.5 * dt);
+
+            let error = simulation::timestep::max_position_difference(&full_step, &self.scene.nodes);
+            let next_dt = simulation::timestep::step_doubling_dt(dt, error, tolerance, self.settings.integrator.order());
+            self.settings.dt = next_dt.clamp(self.settings.min_dt, self.settings.max_dt);
+
+            if error <= tolerance || dt <= self.settings.min_dt {
+                return (dt, rejected_steps);
+            }
+            rejected_steps += 1;
+            self.scene.nodes = start.clone();
+        }
+    }
+
+    pub fn next_step(&mut self) {
+        let mut frame_time = 0.0;
+        let mut rejected_steps = 0;
+
+        if self.is_engine_running() {
+            for _i in 0..self.settings.steps_per_frame {
+                let dt = if !self.settings.use_adaptive_dt {
+                    self.simulate_step(self.settings.dt);
+                    self.settings.dt
+                } else {
+                    match self.settings.adaptive_dt_method {
+                        AdaptiveDtMethod::Cfl => {
+                            let dt = simulation::timestep::cfl_dt(&self.scene.nodes, self.shortest_bond, self.settings.courant_number, self.max_bond_frequency);
+                            self.settings.dt = simulation::timestep::limit_dt(dt, self.settings.dt, self.settings.min_dt, self.settings.max_dt);
+                            self.simulate_step(self.settings.dt);
+                            self.settings.dt
+                        }
+                        AdaptiveDtMethod::StepDoubling => {
+                            let (dt, rejected) = self.step_doubling_step();
+                            rejected_steps += rejected;
+                            dt
+                        }
+                    }
+                };
+                frame_time += dt;
+                if !self.scene.environment.colliders.is_empty() {
+                    self.update_colliders(self.total_simulation_time + frame_time, dt);
+                }
+            }
+        }
+
+        self.last_frame_time = frame_time;
+        if self.settings.use_adaptive_dt && self.is_engine_running() {
+            self.dt_history.push(DtRecord {
+                time: self.total_simulation_time + frame_time,
+                dt: self.settings.dt,
+                rejected_steps,
+            });
+        }
+    }
+
     fn update_backup(&mut self) {
         if self.settings.use_backup {
             self.current_backup_dt += self.last_step_dt();
@@ -185,19 +611,32 @@ impl SimulationManager {
         if self.is_broken() {
             println!("Error detected, restoring scene");
             self.scene = self.scene_backup.clone();
+            self.restore_history();
+            self.material_table = MaterialTable::new(&self.scene);
             self.connections_structure = simulation::general::calculate_connections_structure(&self.scene.connections, &self.scene.nodes);
-            self.grid = simulation::general::Grid::new(&self.scene.nodes, self.settings.cell_size);
-            self.collisions_structure = simulation::general::calculate_collisions_structure_simple(&self.scene.nodes);
+            let radius = self.collision_radius();
+            self.grid.update(&self.scene.nodes, radius);
+            self.neighbour_list.positions.clear();
+            self.collisions_structure = simulation::general::calculate_collisions_structure_simple(&self.scene.nodes, &self.material_table, &self.connections_structure, self.settings.self_collision_hops());
+            self.shortest_bond = simulation::timestep::shortest_bond(&self.scene.connections, self.material_table.max_repulsion_dx());
+            self.max_bond_frequency = simulation::timestep::max_bond_frequency(&self.scene.nodes, &self.scene.connections);
             self.settings.dt *= 0.5;
             #[cfg(feature = "opencl3")]
-            if self.settings.engine == SimulationEngineEnum::OpenCl {
-                self.opencl_simulation_engine.update_connection_buffer(&self.connections_structure);
-                self.opencl_simulation_engine.update_collision_buffer(&self.collisions_structure);
+            if let Some(engine) = self.opencl_simulation_engine.as_mut() {
+                engine.update_connection_buffer(&self.connections_structure);
+                engine.update_collision_buffer(&self.collisions_structure);
             }
         }
         else {
             self.scene_backup = self.scene.clone();
-            if self.settings.use_auto_dt {
+            self.history_backup = HistoryBackup {
+                damage: self.fracture_state.damage.clone(),
+                break_events: self.fracture_state.events.len(),
+                heal_events: self.healing_events.len(),
+                fragment_records: self.fragment_history.len(),
+                object_origins: self.object_origins.clone(),
+            };
+            if self.settings.use_auto_dt && !self.settings.use_adaptive_dt {
                 self.settings.dt *= self.settings.auto_dt_factor;
                 if self.settings.dt > MAX_DT {
                     self.settings.dt = MAX_DT;
@@ -206,13 +645,25 @@ impl SimulationManager {
         }
     }
 
+    /// Drops bonds broken and healed after the backup, so the logs match the restored scene
+    fn restore_history(&mut self) {
+        let backup = &self.history_backup;
+        self.fracture_state.damage = backup.damage.clone();
+        self.fracture_state.events.truncate(backup.break_events);
+        self.healing_events.truncate(backup.heal_events);
+        self.fragment_history.truncate(backup.fragment_records);
+        self.object_origins = backup.object_origins.clone();
+    }
+
+    /// Simulated time of the last update
     pub fn last_step_dt(&self) -> f32 {
-        self.settings.dt * self.settings.steps_per_frame as f32
+        self.last_frame_time
     }
 
     pub fn update(&mut self) {
         self.grid_check();
         self.connection_break();
+        self.connection_heal();
         self.update_grid();
         self.next_step();
         self.update_backup();
@@ -220,4 +671,76 @@ impl SimulationManager {
         self.total_simulation_time += self.last_step_dt();
     }
 
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::*;
+    use crate::scene::builder::{NodeProperties, SceneBuilder};
+    use crate::simulation::fracture::{BondBreakEvent, BreakCause};
+
+    #[test]
+    fn restoring_backup_rolls_back_fracture_and_healing_history() {
+        let mut builder = SceneBuilder::new(0.04, 100.0);
+        builder.add_rectangle(4, 4, Vec2::new(0.0, 0.0), NodeProperties::new(0.03, 1.0, 1.0, 10.0));
+        let mut manager = SimulationManager::new(SimulationSettings::default(), builder.build().unwrap()).unwrap();
+
+        manager.fracture_state.damage.insert((0, 1), 0.25);
+        manager.restore_if_broken();
+        let fracture_state = manager.fracture_state.clone();
+        let fragment_history = manager.fragment_history.clone();
+
+        manager.fracture_state.damage.insert((0, 1), 0.75);
+        manager.fracture_state.events.push(BondBreakEvent { time: 0.1, bond: (0, 1), cause: BreakCause::Strain, strain: 0.6 });
+        manager.healing_events.push(BondHealEvent { time: 0.2, bond: (0, 1), welded: false });
+        manager.fragment_history.push(FragmentStats { time: 0.1, sizes: vec![15, 1] });
+        manager.scene.nodes[0].position.x = f32::NAN;
+        manager.restore_if_broken();
+
+        assert!(!manager.is_broken());
+        assert_eq!(manager.fracture_state, fracture_state);
+        assert!(manager.healing_events.is_empty());
+        assert_eq!(manager.fragment_history, fragment_history);
+    }
+
+    #[test]
+    fn healed_crack_joins_fragments_into_one_object() {
+        let mut builder = SceneBuilder::new(0.04, 100.0);
+        builder.add_rectangle(6, 4, Vec2::new(0.0, 0.0), NodeProperties::new(0.03, 1.0, 1.0, 10.0));
+        let settings = SimulationSettings { use_healing: true, healing_distance: 0.05, ..Default::default() };
+        let mut manager = SimulationManager::new(settings, builder.build().unwrap()).unwrap();
+        let object_id = manager.scene.nodes[0].object_id;
+
+        // vertical crack between the third and the fourth column
+        let cut = 0.075;
+        let nodes = &manager.scene.nodes;
+        let crack_bonds: Vec<(usize, usize)> = manager.scene.connections.keys()
+            .filter(|(i, j)| (nodes[*i].position.x < cut) != (nodes[*j].position.x < cut))
+            .copied()
+            .collect();
+        let crack_nodes: Vec<usize> = (0..nodes.len())
+            .filter(|i| (nodes[*i].position.x - cut).abs() < 0.03 && nodes[*i].position.y > 0.01 && nodes[*i].position.y < 0.08)
+            .collect();
+        assert_eq!(crack_nodes.len(), 4);
+        assert!(crack_nodes.iter().all(|i| !manager.scene.nodes[*i].is_boundary));
+
+        crack_bonds.iter().for_each(|bond| {
+            manager.scene.connections.remove(bond);
+            manager.fracture_state.events.push(BondBreakEvent { time: 0.0, bond: *bond, cause: BreakCause::Strain, strain: 0.5 });
+        });
+        manager.detect_fragments();
+        manager.connections_changed();
+
+        assert_eq!(manager.object_origins.len(), 1);
+        assert!(manager.scene.nodes.iter().any(|n| n.object_id != object_id));
+        assert!(crack_nodes.iter().all(|i| manager.scene.nodes[*i].is_boundary));
+
+        manager.connection_heal();
+
+        assert!(crack_bonds.iter().all(|bond| manager.scene.connections.contains_key(bond)));
+        assert!(manager.scene.nodes.iter().all(|n| n.object_id == object_id));
+        assert!(manager.object_origins.is_empty());
+        assert!(crack_nodes.iter().all(|i| !manager.scene.nodes[*i].is_boundary));
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Serialize, Deserialize};
+
+use crate::scene::Scene;
+use super::node::Node;
+use super::fracture::FractureRule;
+
+/// Relative stretch of a bond after which it breaks, used for objects without material
+pub const DEFAULT_BREAK_STRAIN: f32 = 0.5;
+
+/// Physical properties shared by all nodes and bonds of objects made from it.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Material {
+    pub name: String,
+    /// Mass per unit of area, node mass is density multiplied by area of a lattice cell
+    pub density: f32,
+    /// Strength `v0` of bonds between nodes
+    pub bond_stiffness: f32,
+    /// Distance between neighbouring nodes when object is created
+    pub rest_spacing: f32,
+    /// Relative stretch of a bond after which it breaks
+    #[serde(default = "default_break_strain")]
+    pub break_strain: f32,
+    /// Other conditions breaking bonds, by default only break strain is used
+    #[serde(default)]
+    pub fracture: FractureRule,
+    #[serde(default)]
+    pub damping: f32,
+    /// Repulsion from nodes of other objects, mixed with parameters of the other material
+    pub repulsion_dx: f32,
+    pub repulsion_v0: f32,
+}
+
+fn default_break_strain() -> f32 {
+    DEFAULT_BREAK_STRAIN
+}
+
+/// Materials used in the scene and their assignment to objects
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct MaterialLibrary {
+    pub list: Vec<Material>,
+    /// Index into `list` for each object id, objects not present here have no material
+    pub object_materials: HashMap<u32, usize>,
+    /// Repulsion `(dx, v0)` between materials `(a, b)` with `a <= b`, replaces mixed parameters of the pair
+    pub pair_repulsion: BTreeMap<(usize, usize), (f32, f32)>,
+}
+
+impl MaterialLibrary {
+    /// Adds material and returns its index
+    pub fn add(&mut self, material: Material) -> usize {
+        self.list.push(material);
+        self.list.len() - 1
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.list.iter().position(|m| m.name == name)
+    }
+
+    pub fn set_pair_repulsion(&mut self, a: usize, b: usize, dx: f32, v0: f32) {
+        self.pair_repulsion.insert((a.min(b), a.max(b)), (dx, v0));
+    }
+}
+
+/// Repulsion between two materials, arithmetic mean of distances and geometric mean of strengths
+pub fn mix_repulsion(a: (f32, f32), b: (f32, f32)) -> (f32, f32) {
+    (0.5 * (a.0 + b.0), (a.1 * b.1).sqrt())
+}
+
+/// Lookup of material properties by object id, built from the scene for use in simulation.
+///
+/// Objects without material use scene wide `object_repulsion_dx`, `object_repulsion_v0`,
+/// `DEFAULT_BREAK_STRAIN` and no other fracture rules, they are stored as the last entry of the table.
+#[derive(Clone, Debug)]
+pub struct MaterialTable {
+    object_material: HashMap<u32, usize>,
+    default_material: usize,
+    repulsion: Vec<Vec<(f32, f32)>>,
+    break_strain: Vec<f32>,
+    fracture: Vec<FractureRule>,
+}
+
+impl MaterialTable {
+    pub fn new(scene: &Scene) -> MaterialTable {
+        let library = &scene.materials;
+        let default_material = library.list.len();
+
+        let mut own_repulsion: Vec<(f32, f32)> = library.list.iter().map(|m| (m.repulsion_dx, m.repulsion_v0)).collect();
+        own_repulsion.push((scene.object_repulsion_dx, scene.object_repulsion_v0));
+
+        let mut break_strain: Vec<f32> = library.list.iter().map(|m| m.break_strain).collect();
+        break_strain.push(DEFAULT_BREAK_STRAIN);
+
+        let mut fracture: Vec<FractureRule> = library.list.iter().map(|m| m.fracture).collect();
+        fracture.push(FractureRule::default());
+
+        let repulsion = (0..own_repulsion.len()).map(|a| {
+            (0..own_repulsion.len()).map(|b| {
+                let pair = if a < b { (a, b) } else { (b, a) };
+                match library.pair_repulsion.get(&pair) {
+                    Some(parameters) => *parameters,
+                    None => mix_repulsion(own_repulsion[a], own_repulsion[b]),
+                }
+            }).collect()
+        }).collect();
+
+        MaterialTable {
+            object_material: library.object_materials.clone(),
+            default_material,
+            repulsion,
+            break_strain,
+            fracture,
+        }
+    }
+
+    /// Index of material of the object, objects without material get index past the scene materials
+    pub fn material_of(&self, object_id: u32) -> usize {
+        *self.object_material.get(&object_id).unwrap_or(&self.default_material)
+    }
+
+    pub fn node_materials(&self, nodes: &[Node]) -> Vec<usize> {
+        nodes.iter().map(|n| self.material_of(n.object_id)).collect()
+    }
+
+    /// Repulsion `(dx, v0)` between materials with given indexes
+    pub fn repulsion(&self, material_a: usize, material_b: usize) -> (f32, f32) {
+        self.repulsion[material_a][material_b]
+    }
+
+    pub fn break_strain(&self, material: usize) -> f32 {
+        self.break_strain[material]
+    }
+
+    pub fn fracture(&self, material: usize) -> &FractureRule {
+        &self.fracture[material]
+    }
+
+    /// Largest repulsion distance between any pair of materials, grid cells should be larger than that
+    pub fn max_repulsion_dx(&self) -> f32 {
+        self.repulsion.iter().flatten().fold(0.0, |acc: f32, (dx, _v0)| acc.max(*dx))
+    }
+}
@@ -1,8 +1,22 @@
 pub mod general;
+pub mod boundary;
+pub mod checkpoint;
+pub mod collider;
 pub mod cpu;
+pub mod cross_validation;
+pub mod environment;
+pub mod forces;
+pub mod fracture;
+pub mod fragments;
+pub mod grid;
+pub mod healing;
+pub mod integrator;
+pub mod material;
+pub mod neighbour_list;
 pub mod node;
 pub mod energy;
 pub mod temperature;
+pub mod timestep;
 pub mod pressure;
 pub mod manager;
 
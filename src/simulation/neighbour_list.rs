@@ -0,0 +1,73 @@
+use glam::Vec2;
+use serde::{Serialize, Deserialize};
+
+use super::general::calculate_collisions_structure_with_grid;
+use super::grid::Grid;
+use super::material::MaterialTable;
+use super::node::Node;
+
+/// Decides which pairs of nodes collide and with which repulsion parameters
+#[derive(Clone, Copy)]
+pub struct CollisionRules<'a> {
+    pub material_table: &'a MaterialTable,
+    pub connections_structure: &'a [Vec<(usize, f32, f32)>],
+    /// Nodes of one object collide only when they are more than this many bonds apart, `None` disables self collisions
+    pub self_collision_hops: Option<u32>,
+}
+
+/// Verlet list of collision candidates.
+///
+/// The list holds pairs of boundary nodes closer than `radius + skin` when it was built and is kept
+/// until some node moves more than half of the skin away from its position at the build,
+/// so pairs closer than `radius` are always in the list.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub struct NeighbourList {
+    /// Node positions at the last build, empty before the first build
+    pub positions: Vec<Vec2>,
+    pub radius: f32,
+    pub skin: f32,
+    pub self_collision_hops: Option<u32>,
+    /// Number of builds since the start of the simulation
+    pub rebuilds: u64,
+}
+
+impl NeighbourList {
+    /// Whether some node moved more than half of the skin since the last build or the list was built with other parameters
+    pub fn needs_rebuild(&self, nodes: &[Node], radius: f32, skin: f32, self_collision_hops: Option<u32>) -> bool {
+        if self.positions.len() != nodes.len() || self.radius != radius || self.skin != skin || self.self_collision_hops != self_collision_hops {
+            return true;
+        }
+        let max_displacement = 0.5 * skin;
+        nodes.iter().zip(&self.positions).any(|(n, p)| n.position.distance(*p) > max_displacement || n.position.is_nan())
+    }
+
+    /// Builds the list for current node positions, `grid` is updated with cell size `radius + skin`
+    pub fn build(&mut self, nodes: &[Node], grid: &mut Grid, rules: CollisionRules, radius: f32, skin: f32) -> Vec<Vec<(usize, f32, f32)>> {
+        self.positions = nodes.iter().map(|n| n.position).collect();
+        self.radius = radius;
+        self.skin = skin;
+        self.self_collision_hops = rules.self_collision_hops;
+        self.rebuilds += 1;
+        self.collisions(nodes, grid, rules)
+    }
+
+    /// Collision structure of the last build, used to continue with the same list after loading a checkpoint.
+    /// Bonds and boundary nodes must not have changed since the build, self collision hops of the build are used.
+    pub fn rebuild_last(&self, nodes: &[Node], grid: &mut Grid, rules: CollisionRules) -> Vec<Vec<(usize, f32, f32)>> {
+        let mut nodes_at_build = nodes.to_vec();
+        nodes_at_build.iter_mut().zip(&self.positions).for_each(|(n, p)| n.position = *p);
+        self.collisions(&nodes_at_build, grid, CollisionRules { self_collision_hops: self.self_collision_hops, ..rules })
+    }
+
+    fn collisions(&self, nodes: &[Node], grid: &mut Grid, rules: CollisionRules) -> Vec<Vec<(usize, f32, f32)>> {
+        let list_radius = self.radius + self.skin;
+        grid.update(nodes, list_radius);
+        let mut collisions_structure = calculate_collisions_structure_with_grid(
+            nodes, grid, rules.material_table, rules.connections_structure, rules.self_collision_hops
+        );
+        collisions_structure.iter_mut().enumerate().for_each(|(i, collisions)| {
+            collisions.retain(|(j, _dx, _v0)| nodes[i].position.distance(nodes[*j].position) < list_radius);
+        });
+        collisions_structure
+    }
+}
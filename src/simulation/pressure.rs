@@ -1,47 +1,42 @@
 use glam::Vec2;
-use crate::scene::Scene;
 
+use super::forces::ForceField;
 use super::node::Node;
 
-fn force_derivative_near_node(nodes: &[Node], connections_structure: &[(usize, f32, f32)], point: Vec2) -> Vec2 {
+/// Sum of forces of the node bonds acting on a node placed at `point`, taken from the bond potential of the force field
+fn force_near_node(nodes: &[Node], connections_structure: &[(usize, f32, f32)], force_field: &ForceField, point: Vec2) -> Vec2 {
     connections_structure.iter().fold(Vec2::new(0.0, 0.0), |accum, &(i, dx, v0)| {
-        let dir = nodes[i].position - point;
-        let l = dir.length();
-        let c = -7.0 * (dx / l).powi(8) + 13.0 * (dx / l).powi(14);
-        let v = dir.normalize() * 3.0 * (v0 / (dx * dx)) * c;
-        accum + v
-    })
-}
-
-fn force_derivative_near_node2(nodes: &[Node], connections_structure: &[(usize, f32, f32)], point: Vec2) -> Vec2 {
-    connections_structure.iter().fold(Vec2::new(0.0, 0.0), |accum, &(i, dx, v0)| {
-        let dir = nodes[i].position - point;
-        let l = dir.length();
-        let c = (dx / l).powi(7) + (dx / l).powi(13);
-        let v = dir.normalize() * 3.0 * (v0 / dx) * c;
-        accum + v
+        accum + force_field.bond_force(point, nodes[i].position, dx, v0)
     })
 }
 
+/// Pressure of every node estimated from divergence of the force of its bonds, sampled 0.0005 away from the node along both axes.
+///
+/// Forces come from the bond potential of the scene, so the value has units of force per length of that
+/// potential. Before bond potentials were selectable it was computed from the sum of magnitudes of both
+/// Lennard-Jones terms instead of the bond force, values logged then are not comparable with current ones.
 pub fn pressure_per_node(
     nodes: &[Node],
-    connections_structure: &[Vec<(usize, f32, f32)>]
+    connections_structure: &[Vec<(usize, f32, f32)>],
+    force_field: &ForceField
 ) -> Vec<f32> {
 
     let dx = 0.0005;
     nodes.iter().enumerate().map(|(index, n)| {
-        let top = force_derivative_near_node2(nodes, &connections_structure[index], n.position + Vec2::new(0.0, dx)).y;
-        let bottom = force_derivative_near_node2(nodes, &connections_structure[index], n.position + Vec2::new(0.0, -dx)).y;
-        let right = force_derivative_near_node2(nodes, &connections_structure[index], n.position + Vec2::new(dx, 0.0)).x;
-        let left = force_derivative_near_node2(nodes, &connections_structure[index], n.position + Vec2::new(-dx, 0.0)).x;
+        let top = force_near_node(nodes, &connections_structure[index], force_field, n.position + Vec2::new(0.0, dx)).y;
+        let bottom = force_near_node(nodes, &connections_structure[index], force_field, n.position + Vec2::new(0.0, -dx)).y;
+        let right = force_near_node(nodes, &connections_structure[index], force_field, n.position + Vec2::new(dx, 0.0)).x;
+        let left = force_near_node(nodes, &connections_structure[index], force_field, n.position + Vec2::new(-dx, 0.0)).x;
         let pressure = -0.25 * (-(right - left) - (top - bottom));
         pressure
     }).collect()
 }
 
+/// Largest pressure of all nodes, see `pressure_per_node`, written as `max_pressure` to the energy log
 pub fn max_pressure(
     nodes: &[Node],
-    connections_structure: &[Vec<(usize, f32, f32)>]
+    connections_structure: &[Vec<(usize, f32, f32)>],
+    force_field: &ForceField
 ) -> f32 {
-    pressure_per_node(nodes, connections_structure).iter().fold(f32::NEG_INFINITY, |a, &b| a.max(b))
+    pressure_per_node(nodes, connections_structure, force_field).iter().fold(f32::NEG_INFINITY, |a, &b| a.max(b))
 }
\ No newline at end of file
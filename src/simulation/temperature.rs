@@ -1,36 +1,26 @@
 use glam::Vec2;
+use super::environment::Environment;
+use super::forces::ForceField;
 use super::node::Node;
 use rayon::prelude::*;
+use serde::{Serialize, Deserialize};
 
 fn force_dot_position(
     nodes: &[Node],
-    connections_structure: &[Vec<(usize, f32, f32)>]
+    connections_structure: &[Vec<(usize, f32, f32)>],
+    environment: &Environment,
+    force_field: &ForceField
 ) -> Vec<f32> {
     let mut forces: Vec<Vec2> = vec![Vec2::new(0.0, 0.0); nodes.len()];
 
     nodes.iter().enumerate().for_each(|(i, n)| {
         connections_structure[i].iter().for_each(|(j, dx, v0)| {
-            let dir = nodes[*j].position - n.position;
-            let l = dir.length();
-    
-            let c = (dx / l).powi(7) - (dx / l).powi(13);
-            let v = dir.normalize() * 3.0 * (v0 / dx) * c;
-    
-            forces[i] += v;
+            forces[i] += force_field.bond_force(n.position, nodes[*j].position, *dx, *v0);
         });
     });
 
-    let v0 = super::general::WALL_REPULSION_V0;
-    let dx = super::general::WALL_REPULSION_DX;
-
     nodes.iter().enumerate().for_each(|(index, n)| {
-        let dir = glam::vec2(n.position.x, -1.0) - n.position;
-        let l = dir.length();
-
-        let c = (dx / l).powi(13);
-        let v = dir.normalize() * 3.0 * (v0 / dx) * c;
-
-        forces[index] -= v;
+        forces[index] += environment.wall_force(force_field, n.position);
     });
 
     forces
@@ -41,43 +31,64 @@ fn force_dot_position(
 }
 
 
-pub fn cached_avg_temperature_per_node(
-    nodes: &[Node],
-    connections_structure: &[Vec<(usize, f32, f32)>],
-    dt: f32
-) -> Vec<f32> {
-    const TEMPERATURE_CACHE_SIZE: usize = 500;
-    const RECORD_INTERVAL: f32 = 0.0005;
-    const TOTAL_DT: f32 = TEMPERATURE_CACHE_SIZE as f32 * RECORD_INTERVAL;
+const TEMPERATURE_CACHE_SIZE: usize = 500;
+const RECORD_INTERVAL: f32 = 0.0005;
+const TOTAL_DT: f32 = TEMPERATURE_CACHE_SIZE as f32 * RECORD_INTERVAL;
 
-    static mut TEMPERATURE_CACHE: Vec<Vec<f32>> = Vec::new();
-    static mut CURRENT_RECORD: usize = 0;
-    static mut CURRENT_DT: f32 = 0.0;
-    unsafe {
-        TEMPERATURE_CACHE.resize(nodes.len(), vec![0.0; TEMPERATURE_CACHE_SIZE]);
-        TEMPERATURE_CACHE
+/// Last `TEMPERATURE_CACHE_SIZE` records of force times position of every node, taken every `RECORD_INTERVAL`
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub struct TemperatureCache {
+    pub records: Vec<Vec<f32>>,
+    pub current_record: usize,
+    /// Simulated time since the last record
+    pub current_dt: f32,
+}
+
+impl TemperatureCache {
+    fn record(
+        &mut self,
+        nodes: &[Node],
+        connections_structure: &[Vec<(usize, f32, f32)>],
+        environment: &Environment,
+        force_field: &ForceField,
+        dt: f32
+    ) {
+        self.records.resize(nodes.len(), vec![0.0; TEMPERATURE_CACHE_SIZE]);
+        self.records
             .iter_mut()
             .for_each(|cache| cache.resize(TEMPERATURE_CACHE_SIZE, 0.0));
 
-        CURRENT_DT += dt;
+        self.current_dt += dt;
 
-        if CURRENT_DT > RECORD_INTERVAL {
-            CURRENT_RECORD = (CURRENT_RECORD + 1) % TEMPERATURE_CACHE_SIZE;
-            let current_temperature = force_dot_position(nodes, connections_structure);
-            TEMPERATURE_CACHE
+        if self.current_dt > RECORD_INTERVAL {
+            self.current_record = (self.current_record + 1) % TEMPERATURE_CACHE_SIZE;
+            let current_temperature = force_dot_position(nodes, connections_structure, environment, force_field);
+            let current_record = self.current_record;
+            self.records
                 .iter_mut()
                 .enumerate()
                 .for_each(|(node_index, cache)| {
-                    cache[CURRENT_RECORD] = current_temperature[node_index];
+                    cache[current_record] = current_temperature[node_index];
                 });
-            CURRENT_DT = 0.0;
+            self.current_dt = 0.0;
         }
     }
+}
+
+pub fn cached_avg_temperature_per_node(
+    cache: &mut TemperatureCache,
+    nodes: &[Node],
+    connections_structure: &[Vec<(usize, f32, f32)>],
+    environment: &Environment,
+    force_field: &ForceField,
+    dt: f32
+) -> Vec<f32> {
+    cache.record(nodes, connections_structure, environment, force_field, dt);
 
     let energy: Vec<f32> = nodes
         .iter()
         .enumerate()
-        .map(|(i, _n)| unsafe { -0.5 * TEMPERATURE_CACHE[i].iter().copied().sum::<f32>() / TOTAL_DT })
+        .map(|(i, _n)| -0.5 * cache.records[i].iter().copied().sum::<f32>() / TOTAL_DT)
         .collect();
 
     let avg_per_node: Vec<f32> = energy.par_iter().enumerate().map(|(i, _n)| {
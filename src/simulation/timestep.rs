@@ -0,0 +1,94 @@
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Deserialize};
+
+use super::forces::bond_stiffness;
+use super::node::Node;
+
+/// How `SimulationManager` chooses time step when adaptive dt is enabled
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+pub enum AdaptiveDtMethod {
+    /// Limits distance a node can travel in one step to `courant_number` times the shortest bond,
+    /// based on the largest velocity and acceleration
+    Cfl,
+    /// Compares one full step with two half steps and keeps the position difference
+    /// below `error_tolerance` times the shortest bond
+    StepDoubling,
+}
+
+impl AdaptiveDtMethod {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AdaptiveDtMethod::Cfl => "cfl",
+            AdaptiveDtMethod::StepDoubling => "step-doubling",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<AdaptiveDtMethod> {
+        [AdaptiveDtMethod::Cfl, AdaptiveDtMethod::StepDoubling].iter().copied().find(|method| method.name() == name)
+    }
+}
+
+/// Time step used by the manager over one update
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct DtRecord {
+    pub time: f32,
+    pub dt: f32,
+    /// Steps repeated with smaller dt because of too large error estimate
+    pub rejected_steps: u32,
+}
+
+/// Largest change of step between consecutive steps
+const MAX_GROWTH: f32 = 2.0;
+const MIN_SHRINK: f32 = 0.2;
+/// Margin keeping the next step below the estimated limit
+const SAFETY: f32 = 0.9;
+/// Fraction of the stability limit `2 / frequency` of explicit integrators used by CFL method
+const STABILITY_FRACTION: f32 = 0.2;
+
+/// Shortest of rest lengths of bonds and `fallback`
+pub fn shortest_bond(connections: &BTreeMap<(usize, usize), (f32, f32)>, fallback: f32) -> f32 {
+    connections.values().map(|(dx, _v0)| *dx).fold(f32::INFINITY, f32::min).min(fallback)
+}
+
+/// Highest angular frequency of a node oscillating on its bonds against fixed neighbours
+pub fn max_bond_frequency(nodes: &[Node], connections: &BTreeMap<(usize, usize), (f32, f32)>) -> f32 {
+    let mut stiffness = vec![0.0; nodes.len()];
+    connections.iter().for_each(|(&(i, j), &(dx, v0))| {
+        stiffness[i] += bond_stiffness(dx, v0);
+        stiffness[j] += bond_stiffness(dx, v0);
+    });
+    nodes.iter().zip(stiffness.iter()).map(|(n, k)| (k / n.mass).sqrt()).fold(0.0, f32::max)
+}
+
+/// Time step in which no node moves further than `courant_number * length` and which stays
+/// below the stability limit of bond oscillations, `f32::INFINITY` for scene at rest without bonds
+pub fn cfl_dt(nodes: &[Node], length: f32, courant_number: f32, max_frequency: f32) -> f32 {
+    let max_velocity = nodes.iter().map(|n| n.velocity.length()).fold(0.0, f32::max);
+    let max_acceleration = nodes.iter().map(|n| n.current_acceleration.length()).fold(0.0, f32::max);
+    let distance = courant_number * length;
+
+    let velocity_dt = if max_velocity > 0.0 { distance / max_velocity } else { f32::INFINITY };
+    let acceleration_dt = if max_acceleration > 0.0 { (2.0 * distance / max_acceleration).sqrt() } else { f32::INFINITY };
+    let stability_dt = if max_frequency > 0.0 { STABILITY_FRACTION * 2.0 / max_frequency } else { f32::INFINITY };
+    velocity_dt.min(acceleration_dt).min(stability_dt)
+}
+
+/// Largest distance between positions of the same node in two states of the scene
+pub fn max_position_difference(a: &[Node], b: &[Node]) -> f32 {
+    a.iter().zip(b.iter()).map(|(n, m)| (n.position - m.position).length()).fold(0.0, f32::max)
+}
+
+/// Step for the next attempt from the error of the last one, `order` is the order of the integrator
+pub fn step_doubling_dt(dt: f32, error: f32, tolerance: f32, order: i32) -> f32 {
+    if error <= 0.0 || !error.is_finite() {
+        return if error.is_finite() { dt * MAX_GROWTH } else { dt * MIN_SHRINK };
+    }
+    let factor = SAFETY * (tolerance / error).powf(1.0 / (order + 1) as f32);
+    dt * factor.clamp(MIN_SHRINK, MAX_GROWTH)
+}
+
+/// Limits change of the step relative to the previous one and keeps it within bounds
+pub fn limit_dt(dt: f32, previous_dt: f32, min_dt: f32, max_dt: f32) -> f32 {
+    dt.clamp(previous_dt * MIN_SHRINK, previous_dt * MAX_GROWTH).clamp(min_dt, max_dt)
+}
@@ -15,6 +15,9 @@ use crate::simulation::{self, pressure};
 use crate::rendering;
 use crate::simulation::manager::SimulationEngineEnum;
 use crate::simulation::manager::SimulationSettings;
+use crate::simulation::material::MaterialTable;
+use crate::simulation::integrator::Integrator;
+use crate::simulation::timestep::AdaptiveDtMethod;
 
 #[derive(Clone, Copy)]
 pub struct RenderingSettings {
@@ -29,25 +32,49 @@ pub struct RenderingSettings {
 
 pub fn run_with_gui(scene: Scene) {
 
-    let mut simulation_manager = {
+    let simulation_manager = {
     
         let simulation_settings = SimulationSettings {
             dt: 0.0,
             steps_per_frame: 5,
             engine: SimulationEngineEnum::None,
+            integrator: Integrator::VelocityVerlet,
             use_grid: false,
-            cell_size: scene.object_repulsion_dx * 2.5,
+            cell_size: MaterialTable::new(&scene).max_repulsion_dx() * 2.5,
+            use_neighbour_list: true,
+            neighbour_skin: MaterialTable::new(&scene).max_repulsion_dx() * 0.5,
             log_to_csv: true,
             log_interval: 0.01,
             use_backup: true,
             backup_interval: 0.1,
             use_auto_dt: true,
             auto_dt_factor: 1.1,
+            use_adaptive_dt: false,
+            adaptive_dt_method: AdaptiveDtMethod::Cfl,
+            courant_number: 0.01,
+            error_tolerance: 0.001,
+            min_dt: 0.000001,
+            max_dt: simulation::manager::MAX_DT,
+            use_healing: false,
+            healing_distance: scene.object_repulsion_dx,
+            healing_max_speed: 0.5,
+            use_welding: false,
+            use_self_collision: false,
+            self_collision_hops: 3,
+            deterministic: false,
         };
     
         simulation::manager::SimulationManager::new(simulation_settings, scene)
     };
 
+    let mut simulation_manager = match simulation_manager {
+        Ok(simulation_manager) => simulation_manager,
+        Err(errors) => {
+            eprintln!("invalid scene:\n{}", crate::scene::validation::format_errors(&errors));
+            return;
+        }
+    };
+
     let mut rendering_settings = RenderingSettings {
         coloring_mode: graphics::ColoringMode::KineticEnergy,
         gui_active: true,
@@ -120,7 +147,7 @@ pub fn run_with_gui(scene: Scene) {
                 if current_log_dt > simulation_manager.settings.log_interval {
                     
                     let (kinetic, gravity, lennjon, wallrep, objrepu) =
-                        simulation::energy::calculate_total_energy(&simulation_manager.scene);
+                        simulation_manager.total_energy();
     
                     println!("{:.2}\t{:.2}\t{:.2}\t{:.2}\t{:.2}\t{:.2}", 
                         simulation_manager.total_simulation_time, 
@@ -131,7 +158,7 @@ pub fn run_with_gui(scene: Scene) {
                         objrepu
                     );
 
-                    let max_pressure = pressure::max_pressure(&simulation_manager.scene.nodes, &simulation_manager.connections_structure);
+                    let max_pressure = pressure::max_pressure(&simulation_manager.scene.nodes, &simulation_manager.connections_structure, &simulation_manager.scene.force_field);
 
                     csv_writer
                         .write_record(&[
@@ -156,7 +183,7 @@ pub fn run_with_gui(scene: Scene) {
             // create egui interface
             egui.begin_frame(&display);
             draw_rendering_settings(egui, rendering_settings);
-            draw_simulation_settings(egui, current_fps, &mut simulation_manager.settings);
+            draw_simulation_settings(egui, current_fps, simulation_manager.neighbour_list.rebuilds, &mut simulation_manager.settings);
             let (_needs_repaint, egui_shapes) = egui.end_frame(&display);
     
             let mut target = display.draw();
@@ -169,7 +196,7 @@ pub fn run_with_gui(scene: Scene) {
                 &mut target, 
                 &rendering_settings,
                 screen_ratio,
-                &simulation_manager,
+                &mut simulation_manager,
             );
 
             // draw egui
@@ -326,7 +353,7 @@ fn draw_rendering_settings(egui: &mut egui_glium::EguiGlium, rendering_settings:
 }
 
 
-fn draw_simulation_settings(egui: &mut egui_glium::EguiGlium, current_fps: u32, simulation_settings: &mut SimulationSettings) {
+fn draw_simulation_settings(egui: &mut egui_glium::EguiGlium, current_fps: u32, neighbour_list_rebuilds: u64, simulation_settings: &mut SimulationSettings) {
     egui::Window::new("Simulation settings").show(egui.ctx(), |ui| {
         ui.label(format!("FPS: {}", current_fps));
 
@@ -379,6 +406,16 @@ fn draw_simulation_settings(egui: &mut egui_glium::EguiGlium, current_fps: u32,
             ui.selectable_value(&mut simulation_settings.engine, SimulationEngineEnum::OpenCl, "OpenCL");
         }
 
+        ui.checkbox(&mut simulation_settings.deterministic, "Deterministic CPU engines");
+
+        ui.separator();
+        ui.label("Integrator");
+        ui.horizontal_wrapped(|ui| {
+            Integrator::ALL.iter().for_each(|integrator| {
+                ui.selectable_value(&mut simulation_settings.integrator, *integrator, integrator.name());
+            });
+        });
+
         ui.separator();
         ui.horizontal(|ui| {
             ui.checkbox(&mut simulation_settings.use_grid, "Use grid");
@@ -389,6 +426,41 @@ fn draw_simulation_settings(egui: &mut egui_glium::EguiGlium, current_fps: u32,
                 &mut simulation_settings.cell_size,
                 RangeInclusive::new(0.02, 0.3),
             ));
+            ui.checkbox(&mut simulation_settings.use_neighbour_list, "Neighbour list");
+            if simulation_settings.use_neighbour_list {
+                ui.label("Skin");
+                ui.add(egui::Slider::new(
+                    &mut simulation_settings.neighbour_skin,
+                    RangeInclusive::new(0.0, 0.1),
+                ));
+                ui.label(format!("Rebuilds: {}", neighbour_list_rebuilds));
+            }
+        }
+
+        ui.separator();
+        ui.checkbox(&mut simulation_settings.use_healing, "Bond healing");
+        if simulation_settings.use_healing {
+            ui.label("Capture distance");
+            ui.add(egui::Slider::new(
+                &mut simulation_settings.healing_distance,
+                RangeInclusive::new(0.001, 0.1),
+            ));
+            ui.label("Max relative speed");
+            ui.add(egui::Slider::new(
+                &mut simulation_settings.healing_max_speed,
+                RangeInclusive::new(0.0, 5.0),
+            ));
+            ui.checkbox(&mut simulation_settings.use_welding, "Welding of different objects");
+        }
+
+        ui.separator();
+        ui.checkbox(&mut simulation_settings.use_self_collision, "Self-collision");
+        if simulation_settings.use_self_collision {
+            ui.label("Excluded bond distance");
+            ui.add(egui::Slider::new(
+                &mut simulation_settings.self_collision_hops,
+                RangeInclusive::new(1, 10),
+            ));
         }
 
         ui.separator();
@@ -418,5 +490,35 @@ fn draw_simulation_settings(egui: &mut egui_glium::EguiGlium, current_fps: u32,
                 ));
             }
         }
+
+        ui.separator();
+        ui.checkbox(&mut simulation_settings.use_adaptive_dt, "Adaptive dt");
+        if simulation_settings.use_adaptive_dt {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut simulation_settings.adaptive_dt_method, AdaptiveDtMethod::Cfl, "CFL");
+                ui.selectable_value(&mut simulation_settings.adaptive_dt_method, AdaptiveDtMethod::StepDoubling, "Step doubling");
+            });
+            match simulation_settings.adaptive_dt_method {
+                AdaptiveDtMethod::Cfl => {
+                    ui.label("Courant number");
+                    ui.add(egui::Slider::new(
+                        &mut simulation_settings.courant_number,
+                        RangeInclusive::new(0.001, 0.1),
+                    ).logarithmic(true));
+                }
+                AdaptiveDtMethod::StepDoubling => {
+                    ui.label("Error tolerance");
+                    ui.add(egui::Slider::new(
+                        &mut simulation_settings.error_tolerance,
+                        RangeInclusive::new(0.0001, 0.01),
+                    ).logarithmic(true));
+                }
+            }
+            ui.label("Max dt");
+            ui.add(egui::Slider::new(
+                &mut simulation_settings.max_dt,
+                RangeInclusive::new(simulation_settings.min_dt, 0.0005),
+            ).logarithmic(true));
+        }
     });
 }
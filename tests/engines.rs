@@ -0,0 +1,124 @@
+use glam::Vec2;
+
+use mylib::scene::Scene;
+use mylib::scene::builder::{NodeProperties, SceneBuilder};
+use mylib::simulation::collider::{Collider, ContactLaw, Motion, Shape};
+use mylib::simulation::cross_validation::{self, Tolerances};
+use mylib::simulation::environment::{Environment, Wall};
+use mylib::simulation::integrator::Integrator;
+use mylib::simulation::manager::{SimulationEngineEnum, SimulationSettings};
+
+const STEPS: u32 = 300;
+
+/// Block falling on the floor next to a wall and a disc flying into it, covers bonds, collisions and walls
+fn collision_scene() -> Scene {
+    let environment = Environment::default().with_wall(Wall::right(0.25, 100.0, 0.04));
+    let mut builder = SceneBuilder::new(0.04, 100.0).with_environment(environment);
+
+    builder.add_rectangle(8, 8, Vec2::new(0.0, -0.96), NodeProperties::new(0.03, 1.0, 1.0, 40.0));
+    let disc = builder.add_circle(3, Vec2::new(-0.15, -0.85), NodeProperties::new(0.03, 1.0, 1.0, 40.0));
+    builder.set_velocity(disc, Vec2::new(3.0, 0.0));
+
+    builder.build().unwrap()
+}
+
+/// Block on the floor pushed by a descending rotating press and an oscillating indenter, covers moving colliders
+fn collider_scene() -> Scene {
+    let press = Collider::new(Shape::Box { half_extents: Vec2::new(0.2, 0.02) }, Vec2::new(0.1, -0.68), 0.0, 100.0, 0.04)
+        .with_motion(Motion::Constant { velocity: Vec2::new(0.0, -1.0), angular_velocity: 2.0 })
+        .with_law(ContactLaw::Linear);
+    let indenter = Collider::new(Shape::Circle { radius: 0.03 }, Vec2::new(-0.09, -0.85), 0.0, 100.0, 0.04)
+        .with_motion(Motion::Oscillating { amplitude: Vec2::new(0.05, 0.0), angular_amplitude: 0.0, frequency: 10.0 })
+        .with_law(ContactLaw::Hertz);
+    let environment = Environment::default().with_collider(press).with_collider(indenter);
+    let mut builder = SceneBuilder::new(0.04, 100.0).with_environment(environment);
+
+    builder.add_rectangle(8, 8, Vec2::new(0.0, -0.96), NodeProperties::new(0.03, 1.0, 1.0, 40.0));
+
+    builder.build().unwrap()
+}
+
+fn settings() -> SimulationSettings {
+    SimulationSettings {
+        steps_per_frame: 5,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn engines_agree_within_tolerances() {
+    let engines = cross_validation::available_engines();
+    let report = cross_validation::cross_validate(settings(), &collision_scene(), &engines, STEPS).unwrap();
+
+    assert_eq!(report.divergences.len(), engines.len() - 1);
+    assert!(report.is_within(&Tolerances::default()), "{}", report);
+}
+
+#[test]
+fn engines_agree_for_every_integrator() {
+    let engines = [
+        SimulationEngineEnum::Cpu,
+        SimulationEngineEnum::CpuMultithread,
+        SimulationEngineEnum::CpuMultithreadSingleKernel,
+    ];
+    for integrator in Integrator::ALL {
+        let settings = SimulationSettings { integrator, ..settings() };
+        let report = cross_validation::cross_validate(settings, &collision_scene(), &engines, STEPS).unwrap();
+        assert!(report.is_within(&Tolerances::default()), "{:?}\n{}", integrator, report);
+    }
+}
+
+#[test]
+fn engines_agree_with_moving_colliders() {
+    let engines = cross_validation::available_engines();
+    let report = cross_validation::cross_validate(settings(), &collider_scene(), &engines, STEPS).unwrap();
+
+    assert!(report.is_within(&Tolerances::default()), "{}", report);
+}
+
+/// Every CPU engine runs its own code path, deterministic mode only makes the single threaded engine
+/// sum bonds per node, so this compares the actual summation order of the engines
+#[test]
+fn deterministic_cpu_engines_are_identical() {
+    let engines = [
+        SimulationEngineEnum::Cpu,
+        SimulationEngineEnum::CpuMultithread,
+        SimulationEngineEnum::CpuMultithreadSingleKernel,
+    ];
+    let settings = SimulationSettings { deterministic: true, ..settings() };
+    let exact = Tolerances { position: 0.0, velocity: 0.0, energy: 0.0 };
+
+    for scene in [collision_scene(), collider_scene()] {
+        let report = cross_validation::cross_validate(settings, &scene, &engines, STEPS).unwrap();
+        assert!(report.is_within(&exact), "{}", report);
+    }
+}
+
+#[test]
+fn repeated_runs_are_identical() {
+    let scene = collision_scene();
+    let first = cross_validation::run_engine(settings(), scene.clone(), SimulationEngineEnum::Cpu, STEPS).unwrap();
+    let second = cross_validation::run_engine(settings(), scene, SimulationEngineEnum::Cpu, STEPS).unwrap();
+
+    assert_eq!(first.scene.nodes, second.scene.nodes);
+}
+
+#[test]
+fn divergence_is_detected() {
+    let scene = collision_scene();
+    let reference = cross_validation::run_engine(settings(), scene.clone(), SimulationEngineEnum::Cpu, STEPS).unwrap();
+    let changed_settings = SimulationSettings { dt: 2.0 * settings().dt, ..settings() };
+    let changed = cross_validation::run_engine(changed_settings, scene, SimulationEngineEnum::Cpu, STEPS).unwrap();
+
+    let divergence = cross_validation::divergence(SimulationEngineEnum::Cpu, &reference, &changed);
+    assert!(!divergence.is_within(&Tolerances::default()), "{:?}", divergence);
+}
+
+#[test]
+fn invalid_scene_is_rejected() {
+    let mut scene = collision_scene();
+    scene.nodes[0].mass = 0.0;
+
+    let result = cross_validation::cross_validate(settings(), &scene, &cross_validation::available_engines(), 1);
+    assert!(result.is_err());
+}